@@ -0,0 +1,21 @@
+// Every parser that sees frames straight off the wire. None of them may
+// panic, whatever arrives. Run with: cargo +nightly fuzz run parse_frame
+#![no_main]
+
+use arp_scan::{dhcp, ndp, neighbor, packet};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|frame: &[u8]| {
+    if let Some(parsed) = packet::parse_arp_frame(frame) {
+        assert!(parsed.payload_end() <= frame.len());
+    }
+    if dhcp::is_dhcp_frame(frame) {
+        let _ = dhcp::parse_dhcp_frame(frame);
+    }
+    if ndp::is_ndp_frame(frame) {
+        let _ = ndp::parse_ndp_frame(frame);
+    }
+    if neighbor::is_neighbor_frame(frame) {
+        let _ = neighbor::parse_neighbor_frame(frame);
+    }
+});
@@ -0,0 +1,67 @@
+/*
+ * C interface to the arp_scan library. Build with
+ *
+ *     cargo build --release --no-default-features --features ffi
+ *
+ * and link against target/release/libarp_scan.so (arp_scan.dll on
+ * Windows). Scanning needs the same raw-socket privileges as the
+ * command-line tool.
+ *
+ * Functions returning int return 0 on success and -1 on failure, with
+ * arpscan_last_error() describing the failure.
+ */
+#ifndef ARPSCAN_H
+#define ARPSCAN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct {
+    uint8_t ip[4];
+    uint8_t mac[6];
+    /* Round-trip time in milliseconds, or -1 when unknown */
+    double rtt_ms;
+} ArpscanHost;
+
+typedef struct {
+    ArpscanHost *hosts;
+    size_t len;
+} ArpscanResults;
+
+typedef void (*ArpscanHostCallback)(const ArpscanHost *host, void *user);
+
+/*
+ * Scan target (an address, range or CIDR) and store the hosts that
+ * answered, sorted by address, in *out. interface may be NULL to use the
+ * default interface. Release the results with arpscan_free_results().
+ */
+int arpscan_scan(const char *target, const char *interface,
+                 uint32_t timeout_ms, ArpscanResults *out);
+
+/*
+ * Like arpscan_scan(), but calls callback for each host as soon as it
+ * answers. host is only valid during the call. Blocks until the scan is
+ * finished. Fails without scanning if callback is NULL.
+ */
+int arpscan_scan_stream(const char *target, const char *interface,
+                        uint32_t timeout_ms, ArpscanHostCallback callback,
+                        void *user);
+
+/* Free results filled in by arpscan_scan(). NULL is ignored. */
+void arpscan_free_results(ArpscanResults *results);
+
+/*
+ * Message for the last failure on the calling thread, or NULL. Valid until
+ * the next failing call on the same thread.
+ */
+const char *arpscan_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ARPSCAN_H */
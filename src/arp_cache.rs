@@ -0,0 +1,50 @@
+use std::net::Ipv4Addr;
+
+use pnet_base::MacAddr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub ip: Ipv4Addr,
+    pub mac: MacAddr,
+    pub interface: String,
+}
+
+// Parse the complete entries out of Linux's /proc/net/arp. Incomplete ones
+// (flags without ATF_COM, all-zero MAC) carry no binding to verify.
+pub fn parse_proc_net_arp(text: &str) -> Vec<CacheEntry> {
+    let mut entries = Vec::new();
+    for line in text.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 6 {
+            continue;
+        }
+        let flags = u32::from_str_radix(fields[2].trim_start_matches("0x"), 16).unwrap_or(0);
+        let (Ok(ip), Ok(mac)) = (fields[0].parse::<Ipv4Addr>(), fields[3].parse::<MacAddr>()) else {
+            continue;
+        };
+        if flags & 0x2 == 0 || mac == MacAddr::zero() {
+            continue;
+        }
+        entries.push(CacheEntry {
+            ip,
+            mac,
+            interface: fields[5].to_string(),
+        });
+    }
+    entries
+}
+
+// The operating system's IPv4 neighbor cache. Only Linux is supported so
+// far; other platforms return nothing.
+pub fn entries() -> Vec<CacheEntry> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/net/arp")
+            .map(|text| parse_proc_net_arp(&text))
+            .unwrap_or_default()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
@@ -0,0 +1,1281 @@
+use pnet::util::MacAddr;
+
+use arp_scan::defend::DefendPolicy;
+use arp_scan::mac::{self, MacAddress, MacFormat, MacStyle};
+use arp_scan::monitor::{Debounce, FlapThresholds, PresenceModel, QuietHours};
+use arp_scan::passive::StormThresholds;
+use arp_scan::packet::Vlan;
+use arp_scan::scan::{ChannelTuning, DupPolicy, ProbeStyle};
+use arp_scan::target::{self, CidrV4};
+use arp_scan::vendor;
+
+use crate::control;
+use crate::output::Template;
+
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const MIN_BUFFER: usize = 1518;
+const MAX_BUFFER: usize = 64 << 20;
+
+pub const USAGE: &str = "\
+Usage: arp_scan [OPTIONS] [TARGET]
+       arp_scan compare --nmap <FILE> [OPTIONS] [TARGET]
+       arp_scan monitor [OPTIONS] [TARGET]
+       arp_scan passive [OPTIONS]
+       arp_scan defend [--policy <once|always|log>] [OPTIONS]
+       arp_scan merge [OPTIONS] <SNAPSHOT>...
+       arp_scan note [MAC [TEXT | --clear]]
+       arp_scan mac validate <MAC>... | random [--vendor <PREFIX>] [--count <N>]
+       arp_scan oui <MAC|PREFIX>... | --search <TEXT> [--oui-file <FILE>]
+       arp_scan ctl <rescan|pause|resume|status> [--control-socket <PATH>]
+       arp_scan install-service [--print] [OPTIONS] [TARGET]
+       arp_scan uninstall-service
+       arp_scan schema
+       arp_scan self-test
+
+Commands:
+  compare                 Scan TARGET and compare the responders with the
+                          hosts an nmap XML report (-oX) found up
+  monitor                 Sweep TARGET repeatedly, listening passively in
+                          between, and report hosts that appear, leave or
+                          change MAC, flapping bindings and ARP storms
+  passive                 Listen without sending anything: list hosts as
+                          they show up in ARP and IPv6 neighbor discovery
+                          traffic (one row per device), name them from
+                          their DHCP broadcasts (hostname and option 55
+                          fingerprint), report the switch and port from
+                          LLDP/CDP announcements, and alert on request
+                          floods (another scanner, a loop or an attack)
+  defend                  Guard the interface's own IPv4 addresses (RFC
+                          5227): when another host sends ARP claiming one
+                          of them, report it and answer with a gratuitous
+                          ARP announcement. --policy once (default) stops
+                          defending an address when the other host keeps
+                          claiming it within 10 seconds, always defends at
+                          most once a second, log only reports
+  merge                   Print the results of several --save snapshots
+                          (different segments or sites) as one. When an
+                          address appears more than once, the most recent
+                          sighting wins. Takes the same output options as
+                          a scan, and --save for the combined result.
+                          A MAC seen on different segments within
+                          --roam-window is shown once, where it was seen
+                          last, as a device that moved
+  ctl                     Control a monitor started with --control: sweep
+                          now, pause or resume probing (passive listening
+                          continues while paused), or show its status
+                          and health (send and receive errors, skipped
+                          probes, interface drops, sweep time)
+  install-service         Run monitor with the given options as a systemd
+                          service, started now and at every boot: writes
+                          /etc/systemd/system/arp_scan.service, which runs
+                          it as a dynamic user with only CAP_NET_RAW and
+                          restarts it if it fails or its watchdog goes
+                          quiet, and enables it. --print only shows the
+                          unit. With --control the socket is
+                          /run/arp_scan/arp_scan.sock. Install arp_scan
+                          where any user can run it, e.g. /usr/local/bin.
+                          Linux only
+  uninstall-service       Stop, disable and remove that service
+  note                    Attach a note to a MAC address ('flaky PSU').
+                          Notes are kept between runs and shown with the
+                          host in later scans. Without TEXT prints the
+                          note, without MAC lists all notes. Stored in
+                          $ARPSCAN_NOTES or arp_scan/notes.txt in the user
+                          data directory (the invoking user's under sudo)
+  mac                     Utilities for provisioning scripts. validate
+                          checks MAC addresses in any common notation
+                          (colons, dashes, Cisco dots, bare hex), prints
+                          each in canonical form with its kind (unicast,
+                          multicast, broadcast) and whether it is
+                          locally administered, and exits with status 1
+                          if any is invalid. random prints --count (default
+                          1) new unicast addresses: locally administered
+                          by default (--vendor locally-administered), or
+                          starting with the given prefix (--vendor
+                          00:1b:21). Both take --mac-format
+  oui                     Look up the vendor of MAC addresses or prefixes
+                          (00:1b:21, 001B21, 70:b3:d5:1f:3/36) in the
+                          vendor database, without scanning. --search
+                          lists the prefixes whose vendor name contains
+                          TEXT (any case). Exits with status 1 when
+                          nothing is found
+  schema                  Print the JSON Schema for --output json
+  self-test               Check that scanning works on this system (Linux,
+                          needs root): scans simulated hosts on a veth
+                          pair in a temporary network namespace and
+                          checks sending, receiving, parsing, vendor
+                          lookup and saving. Exits with status 1 if any
+                          check fails
+
+TARGET is a network in CIDR notation (192.168.1.0/24, or with a netmask:
+192.168.1.0/255.255.255.0), a single IP, a range (192.168.1.10-50), a
+host name (nas.local; .local names are also tried over mDNS), or several
+of these separated by commas. Entries starting with '!' are excluded
+(quote them for the shell): '10.0.0.0/24,!10.0.0.1'. Use '-' to read
+targets from stdin, one per line. TARGET may also be given as --target
+TARGET. If it is omitted you will be prompted for one, unless stdin is
+not a terminal or --no-prompt is given, in which case it is an error.
+Several entries are probed side by side under the one --pps budget, and
+the table shows each entry's hosts in its own section followed by
+per-entry counts.
+
+Options:
+  --target <TARGET>       The same as the TARGET argument
+  --no-prompt             Never ask for input; fail instead. Prompts are
+                          already skipped when stdin is not a terminal
+  --save <FILE>           Also save the complete results to FILE, to print
+                          again later with --load
+  --load <FILE>           Print results saved with --save instead of
+                          scanning. All output options apply. May be
+                          repeated, which is the same as merge
+  --roam-window <TIME>    When merging, how close together sightings of
+                          one MAC on different segments must be to count
+                          as the device roaming between them, in seconds
+                          or with an s, m or h suffix, e.g. 5m. Without it
+                          every sighting is kept
+  --redact <mac|vendor>   Mask device identifiers in the output. 'mac' hides
+                          the lower 3 bytes of each MAC, 'vendor' hides the
+                          manufacturer. Both may be given, comma separated.
+  --mac-format <FORMAT>   How to print MAC addresses: colon (default), dash,
+                          dot (Cisco aabb.ccdd.eeff) or bare, optionally
+                          with upper or lower: --mac-format dot,upper.
+                          --export ethers and dnsmasq only take colon (in
+                          either case), as their file formats require
+  --pad-ip                Zero-pad addresses in tables to a fixed width
+                          (192.168.001.007)
+  --strip-suffixes        Drop legal suffixes such as 'Co., Ltd.' or 'Inc.'
+                          from vendor names
+  --show-country          Add a Country column with the ISO code of the
+                          manufacturer's registered address (IEEE oui.txt
+                          only)
+  --raw-vendor            Print vendor names exactly as found in the
+                          database, without any cleanup
+  --output <FORMAT>       Output format: table (default), json, csv or
+                          markdown. JSON and CSV exports include scan
+                          metadata (scan ID, timestamps, interface, targets
+                          and settings); markdown is a GitHub-flavored
+                          table plus the --summary overview, for wikis,
+                          tickets and chat
+  --output html <FILE>    Write a self-contained HTML report to FILE: scan
+                          metadata, overview charts and a sortable table
+  --format <TEMPLATE>     Print one line per host using a template instead of
+                          the results table. Placeholders: {ip}, {mac},
+                          {vendor}, {rtt} (milliseconds). \\t and \\n are
+                          expanded, e.g. --format '{ip}\\t{mac}'
+  --export <FORMAT>       Print the results as configuration instead of the
+                          table: 'ethers' (/etc/ethers, for arp -f),
+                          'dnsmasq' (dhcp-host= lines) or
+                          'ansible-inventory' (YAML, grouped by vendor), or
+                          as a network map: 'graph' (Graphviz DOT, e.g.
+                          | dot -Tsvg) or 'mermaid'. Maps group hosts by
+                          vendor, mark the default gateway and draw shared
+                          or spoofed MACs as red edges
+  --label <TARGET=GROUP>  Put hosts in TARGET (IP, CIDR or range) into
+                          inventory or map group GROUP instead of their
+                          vendor's group. May be repeated; the first match
+                          wins
+  --enrich-exec <COMMAND> Run COMMAND once per host with a JSON object (ip,
+                          mac, vendor, interface) on stdin. A JSON object it
+                          prints on stdout is merged into the output
+  --analyze-padding       Report reply frame sizes and non-zero padding bytes
+                          per host (can reveal buggy stacks or Etherleak)
+  --topology-hints        Group hosts by reply latency into those probably
+                          on our own switch, further away, or on Wi-Fi.
+                          A rough guess; more --rounds make it better
+  --summary               Append an overview: hosts per vendor and per /24,
+                          randomized MACs, hosts with a note, and the
+                          response rate (table and JSON;
+                          markdown and HTML always have it)
+  --pad-to <BYTES>        Zero-pad ARP requests to this frame size (42-9216,
+                          excluding FCS) to test minimum/odd-size handling
+  --unicast-verify        Instead of sweeping, send a unicast ARP request to
+                          the cached MAC of every entry in the OS ARP cache
+                          (limited to TARGET if given) and report which
+                          entries fail to confirm. Linux only
+  --rounds <N>            Sweep the targets N times and report how many
+                          rounds each host answered (e.g. 7/10), and
+                          which attempt first got an answer (--summary,
+                          --verbose, JSON and CSV)
+  --pps <N>               Send at most N probes per second
+  --adaptive-rate         Halve the send rate each time the send buffer
+                          overflows (useful on small devices). Without
+                          --pps it starts at 10000 per second
+  --probe-style <STYLE>   normal (default) sends requests from our address;
+                          dad sends RFC 5227 address conflict probes from
+                          0.0.0.0, which targets answer without caching us
+  --dup-policy <POLICY>   Which reply to keep when a host answers more
+                          than once: first, last (default), or flag to keep
+                          the first and warn about any other MAC that
+                          answered. Duplicates are counted either way and
+                          shown with --verbose and in JSON and CSV
+  --vlan <ID|OUTER:INNER> Tag requests with 802.1Q VLAN ID, or with an
+                          802.1ad outer and 802.1Q inner tag (QinQ, e.g.
+                          100:200), for scanning through a trunk port.
+                          Replies tagged for other VLANs are ignored. A
+                          VLAN sub-interface (eth0.100) needs no --vlan;
+                          pass it to -i instead
+  --rx-buffer <BYTES>     Receive buffer size for the capture channel
+                          (default 4096). On macOS and BSD this is the BPF
+                          buffer; raise it if replies from large segments
+                          go missing. On Linux it only sizes each read
+  --tx-buffer <BYTES>     Send buffer size for the capture channel
+                          (default 4096)
+  --read-timeout <MS>     How long each capture read waits before checking
+                          whether the scan is over (default 100)
+  --drop-privileges       Give up root as soon as the capture channel is
+                          open: become the user who ran sudo, doas or
+                          pkexec (or nobody), or clear capabilities when
+                          not root. Parsing, --enrich-exec and output then
+                          run unprivileged, so files written by --save or
+                          --output html must be writable by that user.
+                          Scans, compare, monitor (Linux only), passive
+                          and defend on a single interface; Unix only
+  --sandbox               Once the capture channel is open (and after
+                          --drop-privileges), restrict the process to the
+                          system calls needed for packet I/O, the clock,
+                          memory and files with a seccomp filter. Same
+                          limits as --drop-privileges, and not with
+                          --enrich-exec; Linux on x86_64 and aarch64 only
+  --netns <NS>            Scan from another network namespace (Linux, needs
+                          root): /proc/<pid>/ns/net, e.g. a container's
+                          (docker inspect -f '{{.State.Pid}}' NAME), or a
+                          name from ip netns. Interfaces are then looked
+                          up there too, --list-interfaces included
+  --fd <N>                Capture and send on the AF_PACKET socket already
+                          open as file descriptor N, instead of opening
+                          one. A privileged parent or container runtime
+                          can create the socket and leave it open across
+                          exec, so this process needs no root or
+                          CAP_NET_RAW of its own. Any
+                          interface it was bound to is replaced by the
+                          scanning one. Single interface, not with
+                          --tx-buffer; Linux only
+  --lock-dir <DIR>        Keep other arp_scan processes from scanning the
+                          same interface at the same time, with a lock
+                          file per interface in DIR (e.g. /run/lock).
+                          Within one process this is always enforced.
+                          Scans, compare and monitor sweeps; Unix only
+  --max-duration <TIME>   Finish within TIME (e.g. 30s or 2m) however large
+                          the scan: sending, waiting for replies and
+                          --enrich-exec each get a share, and targets that
+                          don't fit are skipped with a warning
+  --max-hosts <N>         Stop as soon as N hosts have answered
+  --exclude <TARGET>      Leave these addresses out of the sweep. May be
+                          repeated
+  --shuffle               Probe the targets in random order instead of
+                          sequentially
+  --quick                 Rough population estimate in about 2 seconds: probe
+                          only likely gateways, send a broadcast ping and
+                          count hosts seen in background ARP traffic
+  -i, --interface <NAME>  Scan from this interface. On Windows the friendly
+                          name ('Ethernet', 'Wi-Fi') or the device GUID may
+                          be used instead of the \\Device\\NPF_{...} name.
+                          Repeat to scan from several interfaces at once;
+                          results are then grouped by interface
+  --list-interfaces       List network interfaces and exit
+  --oui-file <FILE>       Vendor database to use. Otherwise $ARPSCAN_OUI,
+                          the user data directory (arp_scan/oui.txt under
+                          $XDG_DATA_HOME or %APPDATA%), the executable's
+                          directory and the current directory are searched
+  --require-oui           Fail instead of warning when no vendor database
+                          can be loaded
+  --nmap <FILE>           nmap XML report to compare against (compare only)
+  --duration <SECS>       Stop after SECS seconds (passive, monitor and defend;
+                          default: run until interrupted)
+  --interval <SECS>       Time between sweeps (monitor only, default 60)
+  --flap-changes <N>      Report an IP or MAC as flapping once its binding
+                          changed N times within the flap window (monitor
+                          only, default 3)
+  --flap-window <SECS>    Flap window (monitor only, default 300)
+  --miss-threshold <N>    Report a host gone only after it missed N sweeps
+                          in a row (monitor only, default 1)
+  --grace <TIME>          ...and was not heard from for TIME, in seconds or
+                          with an s, m or h suffix, e.g. 10m (monitor only,
+                          default 0)
+  --arrive-threshold <N>  Report a new host only after it answered N sweeps
+                          in a row (monitor only, default 1)
+  --presence <TIME>       Instead of the three options above, give each
+                          host a presence score that rises when it is
+                          heard and halves every TIME in between (monitor
+                          only, e.g. 10m). Far fewer comings and goings
+                          for phones and other sleepy devices
+  --presence-thresholds <PRESENT,AWAY>
+                          Scores at which a host becomes present and away
+                          (default 0.6,0.2; implies --presence 10m). One
+                          reply scores 0.7, two in a row 0.91
+  --quiet-hours <HH:MM-HH:MM>
+                          Send no probes during this daily window, e.g.
+                          22:00-06:00; passive listening continues (monitor
+                          only; local time, UTC on Windows)
+  --trap <TARGET>         Watch unused addresses as a tripwire, e.g.
+                          192.168.1.250-252: they are probed with every
+                          sweep, and anything asking for or answering for
+                          them is reported at once (monitor only,
+                          repeatable)
+  --control               Accept ctl commands on a Unix socket (monitor
+                          only). The socket is $XDG_RUNTIME_DIR/arp_scan.sock,
+                          or without it /run/arp_scan.sock for root and
+                          arp_scan.sock in a private arp_scan-<UID>
+                          directory under /tmp for other users, unless
+                          --control-socket is given
+  --control-socket <PATH> Control socket path (monitor and ctl)
+  --heartbeat-file <PATH> Write the time to PATH every 10 seconds while
+                          the monitor is making progress, so a supervisor
+                          can restart it once the file goes stale (monitor
+                          only). Under systemd with Type=notify the monitor
+                          also reports READY=1 and, with WatchdogSec set,
+                          pings the watchdog, sweeps included
+  --email-to <ADDR>       Mail hosts that appear, leave or change MAC and
+                          alerts to ADDR (monitor only, repeatable). What
+                          happens within a minute of the first change goes
+                          out as one message. Sent with the local sendmail
+                          (postfix, msmtp, ...), which holds the SMTP
+                          settings
+  --local-subnet <CIDR>   Also count CIDR as belonging on the segment
+                          (passive only, repeatable). Hosts using
+                          addresses outside it and the interface's own
+                          subnets are flagged as off-subnet
+  --dhcp-snooping <FILE>  Show the switch port of each host from a DHCP
+                          snooping binding table: 'show ip dhcp snooping
+                          binding' output or mac,switch,port[,vlan] lines
+                          (scans and passive). Passive also uses option 82
+                          from relayed DHCP it hears
+  --storm-rate <N>        Alert when one sender makes more than N ARP
+                          requests per second (passive and monitor,
+                          default 50)
+  --storm-total <N>       Alert when the segment carries more than N ARP
+                          requests per second (passive and monitor,
+                          default 200)
+  -v, --verbose           Show details, such as every target a probe could
+                          not be sent to
+  -h, --help              Print this help";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+    // A standalone HTML report, written to Options::report
+    Html,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ethers,
+    Dnsmasq,
+    AnsibleInventory,
+    // Network maps: Graphviz DOT and Mermaid
+    Graph,
+    Mermaid,
+}
+
+impl ExportFormat {
+    // Maps are for people, so they may redact; the other formats configure
+    // machines and need the real addresses.
+    fn is_map(self) -> bool {
+        matches!(self, ExportFormat::Graph | ExportFormat::Mermaid)
+    }
+
+    // ethers(5) and dnsmasq's dhcp-host only read colon-separated MACs. Hex
+    // case is free, so --mac-format upper still applies.
+    fn needs_colons(self) -> bool {
+        matches!(self, ExportFormat::Ethers | ExportFormat::Dnsmasq)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub target: Option<String>,
+    pub no_prompt: bool,
+    pub save: Option<PathBuf>,
+    pub load: Vec<PathBuf>,
+    pub exclude: Vec<String>,
+    pub shuffle: bool,
+    pub redact_mac: bool,
+    pub redact_vendor: bool,
+    pub mac_format: MacFormat,
+    pub pad_ip: bool,
+    pub strip_suffixes: bool,
+    pub raw_vendor: bool,
+    pub show_country: bool,
+    pub format: Option<Template>,
+    pub output: OutputFormat,
+    pub export: Option<ExportFormat>,
+    pub labels: Vec<(Vec<Ipv4Addr>, String)>,
+    pub analyze_padding: bool,
+    pub topology_hints: bool,
+    pub summary: bool,
+    // Where --output html writes its report
+    pub report: Option<PathBuf>,
+    pub pad_to: Option<usize>,
+    pub rounds: u32,
+    pub max_hosts: Option<usize>,
+    pub max_duration: Option<Duration>,
+    pub lock_dir: Option<PathBuf>,
+    // Network namespace to scan from: a path or an ip netns name
+    pub netns: Option<String>,
+    pub pps: Option<u32>,
+    pub adaptive_rate: bool,
+    pub probe_style: ProbeStyle,
+    pub dup_policy: DupPolicy,
+    pub vlan: Vlan,
+    pub channel: ChannelTuning,
+    pub quick: bool,
+    pub interfaces: Vec<String>,
+    pub oui_file: Option<PathBuf>,
+    pub require_oui: bool,
+    pub duration: Option<Duration>,
+    pub storm: StormThresholds,
+    pub interval: Option<Duration>,
+    pub flap: FlapThresholds,
+    pub debounce: Debounce,
+    pub presence: Option<PresenceModel>,
+    pub traps: Vec<Ipv4Addr>,
+    pub control_socket: Option<PathBuf>,
+    pub heartbeat_file: Option<PathBuf>,
+    pub email_to: Vec<String>,
+    pub roam_window: Option<Duration>,
+    pub quiet_hours: Option<QuietHours>,
+    pub defend_policy: DefendPolicy,
+    pub local_subnets: Vec<CidrV4>,
+    pub dhcp_snooping: Option<PathBuf>,
+    pub unicast_verify: bool,
+    pub verbose: bool,
+    pub enrich_exec: Option<String>,
+}
+
+impl Options {
+    // How long to wait for replies; --quick trades completeness for speed.
+    pub fn timeout(&self) -> Duration {
+        if self.quick {
+            Duration::from_secs(2)
+        } else {
+            Duration::from_secs(5)
+        }
+    }
+
+    // The options that affect results, as recorded in export metadata.
+    pub fn settings(&self) -> Vec<(String, String)> {
+        let mut settings = vec![
+            ("timeout_ms".to_string(), self.timeout().as_millis().to_string()),
+        ];
+        if self.quick {
+            settings.push(("quick".to_string(), "true".to_string()));
+        }
+        if !self.exclude.is_empty() {
+            settings.push(("exclude".to_string(), self.exclude.join(",")));
+        }
+        if self.shuffle {
+            settings.push(("shuffle".to_string(), "true".to_string()));
+        }
+        if self.redact_mac || self.redact_vendor {
+            let mut redact = Vec::new();
+            if self.redact_mac {
+                redact.push("mac");
+            }
+            if self.redact_vendor {
+                redact.push("vendor");
+            }
+            settings.push(("redact".to_string(), redact.join(",")));
+        }
+        if self.mac_format != MacFormat::default() {
+            settings.push(("mac_format".to_string(), self.mac_format.to_string()));
+        }
+        if self.strip_suffixes {
+            settings.push(("strip_suffixes".to_string(), "true".to_string()));
+        }
+        if self.raw_vendor {
+            settings.push(("raw_vendor".to_string(), "true".to_string()));
+        }
+        if self.rounds > 1 {
+            settings.push(("rounds".to_string(), self.rounds.to_string()));
+        }
+        if let Some(max_duration) = self.max_duration {
+            settings.push(("max_duration_ms".to_string(), max_duration.as_millis().to_string()));
+        }
+        if let Some(max_hosts) = self.max_hosts {
+            settings.push(("max_hosts".to_string(), max_hosts.to_string()));
+        }
+        if let Some(pps) = self.pps {
+            settings.push(("pps".to_string(), pps.to_string()));
+        }
+        if self.adaptive_rate {
+            settings.push(("adaptive_rate".to_string(), "true".to_string()));
+        }
+        if self.probe_style == ProbeStyle::Dad {
+            settings.push(("probe_style".to_string(), "dad".to_string()));
+        }
+        match self.dup_policy {
+            DupPolicy::Last => {}
+            DupPolicy::First => settings.push(("dup_policy".to_string(), "first".to_string())),
+            DupPolicy::Flag => settings.push(("dup_policy".to_string(), "flag".to_string())),
+        }
+        if !self.vlan.is_empty() {
+            settings.push(("vlan".to_string(), self.vlan.to_string()));
+        }
+        if let Some(rx_buffer) = self.channel.rx_buffer {
+            settings.push(("rx_buffer".to_string(), rx_buffer.to_string()));
+        }
+        if let Some(tx_buffer) = self.channel.tx_buffer {
+            settings.push(("tx_buffer".to_string(), tx_buffer.to_string()));
+        }
+        if self.channel.read_timeout != ChannelTuning::default().read_timeout {
+            settings.push(("read_timeout_ms".to_string(), self.channel.read_timeout.as_millis().to_string()));
+        }
+        if let Some(pad_to) = self.pad_to {
+            settings.push(("pad_to".to_string(), pad_to.to_string()));
+        }
+        if !self.interfaces.is_empty() {
+            settings.push(("interface".to_string(), self.interfaces.join(",")));
+        }
+        settings
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteAction {
+    List,
+    Show(MacAddr),
+    Set(MacAddr, String),
+    Clear(MacAddr),
+}
+
+pub enum MacAction {
+    // As typed, so invalid ones can be reported rather than rejected
+    Validate(Vec<String>),
+    // None makes locally administered addresses
+    Random { prefix: Option<Vec<u8>>, count: usize },
+}
+
+pub enum OuiQuery {
+    // Prefixes left-aligned in 48 bits, with their length
+    Lookup(Vec<(u64, u8)>),
+    Search(String),
+}
+
+pub enum Command {
+    Scan(Options),
+    Compare(Options, PathBuf),
+    Monitor(Options),
+    Passive(Options),
+    Defend(Options),
+    Note(NoteAction),
+    Mac(MacAction, MacFormat),
+    Oui(OuiQuery, Option<PathBuf>),
+    Ctl(String, PathBuf),
+    // The monitor options for the unit, and whether to only print it
+    InstallService(Vec<String>, bool),
+    UninstallService,
+    Schema,
+    SelfTest,
+    // With the --netns to list them in
+    ListInterfaces(Option<String>),
+    Help,
+}
+
+impl Command {
+    // The network namespace the command runs in, entered before anything
+    // else happens
+    pub fn netns(&self) -> Option<&str> {
+        match self {
+            Command::Scan(opts)
+            | Command::Compare(opts, _)
+            | Command::Monitor(opts)
+            | Command::Passive(opts)
+            | Command::Defend(opts) => opts.netns.as_deref(),
+            Command::ListInterfaces(netns) => netns.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for {}", flag))
+}
+
+fn parse_note<I: Iterator<Item = String>>(args: I) -> Result<NoteAction, String> {
+    let args: Vec<String> = args.collect();
+    let Some(mac) = args.first() else {
+        return Ok(NoteAction::List);
+    };
+    let mac: MacAddr = mac.parse::<MacAddress>()?.into();
+    match &args[1..] {
+        [] => Ok(NoteAction::Show(mac)),
+        [flag] if flag == "--clear" => Ok(NoteAction::Clear(mac)),
+        [text] if !text.trim().is_empty() => Ok(NoteAction::Set(mac, text.clone())),
+        [_] => Err("Note text is empty; use --clear to remove a note".to_string()),
+        _ => Err("Quote the note text as a single argument, e.g. note aa:bb:cc:dd:ee:ff 'flaky PSU'".to_string()),
+    }
+}
+
+// Large enough for a full Ethernet frame, small enough not to be a typo.
+fn parse_buffer_size(value: &str, flag: &str) -> Result<usize, String> {
+    let size: usize = value.parse().map_err(|e| format!("Invalid {} value '{}': {}", flag, value, e))?;
+    if !(MIN_BUFFER..=MAX_BUFFER).contains(&size) {
+        return Err(format!("{} must be between {} and {} bytes", flag, MIN_BUFFER, MAX_BUFFER));
+    }
+    Ok(size)
+}
+
+// A duration in seconds, optionally with an s, m or h suffix.
+fn parse_duration(value: &str, flag: &str) -> Result<Duration, String> {
+    let (number, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((at, _)) => value.split_at(at),
+        None => (value, "s"),
+    };
+    let number: u64 = number.parse().map_err(|e| format!("Invalid {} value '{}': {}", flag, value, e))?;
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => return Err(format!("Invalid {} value '{}'. Use seconds or a s, m or h suffix", flag, value)),
+    };
+    let secs = number.checked_mul(scale).ok_or_else(|| format!("Invalid {} value '{}': too large", flag, value))?;
+    Ok(Duration::from_secs(secs))
+}
+
+// A VLAN ID, or OUTER:INNER for QinQ.
+fn parse_vlan(value: &str) -> Result<Vlan, String> {
+    let id = |text: &str| -> Result<u16, String> {
+        match text.trim().parse::<u16>() {
+            Ok(id) if (1..=4094).contains(&id) => Ok(id),
+            _ => Err(format!("Invalid --vlan value '{}': VLAN IDs are 1 to 4094", value)),
+        }
+    };
+    match value.split_once(':') {
+        Some((outer, inner)) => Ok(Vlan::QinQ { outer: id(outer)?, inner: id(inner)? }),
+        None => Ok(Vlan::Single(id(value)?)),
+    }
+}
+
+fn parse_ctl<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut command = None;
+    let mut socket = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--control-socket" => socket = Some(PathBuf::from(next_value(&mut args, "--control-socket")?)),
+            other if command.is_some() => return Err(format!("Unexpected argument '{}'", other)),
+            other if control::COMMANDS.contains(&other) => command = Some(arg),
+            other => return Err(format!("Unknown ctl command '{}'. Use: {}", other, control::COMMANDS.join(", "))),
+        }
+    }
+    let command = command.ok_or_else(|| format!("ctl needs a command: {}", control::COMMANDS.join(", ")))?;
+    Ok(Command::Ctl(command, socket.unwrap_or_else(control::default_path)))
+}
+
+// The options are checked as monitor would check them, so a unit that
+// can't start isn't installed.
+fn parse_install_service<I: Iterator<Item = String>>(args: I) -> Result<Command, String> {
+    let (print, args): (Vec<String>, Vec<String>) = args.partition(|arg| arg == "--print");
+    match parse_args(std::iter::once("monitor".to_string()).chain(args.iter().cloned()))? {
+        Command::Monitor(_) => Ok(Command::InstallService(args, !print.is_empty())),
+        other => Ok(other),
+    }
+}
+
+fn parse_mac<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let action = args.next().ok_or("mac needs a command: validate, random")?;
+    let mut addresses = Vec::new();
+    let mut prefix = None;
+    let mut count = 1;
+    let mut format = MacFormat::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mac-format" => format = MacFormat::parse(&next_value(&mut args, "--mac-format")?)?,
+            "--vendor" if action == "random" => {
+                let value = next_value(&mut args, "--vendor")?;
+                prefix = match value.as_str() {
+                    "locally-administered" => None,
+                    _ => {
+                        let bytes = mac::parse_prefix(&value)?;
+                        if bytes[0] & 0x01 != 0 {
+                            return Err(format!("'{}' is a multicast prefix; host addresses need the lowest bit of the first byte clear", value));
+                        }
+                        Some(bytes)
+                    }
+                };
+            }
+            "--count" if action == "random" => {
+                let value = next_value(&mut args, "--count")?;
+                count = value.parse().map_err(|e| format!("Invalid --count value '{}': {}", value, e))?;
+            }
+            other if other.starts_with('-') && other.len() > 1 => return Err(format!("Unknown mac {} option '{}'", action, other)),
+            _ if action == "validate" => addresses.push(arg),
+            other => return Err(format!("Unexpected argument '{}'", other)),
+        }
+    }
+    let action = match action.as_str() {
+        "validate" if addresses.is_empty() => return Err("mac validate needs at least one address".to_string()),
+        "validate" => MacAction::Validate(addresses),
+        "random" => MacAction::Random { prefix, count },
+        other => return Err(format!("Unknown mac command '{}'. Use: validate, random", other)),
+    };
+    Ok(Command::Mac(action, format))
+}
+
+fn parse_oui<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut prefixes = Vec::new();
+    let mut search = None;
+    let mut oui_file = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--search" => search = Some(next_value(&mut args, "--search")?),
+            "--oui-file" => oui_file = Some(PathBuf::from(next_value(&mut args, "--oui-file")?)),
+            other if other.starts_with('-') => return Err(format!("Unknown oui option '{}'", other)),
+            other => {
+                let prefix = vendor::parse_prefix(other)
+                    .filter(|(_, bits)| *bits > 0)
+                    .ok_or_else(|| format!("Invalid MAC address or prefix '{}'", other))?;
+                prefixes.push(prefix);
+            }
+        }
+    }
+    let query = match search {
+        Some(_) if !prefixes.is_empty() => return Err("Give either addresses or --search, not both".to_string()),
+        Some(text) if text.trim().is_empty() => return Err("--search text is empty".to_string()),
+        Some(text) => OuiQuery::Search(text),
+        None if prefixes.is_empty() => return Err("oui needs a MAC address, a prefix or --search <TEXT>".to_string()),
+        None => OuiQuery::Lookup(prefixes),
+    };
+    Ok(Command::Oui(query, oui_file))
+}
+
+fn parse_redact(value: &str, opts: &mut Options) -> Result<(), String> {
+    for part in value.split(',') {
+        match part.trim() {
+            "mac" => opts.redact_mac = true,
+            "vendor" => opts.redact_vendor = true,
+            other => return Err(format!("Unknown --redact value '{}'. Use: mac, vendor", other)),
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.peekable();
+    let subcommand = match args.peek().map(String::as_str) {
+        Some("compare") | Some("monitor") | Some("passive") | Some("defend") | Some("merge") => args.next(),
+        Some("schema") => return Ok(Command::Schema),
+        Some("self-test") => {
+            args.next();
+            return match args.next() {
+                None => Ok(Command::SelfTest),
+                Some(arg) => Err(format!("Unexpected argument '{}'", arg)),
+            };
+        }
+        Some("ctl") => {
+            args.next();
+            return parse_ctl(args);
+        }
+        Some("install-service") => {
+            args.next();
+            return parse_install_service(args);
+        }
+        Some("uninstall-service") => {
+            args.next();
+            return match args.next() {
+                None => Ok(Command::UninstallService),
+                Some(arg) => Err(format!("Unexpected argument '{}'", arg)),
+            };
+        }
+        Some("note") => {
+            args.next();
+            return parse_note(args).map(Command::Note);
+        }
+        Some("mac") => {
+            args.next();
+            return parse_mac(args);
+        }
+        Some("oui") => {
+            args.next();
+            return parse_oui(args);
+        }
+        _ => None,
+    };
+
+    let mut opts = Options::default();
+    let mut nmap = None;
+    let mut list_interfaces = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--nmap" if subcommand.as_deref() == Some("compare") => {
+                nmap = Some(PathBuf::from(next_value(&mut args, "--nmap")?));
+            }
+            "--policy" if subcommand.as_deref() == Some("defend") => {
+                opts.defend_policy = DefendPolicy::parse(&next_value(&mut args, "--policy")?)?;
+            }
+            "--redact" => {
+                let value = next_value(&mut args, "--redact")?;
+                parse_redact(&value, &mut opts)?;
+            }
+            "-v" | "--verbose" => opts.verbose = true,
+            "--no-prompt" => opts.no_prompt = true,
+            "--save" => opts.save = Some(PathBuf::from(next_value(&mut args, "--save")?)),
+            "--load" => opts.load.push(PathBuf::from(next_value(&mut args, "--load")?)),
+            "--roam-window" => {
+                let value = next_value(&mut args, "--roam-window")?;
+                opts.roam_window = Some(parse_duration(&value, "--roam-window")?);
+            }
+            "--target" => {
+                let value = next_value(&mut args, "--target")?;
+                if opts.target.is_some() {
+                    return Err(format!("Unexpected target '{}': TARGET was already given", value));
+                }
+                opts.target = Some(value);
+            }
+            "--mac-format" => {
+                opts.mac_format = MacFormat::parse(&next_value(&mut args, "--mac-format")?)?;
+            }
+            "--pad-ip" => opts.pad_ip = true,
+            "--strip-suffixes" => opts.strip_suffixes = true,
+            "--raw-vendor" => opts.raw_vendor = true,
+            "--show-country" => opts.show_country = true,
+            "--analyze-padding" => opts.analyze_padding = true,
+            "--topology-hints" => opts.topology_hints = true,
+            "--summary" => opts.summary = true,
+            "--quick" => opts.quick = true,
+            "--adaptive-rate" => opts.adaptive_rate = true,
+            "--pps" => {
+                let value = next_value(&mut args, "--pps")?;
+                let pps: u32 = value.parse().map_err(|e| format!("Invalid --pps value '{}': {}", value, e))?;
+                if pps == 0 {
+                    return Err("--pps must be at least 1".to_string());
+                }
+                opts.pps = Some(pps);
+            }
+            "--probe-style" => {
+                opts.probe_style = match next_value(&mut args, "--probe-style")?.as_str() {
+                    "normal" => ProbeStyle::Normal,
+                    "dad" => ProbeStyle::Dad,
+                    "announce" => {
+                        return Err("--probe-style announce is not supported: announcing a target's address as \
+                                    our own would overwrite its entry in every neighbour's ARP cache"
+                            .to_string())
+                    }
+                    other => return Err(format!("Unknown --probe-style '{}'. Use: normal, dad", other)),
+                };
+            }
+            "--dup-policy" => {
+                opts.dup_policy = match next_value(&mut args, "--dup-policy")?.as_str() {
+                    "first" => DupPolicy::First,
+                    "last" => DupPolicy::Last,
+                    "flag" => DupPolicy::Flag,
+                    other => return Err(format!("Unknown --dup-policy '{}'. Use: first, last, flag", other)),
+                };
+            }
+            "--rx-buffer" => {
+                let value = next_value(&mut args, "--rx-buffer")?;
+                opts.channel.rx_buffer = Some(parse_buffer_size(&value, "--rx-buffer")?);
+            }
+            "--tx-buffer" => {
+                let value = next_value(&mut args, "--tx-buffer")?;
+                opts.channel.tx_buffer = Some(parse_buffer_size(&value, "--tx-buffer")?);
+            }
+            "--read-timeout" => {
+                let value = next_value(&mut args, "--read-timeout")?;
+                let ms: u64 = value
+                    .parse()
+                    .map_err(|e| format!("Invalid --read-timeout value '{}': {}", value, e))?;
+                if !(1..=10_000).contains(&ms) {
+                    return Err("--read-timeout must be between 1 and 10000 ms".to_string());
+                }
+                opts.channel.read_timeout = Duration::from_millis(ms);
+            }
+            "--drop-privileges" => opts.channel.drop_privileges = true,
+            "--sandbox" => opts.channel.sandbox = true,
+            "--fd" => {
+                let value = next_value(&mut args, "--fd")?;
+                let fd: i32 = value.parse().map_err(|e| format!("Invalid --fd value '{}': {}", value, e))?;
+                if fd < 0 {
+                    return Err("--fd must be a file descriptor number (0 or more)".to_string());
+                }
+                opts.channel.fd = Some(fd);
+            }
+            "--lock-dir" => opts.lock_dir = Some(PathBuf::from(next_value(&mut args, "--lock-dir")?)),
+            "--shuffle" => opts.shuffle = true,
+            "--exclude" => {
+                let value = next_value(&mut args, "--exclude")?;
+                target::TargetSpec::parse(&value)?;
+                opts.exclude.push(value);
+            }
+            "--unicast-verify" => opts.unicast_verify = true,
+            "--list-interfaces" => list_interfaces = true,
+            "--netns" => opts.netns = Some(next_value(&mut args, "--netns")?),
+            "--require-oui" => opts.require_oui = true,
+            "--oui-file" => {
+                opts.oui_file = Some(PathBuf::from(next_value(&mut args, "--oui-file")?));
+            }
+            "-i" | "--interface" => {
+                let name = next_value(&mut args, "--interface")?;
+                if !opts.interfaces.contains(&name) {
+                    opts.interfaces.push(name);
+                }
+            }
+            "--rounds" => {
+                let value = next_value(&mut args, "--rounds")?;
+                opts.rounds = value.parse().map_err(|e| format!("Invalid --rounds value '{}': {}", value, e))?;
+                if opts.rounds == 0 {
+                    return Err("--rounds must be at least 1".to_string());
+                }
+            }
+            "--max-hosts" => {
+                let value = next_value(&mut args, "--max-hosts")?;
+                let max: usize = value.parse().map_err(|e| format!("Invalid --max-hosts value '{}': {}", value, e))?;
+                if max == 0 {
+                    return Err("--max-hosts must be at least 1".to_string());
+                }
+                opts.max_hosts = Some(max);
+            }
+            "--vlan" => {
+                opts.vlan = parse_vlan(&next_value(&mut args, "--vlan")?)?;
+            }
+            "--max-duration" => {
+                let value = next_value(&mut args, "--max-duration")?;
+                let max = parse_duration(&value, "--max-duration")?;
+                if max.is_zero() {
+                    return Err("--max-duration must be greater than zero".to_string());
+                }
+                opts.max_duration = Some(max);
+            }
+            "--pad-to" => {
+                let value = next_value(&mut args, "--pad-to")?;
+                opts.pad_to = Some(value.parse().map_err(|e| format!("Invalid --pad-to value '{}': {}", value, e))?);
+            }
+            "--duration" => {
+                let value = next_value(&mut args, "--duration")?;
+                let secs: u64 = value.parse().map_err(|e| format!("Invalid --duration value '{}': {}", value, e))?;
+                opts.duration = Some(Duration::from_secs(secs));
+            }
+            "--interval" => {
+                let value = next_value(&mut args, "--interval")?;
+                let secs: u64 = value.parse().map_err(|e| format!("Invalid --interval value '{}': {}", value, e))?;
+                opts.interval = Some(Duration::from_secs(secs));
+            }
+            "--flap-changes" => {
+                let value = next_value(&mut args, "--flap-changes")?;
+                opts.flap.changes = value.parse().map_err(|e| format!("Invalid --flap-changes value '{}': {}", value, e))?;
+                if opts.flap.changes < 2 {
+                    return Err("--flap-changes must be at least 2".to_string());
+                }
+            }
+            "--flap-window" => {
+                let value = next_value(&mut args, "--flap-window")?;
+                let secs: u64 = value.parse().map_err(|e| format!("Invalid --flap-window value '{}': {}", value, e))?;
+                opts.flap.window = Duration::from_secs(secs);
+            }
+            "--miss-threshold" => {
+                let value = next_value(&mut args, "--miss-threshold")?;
+                opts.debounce.misses = value.parse().map_err(|e| format!("Invalid --miss-threshold value '{}': {}", value, e))?;
+                if opts.debounce.misses == 0 {
+                    return Err("--miss-threshold must be at least 1".to_string());
+                }
+            }
+            "--arrive-threshold" => {
+                let value = next_value(&mut args, "--arrive-threshold")?;
+                opts.debounce.arrivals = value.parse().map_err(|e| format!("Invalid --arrive-threshold value '{}': {}", value, e))?;
+                if opts.debounce.arrivals == 0 {
+                    return Err("--arrive-threshold must be at least 1".to_string());
+                }
+            }
+            "--grace" => {
+                let value = next_value(&mut args, "--grace")?;
+                opts.debounce.grace = parse_duration(&value, "--grace")?;
+            }
+            "--presence" => {
+                let value = next_value(&mut args, "--presence")?;
+                let half_life = parse_duration(&value, "--presence")?;
+                if half_life.is_zero() {
+                    return Err("--presence must be longer than zero".to_string());
+                }
+                opts.presence.get_or_insert_with(PresenceModel::default).half_life = half_life;
+            }
+            "--presence-thresholds" => {
+                let (present, away) = PresenceModel::parse_thresholds(&next_value(&mut args, "--presence-thresholds")?)?;
+                let model = opts.presence.get_or_insert_with(PresenceModel::default);
+                model.present = present;
+                model.away = away;
+            }
+            "--quiet-hours" if subcommand.as_deref() == Some("monitor") => {
+                opts.quiet_hours = Some(QuietHours::parse(&next_value(&mut args, "--quiet-hours")?)?);
+            }
+            "--control" if subcommand.as_deref() == Some("monitor") => {
+                opts.control_socket.get_or_insert_with(control::default_path);
+            }
+            "--control-socket" if subcommand.as_deref() == Some("monitor") => {
+                opts.control_socket = Some(PathBuf::from(next_value(&mut args, "--control-socket")?));
+            }
+            "--heartbeat-file" if subcommand.as_deref() == Some("monitor") => {
+                opts.heartbeat_file = Some(PathBuf::from(next_value(&mut args, "--heartbeat-file")?));
+            }
+            "--email-to" if subcommand.as_deref() == Some("monitor") => {
+                let address = next_value(&mut args, "--email-to")?;
+                let odd = |c: char| c.is_whitespace() || c.is_control() || matches!(c, ',' | '<' | '>');
+                if !address.contains('@') || address.contains(odd) {
+                    return Err(format!("Invalid --email-to address '{}': give one address, e.g. ops@example.com", address));
+                }
+                opts.email_to.push(address);
+            }
+            "--trap" => {
+                let value = next_value(&mut args, "--trap")?;
+                opts.traps.extend(target::expand_target(&value)?);
+            }
+            "--dhcp-snooping" => opts.dhcp_snooping = Some(PathBuf::from(next_value(&mut args, "--dhcp-snooping")?)),
+            "--local-subnet" => {
+                let value = next_value(&mut args, "--local-subnet")?;
+                opts.local_subnets.push(CidrV4::parse(&value)?);
+            }
+            "--storm-rate" => {
+                let value = next_value(&mut args, "--storm-rate")?;
+                opts.storm.per_source = value.parse().map_err(|e| format!("Invalid --storm-rate value '{}': {}", value, e))?;
+            }
+            "--storm-total" => {
+                let value = next_value(&mut args, "--storm-total")?;
+                opts.storm.total = value.parse().map_err(|e| format!("Invalid --storm-total value '{}': {}", value, e))?;
+            }
+            "--output" => {
+                opts.output = match next_value(&mut args, "--output")?.as_str() {
+                    "table" => OutputFormat::Table,
+                    "json" => OutputFormat::Json,
+                    "csv" => OutputFormat::Csv,
+                    "html" => {
+                        opts.report = Some(PathBuf::from(next_value(&mut args, "--output html")?));
+                        OutputFormat::Html
+                    }
+                    "markdown" | "md" => OutputFormat::Markdown,
+                    other => return Err(format!("Unknown output format '{}'. Use: table, json, csv, html, markdown", other)),
+                };
+            }
+            "--export" => {
+                opts.export = match next_value(&mut args, "--export")?.as_str() {
+                    "ethers" => Some(ExportFormat::Ethers),
+                    "dnsmasq" => Some(ExportFormat::Dnsmasq),
+                    "ansible-inventory" => Some(ExportFormat::AnsibleInventory),
+                    "graph" | "dot" => Some(ExportFormat::Graph),
+                    "mermaid" => Some(ExportFormat::Mermaid),
+                    other => {
+                        return Err(format!(
+                            "Unknown export format '{}'. Use: ethers, dnsmasq, ansible-inventory, graph, mermaid",
+                            other
+                        ))
+                    }
+                };
+            }
+            "--label" => {
+                let value = next_value(&mut args, "--label")?;
+                let (spec, group) = value.split_once('=')
+                    .ok_or_else(|| format!("Invalid --label value '{}'. Use: TARGET=GROUP", value))?;
+                if group.trim().is_empty() {
+                    return Err(format!("Invalid --label value '{}': empty group name", value));
+                }
+                opts.labels.push((target::expand_target(spec)?, group.trim().to_string()));
+            }
+            "--enrich-exec" => {
+                opts.enrich_exec = Some(next_value(&mut args, "--enrich-exec")?);
+            }
+            "--format" => {
+                let value = next_value(&mut args, "--format")?;
+                opts.format = Some(Template::parse(&value)?);
+            }
+            flag if flag.starts_with("--") => {
+                return Err(format!("Unknown option '{}'", flag));
+            }
+            _ if subcommand.as_deref() == Some("merge") => opts.load.push(PathBuf::from(arg)),
+            _ => {
+                if opts.target.is_some() {
+                    return Err(format!("Unexpected argument '{}'", arg));
+                }
+                opts.target = Some(arg);
+            }
+        }
+    }
+
+    if opts.netns.is_some() && !cfg!(target_os = "linux") {
+        return Err("--netns is only supported on Linux".to_string());
+    }
+    if list_interfaces {
+        return Ok(Command::ListInterfaces(opts.netns));
+    }
+    if opts.netns.is_some() && !opts.load.is_empty() {
+        return Err("--netns only applies when capturing".to_string());
+    }
+    if opts.format.is_some() && opts.output != OutputFormat::Table {
+        return Err("--format cannot be combined with --output other than table".to_string());
+    }
+    if opts.export.is_some() && (opts.format.is_some() || opts.output != OutputFormat::Table) {
+        return Err("--export cannot be combined with --format or --output other than table".to_string());
+    }
+    if !opts.labels.is_empty() && !matches!(opts.export, Some(ExportFormat::AnsibleInventory | ExportFormat::Graph | ExportFormat::Mermaid)) {
+        return Err("--label only applies to --export ansible-inventory, graph and mermaid".to_string());
+    }
+    if opts.unicast_verify && (opts.format.is_some() || opts.export.is_some() || opts.output != OutputFormat::Table) {
+        return Err("--unicast-verify only supports table output".to_string());
+    }
+    if opts.summary && (opts.format.is_some() || opts.export.is_some() || opts.output == OutputFormat::Csv) {
+        return Err("--summary only supports table, JSON, markdown and HTML output".to_string());
+    }
+    if opts.topology_hints && (opts.format.is_some() || opts.export.is_some() || opts.output != OutputFormat::Table) {
+        return Err("--topology-hints only supports table output".to_string());
+    }
+    // Each sweep and each interface opens its own channel, and only the
+    // first would still have root. A monitor gets around that on Linux by
+    // opening one packet socket up front and sweeping on copies of it.
+    for (set, flag) in [(opts.channel.drop_privileges, "--drop-privileges"), (opts.channel.sandbox, "--sandbox")] {
+        if !set {
+            continue;
+        }
+        if opts.interfaces.len() > 1 {
+            return Err(format!("{} needs a single interface", flag));
+        }
+        if subcommand.as_deref() == Some("monitor") && flag == "--drop-privileges" {
+            if !cfg!(target_os = "linux") {
+                return Err("--drop-privileges with monitor is only supported on Linux".to_string());
+            }
+            continue;
+        }
+        if matches!(subcommand.as_deref(), Some("monitor") | Some("self-test") | Some("merge")) || !opts.load.is_empty() {
+            return Err(format!("{} only applies to scans, compare, passive and defend", flag));
+        }
+    }
+    if opts.channel.drop_privileges && !cfg!(unix) {
+        return Err("--drop-privileges is only supported on Unix".to_string());
+    }
+    if opts.channel.sandbox && !cfg!(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))) {
+        return Err("--sandbox is only supported on Linux on x86_64 and aarch64".to_string());
+    }
+    // The filter doesn't allow running programs
+    if opts.channel.sandbox && opts.enrich_exec.is_some() {
+        return Err("--sandbox cannot be combined with --enrich-exec".to_string());
+    }
+    if let Some(fd) = opts.channel.fd {
+        if !cfg!(target_os = "linux") {
+            return Err("--fd is only supported on Linux".to_string());
+        }
+        // One socket can only be bound to one interface at a time
+        if opts.interfaces.len() > 1 {
+            return Err("--fd needs a single interface".to_string());
+        }
+        if !opts.load.is_empty() || matches!(subcommand.as_deref(), Some("merge")) {
+            return Err(format!("--fd {} only applies when capturing", fd));
+        }
+        if opts.channel.tx_buffer.is_some() {
+            return Err("--tx-buffer has no effect with --fd".to_string());
+        }
+    }
+    if opts.lock_dir.is_some() && !cfg!(unix) {
+        return Err("--lock-dir is only supported on Unix".to_string());
+    }
+    if opts.lock_dir.is_some() && (matches!(subcommand.as_deref(), Some("passive") | Some("defend")) || !opts.load.is_empty()) {
+        return Err("--lock-dir only applies to scans, compare and monitor".to_string());
+    }
+    if opts.unicast_verify && opts.interfaces.len() > 1 {
+        return Err("--unicast-verify runs on a single interface".to_string());
+    }
+    // merge is a scan whose results all come from snapshots
+    let scanning = subcommand.is_none() || subcommand.as_deref() == Some("merge");
+    if !opts.load.is_empty() && (!scanning || opts.unicast_verify) {
+        return Err("--load only applies to printing scan results".to_string());
+    }
+    if !opts.load.is_empty() && opts.target.is_some() {
+        return Err("--load prints saved results and does not take a TARGET".to_string());
+    }
+    if opts.save.is_some() && (!scanning || opts.unicast_verify) {
+        return Err("--save only applies to scans".to_string());
+    }
+    if !opts.local_subnets.is_empty() && subcommand.as_deref() != Some("passive") {
+        return Err("--local-subnet only applies to passive".to_string());
+    }
+    if opts.dhcp_snooping.is_some() && (!scanning || opts.unicast_verify) && subcommand.as_deref() != Some("passive") {
+        return Err("--dhcp-snooping only applies to scans and passive".to_string());
+    }
+    if opts.presence.is_some() && subcommand.as_deref() != Some("monitor") {
+        return Err("--presence only applies to monitor".to_string());
+    }
+    if opts.presence.is_some() && opts.debounce != Debounce::default() {
+        return Err("--presence replaces --miss-threshold, --grace and --arrive-threshold; use one or the other".to_string());
+    }
+    if opts.roam_window.is_some() && opts.load.is_empty() {
+        return Err("--roam-window only applies to merge and --load".to_string());
+    }
+    // ScanConfig::validate checks these too, but only once a scan starts
+    let capturing_only = matches!(subcommand.as_deref(), Some("passive") | Some("defend"));
+    if !opts.vlan.is_empty() && opts.interfaces.is_empty() && opts.load.is_empty() && !capturing_only {
+        return Err("--vlan needs an explicit -i: the default route's interface is rarely the trunk".to_string());
+    }
+    if opts.channel.read_timeout > opts.timeout() && opts.load.is_empty() && !capturing_only {
+        return Err(format!(
+            "--read-timeout of {} ms is longer than the reply timeout of {} ms",
+            opts.channel.read_timeout.as_millis(),
+            opts.timeout().as_millis()
+        ));
+    }
+    if !opts.traps.is_empty() && subcommand.as_deref() != Some("monitor") {
+        return Err("--trap only applies to monitor".to_string());
+    }
+    if opts.max_duration.is_some() && matches!(subcommand.as_deref(), Some("monitor") | Some("passive") | Some("defend")) {
+        return Err("--max-duration only applies to one-off scans; use --duration to bound monitor, passive and defend".to_string());
+    }
+    if opts.export.is_some_and(|export| !export.is_map()) && opts.redact_mac {
+        return Err("--export needs full MAC addresses and cannot be combined with --redact mac".to_string());
+    }
+    if opts.export.is_some_and(ExportFormat::needs_colons) && opts.mac_format.style != MacStyle::Colon {
+        return Err(format!(
+            "--export ethers and dnsmasq need colon-separated MAC addresses; --mac-format {} cannot be used with them",
+            opts.mac_format
+        ));
+    }
+
+    match subcommand.as_deref() {
+        Some("compare") => {
+            let nmap = nmap.ok_or("compare requires --nmap <FILE>")?;
+            Ok(Command::Compare(opts, nmap))
+        }
+        Some("monitor") => {
+            if opts.interfaces.len() > 1 {
+                return Err("monitor runs on a single interface".to_string());
+            }
+            if opts.format.is_some() || opts.export.is_some() || opts.output != OutputFormat::Table {
+                return Err("monitor only supports table output".to_string());
+            }
+            Ok(Command::Monitor(opts))
+        }
+        Some("passive") => {
+            if opts.target.is_some() {
+                return Err("passive does not take a TARGET; it reports every host it hears".to_string());
+            }
+            if opts.interfaces.len() > 1 {
+                return Err("passive listens on a single interface".to_string());
+            }
+            if opts.format.is_some() || opts.export.is_some() || opts.output != OutputFormat::Table {
+                return Err("passive only supports table output".to_string());
+            }
+            Ok(Command::Passive(opts))
+        }
+        Some("defend") => {
+            if opts.target.is_some() {
+                return Err("defend does not take a TARGET; it defends the interface's own addresses".to_string());
+            }
+            if opts.interfaces.len() > 1 {
+                return Err("defend runs on a single interface".to_string());
+            }
+            if opts.format.is_some() || opts.export.is_some() || opts.output != OutputFormat::Table {
+                return Err("defend only supports table output".to_string());
+            }
+            Ok(Command::Defend(opts))
+        }
+        Some("merge") => {
+            if opts.load.is_empty() {
+                return Err("merge needs one or more snapshot files saved with --save".to_string());
+            }
+            Ok(Command::Scan(opts))
+        }
+        _ => Ok(Command::Scan(opts)),
+    }
+}
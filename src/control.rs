@@ -0,0 +1,184 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+
+use arp_scan::meta::format_timestamp;
+use arp_scan::monitor::{Control, Health, MonitorStatus};
+
+pub const COMMANDS: &[&str] = &["rescan", "pause", "resume", "status"];
+
+const SOCKET_NAME: &str = "arp_scan.sock";
+
+// $XDG_RUNTIME_DIR/arp_scan.sock, or /run/arp_scan.sock for root. Anyone
+// else gets a directory of their own in the temp directory, since any
+// local user could take a fixed name there first.
+pub fn default_path() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join(SOCKET_NAME);
+    }
+    match user_dir() {
+        Some(dir) => dir.join(SOCKET_NAME),
+        None if cfg!(unix) => PathBuf::from("/run").join(SOCKET_NAME),
+        None => env::temp_dir().join(SOCKET_NAME),
+    }
+}
+
+// The per-user directory for the default socket, when there is no
+// runtime directory and the user isn't root.
+#[cfg(unix)]
+fn user_dir() -> Option<PathBuf> {
+    let uid = unsafe { libc::geteuid() };
+    (uid != 0).then(|| env::temp_dir().join(format!("arp_scan-{}", uid)))
+}
+
+#[cfg(not(unix))]
+fn user_dir() -> Option<PathBuf> {
+    None
+}
+
+// Create the per-user directory, or make sure the one already there
+// belongs to this user and nobody else can get into it.
+#[cfg(unix)]
+fn check_user_dir(path: &Path, create: bool) -> Result<(), String> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    let Some(dir) = user_dir().filter(|dir| path.parent() == Some(dir.as_path())) else {
+        return Ok(());
+    };
+    if create && !dir.exists() {
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let metadata = std::fs::symlink_metadata(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::geteuid() } || metadata.mode() & 0o077 != 0 {
+        return Err(format!("{} is not a private directory of this user; pass --control-socket instead", dir.display()));
+    }
+    Ok(())
+}
+
+// One line on what the monitor is doing, also used for systemd's STATUS.
+pub fn describe(status: &MonitorStatus) -> String {
+    format!(
+        "{}, {} sweeps, last sweep {}, {} hosts",
+        if status.paused {
+            "paused"
+        } else if status.quiet {
+            "quiet hours"
+        } else {
+            "running"
+        },
+        status.sweeps,
+        status.last_sweep.map(format_timestamp).unwrap_or_else(|| "never".to_string()),
+        status.hosts
+    )
+}
+
+// The monitor's own counters, for telling a broken probe from a quiet
+// network.
+fn health_line(health: &Health) -> String {
+    let mut parts = Vec::new();
+    if let Some(took) = health.last_sweep_took {
+        parts.push(format!("last sweep took {:.1} seconds", took.as_secs_f64()));
+    }
+    parts.push(format!("{} send errors", health.send_errors));
+    parts.push(format!("{} probes skipped", health.unsent));
+    parts.push(format!("{} receive errors", health.receive_errors));
+    if let Some(dropped) = health.dropped {
+        parts.push(format!("{} frames dropped by the interface", dropped));
+    }
+    if let Some(pps) = health.reduced_pps {
+        parts.push(format!("rate reduced to {} pps", pps));
+    }
+    if health.skipped_sweeps > 0 {
+        parts.push(format!("{} sweeps skipped", health.skipped_sweeps));
+    }
+    if let Some(error) = &health.last_error {
+        parts.push(format!("last error: {}", error));
+    }
+    format!("health: {}", parts.join(", "))
+}
+
+// The reply for one request line.
+fn handle(request: &str, controls: &mpsc::Sender<Control>, status: &Mutex<MonitorStatus>) -> String {
+    let control = match request.trim() {
+        "rescan" => Control::Rescan,
+        "pause" => Control::Pause,
+        "resume" => Control::Resume,
+        "status" => {
+            let Ok(status) = status.lock() else {
+                return "error: status unavailable".to_string();
+            };
+            return format!("{}\n{}", describe(&status), health_line(&status.health));
+        }
+        other => return format!("error: unknown command '{}'. Use: {}", other, COMMANDS.join(", ")),
+    };
+    match controls.send(control) {
+        Ok(()) => format!("ok: {} requested", request.trim()),
+        Err(_) => "error: monitor is not running".to_string(),
+    }
+}
+
+// Accept control connections in the background: one command line in, one
+// reply line out. The socket is only accessible to its owner.
+#[cfg(unix)]
+pub fn serve(path: &Path, controls: mpsc::Sender<Control>, status: Arc<Mutex<MonitorStatus>>) -> Result<(), String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    check_user_dir(path, true)?;
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("{} is in use by another monitor", path.display()));
+        }
+        // Left behind by a monitor that didn't exit cleanly
+        let _ = std::fs::remove_file(path);
+    }
+    // Owner only from the start; nothing else creates files this early
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { libc::umask(umask) };
+    let listener = listener.map_err(|e| format!("Failed to create control socket {}: {}", path.display(), e))?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+            let reply = handle(&request, &controls, &status);
+            let _ = writeln!(&stream, "{}", reply);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_path: &Path, _controls: mpsc::Sender<Control>, _status: Arc<Mutex<MonitorStatus>>) -> Result<(), String> {
+    Err("The control socket is only supported on Unix".to_string())
+}
+
+// Send one command to a running monitor and return its reply.
+#[cfg(unix)]
+pub fn send(path: &Path, command: &str) -> Result<String, String> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    check_user_dir(path, false)?;
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| format!("Cannot reach a monitor at {}: {}", path.display(), e))?;
+    writeln!(stream, "{}", command).map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).map_err(|e| e.to_string())?;
+    let reply = reply.trim().to_string();
+    match reply.strip_prefix("error: ") {
+        Some(message) => Err(message.to_string()),
+        None => Ok(reply),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _command: &str) -> Result<String, String> {
+    Err("The control socket is only supported on Unix".to_string())
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+#[cfg(feature = "engine")]
+use std::net::IpAddr;
+#[cfg(feature = "engine")]
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+use pnet_base::MacAddr;
+
+#[cfg(feature = "engine")]
+use crate::packet::{parse_arp_frame, ArpFrame, ARP_FRAME_LEN};
+use crate::scan::ChannelTuning;
+#[cfg(feature = "engine")]
+use crate::scan::{is_arp_frame, open_channel, resolve_interface, spawn_receiver};
+
+// RFC 5227 section 2.4: a host that has seen one conflict must not defend
+// again within this long.
+pub const DEFEND_INTERVAL: Duration = Duration::from_secs(10);
+// Floor between announcements under DefendPolicy::Always, so two hosts
+// defending the same address can't drive each other into a storm
+pub const ALWAYS_INTERVAL: Duration = Duration::from_secs(1);
+
+// What to do when another host uses one of our addresses. These are
+// options (b) and (c) of RFC 5227 section 2.4, plus watching only; option
+// (a), giving the address up, is the operating system's business.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DefendPolicy {
+    // Announce once; a second conflict within DEFEND_INTERVAL means the
+    // other host is defending too, so stop and only report from then on
+    #[default]
+    Once,
+    // Announce on every conflict, at most once per ALWAYS_INTERVAL
+    Always,
+    // Report conflicts without sending anything
+    LogOnly,
+}
+
+impl DefendPolicy {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "once" => Ok(DefendPolicy::Once),
+            "always" => Ok(DefendPolicy::Always),
+            "log" => Ok(DefendPolicy::LogOnly),
+            other => Err(format!("Unknown defend policy '{}'. Use: once, always, log", other)),
+        }
+    }
+}
+
+impl fmt::Display for DefendPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DefendPolicy::Once => "once",
+            DefendPolicy::Always => "always",
+            DefendPolicy::LogOnly => "log",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefendAction {
+    // A gratuitous ARP announcement went out
+    Defended,
+    // Nothing sent: the policy is log-only, or an announcement went out
+    // too recently
+    Logged,
+    // Nothing sent, and under DefendPolicy::Once nothing will be for this
+    // address until it has been quiet for DEFEND_INTERVAL
+    GaveUp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub ip: Ipv4Addr,
+    // The other host
+    pub mac: MacAddr,
+    pub at: SystemTime,
+    pub action: DefendAction,
+}
+
+#[derive(Debug, Clone)]
+pub enum DefendEvent {
+    // Listening has begun; these are the addresses being defended
+    Started(String, Vec<Ipv4Addr>),
+    Conflict(Conflict),
+    // Sending the announcement failed
+    SendFailed(Ipv4Addr, String),
+}
+
+// Decides, conflict by conflict, whether to announce.
+#[derive(Debug)]
+pub struct Defender {
+    policy: DefendPolicy,
+    last_conflict: HashMap<Ipv4Addr, Instant>,
+    last_defended: HashMap<Ipv4Addr, Instant>,
+}
+
+impl Defender {
+    pub fn new(policy: DefendPolicy) -> Self {
+        Defender {
+            policy,
+            last_conflict: HashMap::new(),
+            last_defended: HashMap::new(),
+        }
+    }
+
+    // Someone else used `ip` at `at`.
+    pub fn conflict(&mut self, ip: Ipv4Addr, at: Instant) -> DefendAction {
+        let recent = |last: Option<&Instant>, interval: Duration| {
+            last.is_some_and(|last| at.saturating_duration_since(*last) < interval)
+        };
+        let previous = self.last_conflict.insert(ip, at);
+        let action = match self.policy {
+            DefendPolicy::LogOnly => DefendAction::Logged,
+            DefendPolicy::Once if recent(previous.as_ref(), DEFEND_INTERVAL) => DefendAction::GaveUp,
+            DefendPolicy::Always if recent(self.last_defended.get(&ip), ALWAYS_INTERVAL) => DefendAction::Logged,
+            DefendPolicy::Once | DefendPolicy::Always => DefendAction::Defended,
+        };
+        if action == DefendAction::Defended {
+            self.last_defended.insert(ip, at);
+        }
+        action
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DefendConfig {
+    pub interface: Option<String>,
+    // Stop after this long; defend until interrupted when None
+    pub duration: Option<Duration>,
+    pub policy: DefendPolicy,
+    pub channel: ChannelTuning,
+}
+
+#[cfg(feature = "engine")]
+const STOP_POLL: Duration = Duration::from_millis(200);
+
+// Watch for ARP packets from other hosts that use one of the interface's
+// own IPv4 addresses as their sender address, and answer them with a
+// gratuitous ARP announcement as the policy allows.
+#[cfg(feature = "engine")]
+pub fn defend<F: FnMut(&DefendEvent)>(config: &DefendConfig, mut on_event: F) -> Result<(), String> {
+    let interface = resolve_interface(config.interface.as_deref())?;
+    let own_mac = interface.mac.ok_or_else(|| format!("{} has no MAC address", interface.name))?;
+    let own_ips: Vec<Ipv4Addr> = interface.ips.iter()
+        .filter_map(|network| match network.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    if own_ips.is_empty() {
+        return Err(format!("{} has no IPv4 address to defend", interface.name));
+    }
+    let (mut tx, rx) = open_channel(&interface, &config.channel)?;
+
+    let (frames, receiver) = spawn_receiver(rx, is_arp_frame);
+    let deadline = config.duration.map(|duration| Instant::now() + duration);
+    let mut defender = Defender::new(config.policy);
+    let mut buf = [0u8; ARP_FRAME_LEN];
+    on_event(&DefendEvent::Started(interface.name.clone(), own_ips.clone()));
+
+    loop {
+        let wait = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                remaining.min(STOP_POLL)
+            }
+            None => STOP_POLL,
+        };
+        let received = match frames.recv_timeout(wait) {
+            Ok(received) => received,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        let Some(frame) = parse_arp_frame(&received.data) else {
+            continue;
+        };
+        let mac = MacAddr::from(frame.arp.sender_mac);
+        let ip = frame.arp.sender_ip;
+        if mac == own_mac || MacAddr::from(frame.eth_source) == own_mac || !own_ips.contains(&ip) {
+            continue;
+        }
+
+        let action = defender.conflict(ip, received.at);
+        if action == DefendAction::Defended {
+            let sent = ArpFrame::announcement(own_mac.octets(), ip)
+                .write(&mut buf)
+                .map_err(|e| e.to_string())
+                .and_then(|len| match tx.send_to(&buf[..len], None) {
+                    Some(Ok(())) => Ok(()),
+                    Some(Err(e)) => Err(e.to_string()),
+                    None => Err("the channel cannot send".to_string()),
+                });
+            if let Err(e) = sent {
+                on_event(&DefendEvent::SendFailed(ip, e));
+            }
+        }
+        on_event(&DefendEvent::Conflict(Conflict { ip, mac, at: received.wall_clock, action }));
+    }
+
+    receiver.finish();
+    Ok(())
+}
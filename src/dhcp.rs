@@ -0,0 +1,306 @@
+// DHCP client broadcasts heard on the segment. A DISCOVER or REQUEST
+// carries the client's hardware address, often its hostname, and the list
+// of options it asks for (option 55), whose order is characteristic of the
+// operating system's DHCP client. None of it requires sending anything.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use pnet_base::MacAddr;
+
+use crate::snooping::RelayAgent;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const IP_PROTOCOL_UDP: u8 = 17;
+const DHCP_SERVER_PORT: u16 = 67;
+const BOOTREQUEST: u8 = 1;
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+// Offsets within the BOOTP message
+const CIADDR: usize = 12;
+const CHADDR: usize = 28;
+const OPTIONS: usize = 240;
+
+// Option codes
+const OPTION_PAD: u8 = 0;
+const OPTION_HOSTNAME: u8 = 12;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_PARAMETERS: u8 = 55;
+const OPTION_VENDOR_CLASS: u8 = 60;
+const OPTION_RELAY_AGENT: u8 = 82;
+const OPTION_END: u8 = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DhcpMessage {
+    Discover,
+    Request,
+    Decline,
+    Release,
+    Inform,
+    Other(u8),
+}
+
+impl DhcpMessage {
+    fn from_code(code: u8) -> Self {
+        match code {
+            1 => DhcpMessage::Discover,
+            3 => DhcpMessage::Request,
+            4 => DhcpMessage::Decline,
+            7 => DhcpMessage::Release,
+            8 => DhcpMessage::Inform,
+            other => DhcpMessage::Other(other),
+        }
+    }
+}
+
+impl fmt::Display for DhcpMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DhcpMessage::Discover => f.write_str("DISCOVER"),
+            DhcpMessage::Request => f.write_str("REQUEST"),
+            DhcpMessage::Decline => f.write_str("DECLINE"),
+            DhcpMessage::Release => f.write_str("RELEASE"),
+            DhcpMessage::Inform => f.write_str("INFORM"),
+            DhcpMessage::Other(code) => write!(f, "type {}", code),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpClient {
+    // The client hardware address from the message, not the frame's
+    // Ethernet source, which a relay or bridge may have rewritten
+    pub mac: MacAddr,
+    pub message: DhcpMessage,
+    // Option 12
+    pub hostname: Option<String>,
+    // Option 60, e.g. "MSFT 5.0" or "android-dhcp-13"
+    pub vendor_class: Option<String>,
+    // Option 55, in the order the client listed them
+    pub parameters: Vec<u8>,
+    // Option 50, or ciaddr for a client renewing an address it has
+    pub requested_ip: Option<Ipv4Addr>,
+    // Option 82, only present on the copy a snooping switch or relay
+    // forwards to the server, so seen on a mirror port or the server's
+    // segment rather than the client's
+    pub relay_agent: Option<RelayAgent>,
+}
+
+impl DhcpClient {
+    // The option 55 list in the usual comma-separated fingerprint form,
+    // e.g. "1,3,6,15,31,33,43,44,46,47,119,121,249,252".
+    pub fn fingerprint(&self) -> Option<String> {
+        if self.parameters.is_empty() {
+            return None;
+        }
+        let codes: Vec<String> = self.parameters.iter().map(u8::to_string).collect();
+        Some(codes.join(","))
+    }
+
+    // Whether two messages describe the same client the same way. The
+    // message type and requested address change from one message to the
+    // next without anything about the device changing, and the client's own
+    // broadcast lacks the option 82 its relayed copy carries.
+    pub fn same_identity(&self, other: &DhcpClient) -> bool {
+        self.mac == other.mac
+            && self.hostname == other.hostname
+            && self.vendor_class == other.vendor_class
+            && self.parameters == other.parameters
+            && (other.relay_agent.is_none() || self.relay_agent == other.relay_agent)
+    }
+}
+
+fn u16_at(buf: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*buf.get(at)?, *buf.get(at + 1)?]))
+}
+
+fn ipv4_at(buf: &[u8], at: usize) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = buf.get(at..at + 4)?.try_into().ok()?;
+    Some(Ipv4Addr::from(octets))
+}
+
+fn text(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+// The BOOTP message inside an Ethernet frame carrying IPv4/UDP to the
+// server port. Fragments are skipped; client messages fit in one frame.
+fn bootp_payload(frame: &[u8]) -> Option<&[u8]> {
+    if u16_at(frame, 12)? != ETHERTYPE_IPV4 {
+        return None;
+    }
+    let ip = frame.get(14..)?;
+    let header_len = (*ip.first()? & 0x0f) as usize * 4;
+    let fragment = u16_at(ip, 6)?;
+    if header_len < 20 || *ip.get(9)? != IP_PROTOCOL_UDP || fragment & 0x3fff != 0 {
+        return None;
+    }
+    let udp = ip.get(header_len..)?;
+    if u16_at(udp, 2)? != DHCP_SERVER_PORT {
+        return None;
+    }
+    udp.get(8..)
+}
+
+// Whether a frame is a DHCP message from a client, without decoding it.
+pub fn is_dhcp_frame(frame: &[u8]) -> bool {
+    bootp_payload(frame).and_then(|bootp| bootp.first()) == Some(&BOOTREQUEST)
+}
+
+// Decode a DHCP client message. Returns None for anything else, including
+// plain BOOTP without the DHCP magic cookie.
+pub fn parse_dhcp_frame(frame: &[u8]) -> Option<DhcpClient> {
+    let bootp = bootp_payload(frame)?;
+    // Ethernet hardware type with 6-byte addresses
+    if bootp.get(..3)? != [BOOTREQUEST, 1, 6] || bootp.get(OPTIONS - 4..OPTIONS)? != MAGIC_COOKIE {
+        return None;
+    }
+    let chaddr: [u8; 6] = bootp.get(CHADDR..CHADDR + 6)?.try_into().ok()?;
+    let mut client = DhcpClient {
+        mac: MacAddr::from(chaddr),
+        message: DhcpMessage::Other(0),
+        hostname: None,
+        vendor_class: None,
+        parameters: Vec::new(),
+        requested_ip: ipv4_at(bootp, CIADDR).filter(|ip| !ip.is_unspecified()),
+        relay_agent: None,
+    };
+    let mut message = None;
+    let mut at = OPTIONS;
+    while let Some(&code) = bootp.get(at) {
+        match code {
+            OPTION_END => break,
+            OPTION_PAD => {
+                at += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let len = *bootp.get(at + 1)? as usize;
+        let value = bootp.get(at + 2..at + 2 + len)?;
+        match code {
+            OPTION_MESSAGE_TYPE => message = value.first().copied(),
+            OPTION_HOSTNAME => client.hostname = text(value),
+            OPTION_VENDOR_CLASS => client.vendor_class = text(value),
+            OPTION_PARAMETERS => client.parameters = value.to_vec(),
+            OPTION_REQUESTED_IP => client.requested_ip = ipv4_at(value, 0).or(client.requested_ip),
+            OPTION_RELAY_AGENT => client.relay_agent = RelayAgent::parse(value),
+            _ => {}
+        }
+        at += 2 + len;
+    }
+    // Without option 53 this is BOOTP, not DHCP
+    client.message = DhcpMessage::from_code(message?);
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snooping::SwitchPort;
+
+    fn frame(dump: &str) -> Vec<u8> {
+        dump.split_whitespace().map(|byte| u8::from_str_radix(byte, 16).unwrap()).collect()
+    }
+
+    // Windows 10 asking for an address, and the copy of its REQUEST that a
+    // snooping switch relayed to the server with option 82 added
+    const DHCP_DISCOVER: &str = "ff ff ff ff ff ff 3c d9 2b 10 20 30 08 00 45 00 \
+        01 4a 1c 2e 00 00 80 11 1d 76 00 00 00 00 ff ff \
+        ff ff 00 44 00 43 01 36 75 1f 01 01 06 00 39 03 \
+        f3 26 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 3c d9 2b 10 20 30 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 63 82 53 63 35 01 01 3d 07 01 \
+        3c d9 2b 10 20 30 32 04 c0 00 02 0a 0c 0f 44 45 \
+        53 4b 54 4f 50 2d 37 51 32 4b 34 4c 4d 3c 08 4d \
+        53 46 54 20 35 2e 30 37 0e 01 03 06 0f 1f 21 2b \
+        2c 2e 2f 77 79 f9 fc ff";
+    const DHCP_RELAYED_REQUEST: &str = "00 00 5e 00 01 01 00 1b 54 c2 80 41 08 00 45 00 \
+        01 64 00 42 00 00 ff 11 35 47 c0 00 02 fe c0 00 \
+        02 01 00 43 00 43 01 50 0a bb 01 01 06 01 39 03 \
+        f3 26 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 c0 00 02 fe 3c d9 2b 10 20 30 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 63 82 53 63 35 01 03 3d 07 01 \
+        3c d9 2b 10 20 30 32 04 c0 00 02 0a 36 04 c0 00 \
+        02 01 0c 0f 44 45 53 4b 54 4f 50 2d 37 51 32 4b \
+        34 4c 4d 3c 08 4d 53 46 54 20 35 2e 30 37 0e 01 \
+        03 06 0f 1f 21 2b 2c 2e 2f 77 79 f9 fc 52 12 01 \
+        06 00 04 00 14 01 0c 02 08 00 06 00 1b 54 c2 80 \
+        00 ff";
+
+    #[test]
+    fn decodes_a_discover() {
+        let frame = frame(DHCP_DISCOVER);
+        assert!(is_dhcp_frame(&frame));
+        let client = parse_dhcp_frame(&frame).unwrap();
+        assert_eq!(client.mac, MacAddr::new(0x3c, 0xd9, 0x2b, 0x10, 0x20, 0x30));
+        assert_eq!(client.message, DhcpMessage::Discover);
+        assert_eq!(client.hostname.as_deref(), Some("DESKTOP-7Q2K4LM"));
+        assert_eq!(client.vendor_class.as_deref(), Some("MSFT 5.0"));
+        assert_eq!(client.fingerprint().as_deref(), Some("1,3,6,15,31,33,43,44,46,47,119,121,249,252"));
+        assert_eq!(client.requested_ip, Some(Ipv4Addr::new(192, 0, 2, 10)));
+        assert_eq!(client.relay_agent, None);
+    }
+
+    #[test]
+    fn decodes_a_relayed_request() {
+        let relayed = parse_dhcp_frame(&frame(DHCP_RELAYED_REQUEST)).unwrap();
+        assert_eq!(relayed.message, DhcpMessage::Request);
+        assert_eq!(relayed.mac, MacAddr::new(0x3c, 0xd9, 0x2b, 0x10, 0x20, 0x30));
+        assert_eq!(
+            relayed.relay_agent.as_ref().map(RelayAgent::switch_port),
+            Some(SwitchPort { switch: Some("00:1b:54:c2:80:00".to_string()), port: "1/12".to_string(), vlan: Some(20) })
+        );
+        // The client's own broadcast lacks option 82, the relayed copy doesn't
+        let discover = parse_dhcp_frame(&frame(DHCP_DISCOVER)).unwrap();
+        assert!(relayed.same_identity(&discover));
+        assert!(!discover.same_identity(&relayed));
+    }
+
+    #[test]
+    fn skips_bootp_and_server_replies() {
+        let discover = frame(DHCP_DISCOVER);
+        // Without the magic cookie it is plain BOOTP
+        let mut bootp = discover.clone();
+        bootp[14 + 20 + 8 + OPTIONS - 4] = 0;
+        assert_eq!(parse_dhcp_frame(&bootp), None);
+        // Without option 53 too
+        let mut no_type = discover.clone();
+        no_type[14 + 20 + 8 + OPTIONS] = OPTION_PAD;
+        no_type[14 + 20 + 8 + OPTIONS + 1] = OPTION_PAD;
+        no_type[14 + 20 + 8 + OPTIONS + 2] = OPTION_PAD;
+        assert_eq!(parse_dhcp_frame(&no_type), None);
+        // A reply goes to the client port
+        let mut reply = discover.clone();
+        reply[14 + 20 + 2..14 + 20 + 4].copy_from_slice(&68u16.to_be_bytes());
+        assert!(!is_dhcp_frame(&reply));
+        assert_eq!(parse_dhcp_frame(&reply), None);
+        // Options cut off mid-way
+        assert_eq!(parse_dhcp_frame(&discover[..discover.len() - 5]), None);
+    }
+}
@@ -0,0 +1,128 @@
+// Monitor changes sent by email. Messages are handed to the local mail
+// system with `sendmail -t`, as cron and smartd do, so the relay host,
+// TLS and credentials stay in its configuration (postfix, msmtp, ...)
+// instead of arp_scan's.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use arp_scan::meta::format_timestamp;
+use arp_scan::monitor::MonitorEvent;
+
+// Changes are collected for this long after the first one, so a sweep that
+// finds ten new hosts sends one message rather than ten
+const COLLECT_FOR: Duration = Duration::from_secs(60);
+const TICK: Duration = Duration::from_secs(1);
+const SENDMAIL: &str = "/usr/sbin/sendmail";
+
+#[derive(Debug, Default)]
+struct Batch {
+    since: Option<Instant>,
+    new: usize,
+    gone: usize,
+    changed: usize,
+    alerts: usize,
+    lines: Vec<String>,
+}
+
+impl Batch {
+    // e.g. "3 new devices, 1 gone"
+    fn subject(&self) -> String {
+        let plural = |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+        let parts = [
+            (self.new, plural(self.new, "new device", "new devices")),
+            (self.gone, format!("{} gone", self.gone)),
+            (self.changed, plural(self.changed, "changed MAC", "changed MACs")),
+            (self.alerts, plural(self.alerts, "alert", "alerts")),
+        ];
+        let parts: Vec<String> = parts.into_iter().filter(|(n, _)| *n > 0).map(|(_, text)| text).collect();
+        parts.join(", ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Mailer {
+    to: Vec<String>,
+    // What is being monitored, for the subject line
+    scope: String,
+    batch: Arc<Mutex<Batch>>,
+}
+
+impl Mailer {
+    // Start the thread that sends each batch once it has been collecting
+    // for COLLECT_FOR.
+    pub fn start(to: Vec<String>, scope: String) -> Self {
+        let mailer = Mailer { to, scope, batch: Arc::new(Mutex::new(Batch::default())) };
+        let background = mailer.clone();
+        thread::spawn(move || loop {
+            thread::sleep(TICK);
+            let due = background.batch.lock().is_ok_and(|batch| batch.since.is_some_and(|since| since.elapsed() >= COLLECT_FOR));
+            if due {
+                background.flush();
+            }
+        });
+        mailer
+    }
+
+    // Quiet hours starting and ending are routine and don't warrant a mail.
+    pub fn add(&self, event: &MonitorEvent, line: String) {
+        let Ok(mut batch) = self.batch.lock() else {
+            return;
+        };
+        match event {
+            MonitorEvent::New(..) => batch.new += 1,
+            MonitorEvent::Gone(..) => batch.gone += 1,
+            MonitorEvent::Changed { .. } => batch.changed += 1,
+            MonitorEvent::Quiet(..) => return,
+            _ => batch.alerts += 1,
+        }
+        batch.since.get_or_insert_with(Instant::now);
+        batch.lines.push(line);
+    }
+
+    // Send whatever has been collected, now. A failure is reported and the
+    // monitor carries on; the changes are still in its output.
+    pub fn flush(&self) {
+        let batch = match self.batch.lock() {
+            Ok(mut batch) => std::mem::take(&mut *batch),
+            Err(_) => return,
+        };
+        if batch.lines.is_empty() {
+            return;
+        }
+        let subject = format!("arp_scan monitor {}: {}", self.scope, batch.subject());
+        if let Err(e) = send(&self.to, &subject, &batch.lines.join("\n")) {
+            eprintln!("[{}] ERROR    mail to {} failed: {}", format_timestamp(SystemTime::now()), self.to.join(", "), e);
+        }
+    }
+}
+
+fn send(to: &[String], subject: &str, body: &str) -> Result<(), String> {
+    let program = if Path::new(SENDMAIL).exists() { SENDMAIL } else { "sendmail" };
+    let message = format!(
+        "To: {}\nSubject: {}\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\n\n{}\n",
+        to.join(", "),
+        subject,
+        body
+    );
+    // -t takes the recipients from To:, -oi keeps a line with a lone dot
+    // from ending the message
+    let mut child = Command::new(program)
+        .args(["-t", "-oi"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {}", program, e))?;
+    let written = child.stdin.take().map(|mut stdin| stdin.write_all(message.as_bytes()));
+    let status = child.wait().map_err(|e| format!("{}: {}", program, e))?;
+    if let Some(Err(e)) = written {
+        return Err(format!("{}: {}", program, e));
+    }
+    if !status.success() {
+        return Err(format!("{} failed ({})", program, status));
+    }
+    Ok(())
+}
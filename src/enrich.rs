@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::Ipv4Addr;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use arp_scan::scan::{HostReply, ScanResult};
+
+// Extra fields returned by an --enrich-exec command, keyed by interface and
+// IP. Values are kept as the JSON text the command produced so they can be
+// passed through to JSON output unchanged.
+pub type Enrichment = HashMap<(String, Ipv4Addr), Vec<(String, String)>>;
+
+const MAX_RUNNING: usize = 8;
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Length of the JSON value at the start of `text`, which must be a string,
+// number, literal, object or array. Nested values are skipped by bracket
+// counting, honouring strings and escapes.
+fn value_len(text: &str) -> Result<usize, String> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, &b) in bytes.iter().enumerate() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => {
+                    in_string = false;
+                    if depth == 0 {
+                        return Ok(i + 1);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            }
+            b',' | b'}' | b']' if depth == 0 => return Ok(i),
+            _ => {}
+        }
+    }
+    if depth == 0 && !in_string {
+        Ok(bytes.len())
+    } else {
+        Err("unterminated value".to_string())
+    }
+}
+
+// Split a JSON object into its top-level members. Keys are unescaped only
+// as far as plain strings go; values stay raw JSON.
+pub fn parse_object(text: &str) -> Result<Vec<(String, String)>, String> {
+    let body = text.trim()
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .ok_or("expected a JSON object")?;
+    let mut fields = Vec::new();
+    let mut rest = body.trim_start();
+    while !rest.is_empty() {
+        let key_len = value_len(rest)?;
+        let key = rest[..key_len].trim();
+        let key = key.strip_prefix('"')
+            .and_then(|k| k.strip_suffix('"'))
+            .ok_or_else(|| format!("expected a string key, found '{}'", key))?;
+        rest = rest[key_len..].trim_start()
+            .strip_prefix(':')
+            .ok_or_else(|| format!("expected ':' after key '{}'", key))?
+            .trim_start();
+        let len = value_len(rest)?;
+        let value = rest[..len].trim();
+        let plausible = value.starts_with(['"', '{', '[', '-'])
+            || value.starts_with(|c: char| c.is_ascii_digit())
+            || ["true", "false", "null"].contains(&value);
+        if !plausible {
+            return Err(format!("invalid value for key '{}'", key));
+        }
+        fields.push((key.to_string(), value.to_string()));
+        rest = rest[len..].trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+        } else if !rest.is_empty() {
+            return Err(format!("unexpected '{}'", rest));
+        }
+    }
+    Ok(fields)
+}
+
+// Plain-text rendering of a raw JSON value for table and CSV output.
+pub fn display_value(raw: &str) -> String {
+    match raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) if !inner.contains('\\') => inner.to_string(),
+        _ => raw.to_string(),
+    }
+}
+
+// Run the command once for a host, feeding it `input` on stdin.
+fn run_one(command: &str, input: &str, timeout: Duration) -> Result<Vec<(String, String)>, String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or("empty --enrich-exec command")?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that ignores its input may exit before reading it
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let mut stdout = child.stdout.take().ok_or("no stdout")?;
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break status,
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {:.1} seconds", timeout.as_secs_f64()));
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    let output = reader.join()
+        .map_err(|_| "failed to read output".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("exited with {}", status));
+    }
+    if output.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    parse_object(&output)
+}
+
+// Run `command` for every host, a few at a time, and collect what it
+// returns. Failures are reported per host and don't stop the others. With
+// a deadline, commands are cut short to meet it and hosts left over when
+// it passes are skipped.
+pub fn enrich(
+    command: &str,
+    scans: &[ScanResult],
+    deadline: Option<Instant>,
+    input: impl Fn(&HostReply) -> String + Sync,
+) -> Enrichment {
+    let replies: Vec<&HostReply> = scans.iter().flat_map(|scan| scan.replies.values()).collect();
+    let mut enrichment = Enrichment::new();
+    for (index, batch) in replies.chunks(MAX_RUNNING).enumerate() {
+        let timeout = match deadline {
+            Some(deadline) => COMMAND_TIMEOUT.min(deadline.saturating_duration_since(Instant::now())),
+            None => COMMAND_TIMEOUT,
+        };
+        if timeout.is_zero() {
+            let skipped = replies.len() - index * MAX_RUNNING;
+            eprintln!("Warning: out of time for --enrich-exec; {} hosts were not enriched", skipped);
+            break;
+        }
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = batch.iter()
+                .map(|reply| {
+                    let input = &input;
+                    scope.spawn(move || (reply, run_one(command, &input(reply), timeout)))
+                })
+                .collect();
+            handles.into_iter().filter_map(|handle| handle.join().ok()).collect()
+        });
+        for (reply, result) in results {
+            match result {
+                Ok(fields) => {
+                    enrichment.insert((reply.interface.clone(), reply.ip), fields);
+                }
+                Err(e) => eprintln!("Warning: --enrich-exec failed for {}: {}", reply.ip, e),
+            }
+        }
+    }
+    enrichment
+}
@@ -0,0 +1,174 @@
+// C interface for embedding the scanner as a shared library. The matching
+// declarations are in include/arpscan.h; keep the two in sync.
+//
+// Every function returns 0 on success and -1 on failure, in which case
+// arpscan_last_error() describes what went wrong.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr;
+use std::time::Duration;
+
+use crate::scan::{scan_network_with, ChannelTuning, HostReply, ScanConfig, ScanResult};
+use crate::target::expand_target;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ArpscanHost {
+    pub ip: [u8; 4],
+    pub mac: [u8; 6],
+    // Round-trip time in milliseconds, or -1 when unknown
+    pub rtt_ms: f64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct ArpscanResults {
+    pub hosts: *mut ArpscanHost,
+    pub len: usize,
+}
+
+pub type ArpscanHostCallback = extern "C" fn(host: *const ArpscanHost, user: *mut c_void);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: String) -> c_int {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    -1
+}
+
+impl From<&HostReply> for ArpscanHost {
+    fn from(reply: &HostReply) -> Self {
+        ArpscanHost {
+            ip: reply.ip.octets(),
+            mac: reply.mac.octets(),
+            rtt_ms: reply.rtt.map_or(-1.0, |rtt| rtt.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+unsafe fn c_str<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is NULL", what));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| format!("{} is not valid UTF-8", what))
+}
+
+unsafe fn run(
+    target: *const c_char,
+    interface: *const c_char,
+    timeout_ms: u32,
+    on_reply: &mut dyn FnMut(&HostReply),
+) -> Result<ScanResult, String> {
+    let targets = expand_target(c_str(target, "target")?)?;
+    let interface = if interface.is_null() {
+        None
+    } else {
+        Some(c_str(interface, "interface")?.to_string())
+    };
+    // Reads must not outlast a short timeout; a zero one is left for
+    // validation to reject
+    let timeout = Duration::from_millis(timeout_ms as u64);
+    let defaults = ScanConfig::default();
+    let config = ScanConfig {
+        timeout,
+        interface,
+        channel: ChannelTuning {
+            read_timeout: defaults.channel.read_timeout.min(timeout),
+            ..defaults.channel
+        },
+        ..defaults
+    };
+    scan_network_with(&targets, &config, on_reply)
+}
+
+/// Scan `target` (an address, range or CIDR) and store the hosts that
+/// answered in `out`. `interface` may be NULL to use the default interface.
+/// Release the results with `arpscan_free_results`.
+///
+/// # Safety
+///
+/// `target` must be a valid NUL-terminated string, `interface` NULL or a
+/// valid NUL-terminated string, and `out` a valid pointer to writable memory.
+#[no_mangle]
+pub unsafe extern "C" fn arpscan_scan(
+    target: *const c_char,
+    interface: *const c_char,
+    timeout_ms: u32,
+    out: *mut ArpscanResults,
+) -> c_int {
+    if out.is_null() {
+        return set_error("out is NULL".to_string());
+    }
+    let scan = match run(target, interface, timeout_ms, &mut |_| {}) {
+        Ok(scan) => scan,
+        Err(e) => return set_error(e),
+    };
+    let mut replies: Vec<&HostReply> = scan.replies.values().collect();
+    replies.sort_by_key(|reply| reply.ip);
+    let hosts: Box<[ArpscanHost]> = replies.into_iter().map(ArpscanHost::from).collect();
+    let len = hosts.len();
+    *out = ArpscanResults {
+        hosts: Box::into_raw(hosts) as *mut ArpscanHost,
+        len,
+    };
+    0
+}
+
+/// Like `arpscan_scan`, but calls `callback` with each host as soon as it
+/// answers instead of collecting them. The host pointer is only valid for
+/// the duration of the call. Blocks until the scan is finished.
+///
+/// # Safety
+///
+/// `target` and `interface` as for `arpscan_scan`. `callback`, unless NULL,
+/// must be safe to call with `user` from the calling thread.
+#[no_mangle]
+pub unsafe extern "C" fn arpscan_scan_stream(
+    target: *const c_char,
+    interface: *const c_char,
+    timeout_ms: u32,
+    callback: Option<ArpscanHostCallback>,
+    user: *mut c_void,
+) -> c_int {
+    let Some(callback) = callback else {
+        return set_error("callback is NULL".to_string());
+    };
+    let mut on_reply = |reply: &HostReply| {
+        let host = ArpscanHost::from(reply);
+        callback(&host, user);
+    };
+    match run(target, interface, timeout_ms, &mut on_reply) {
+        Ok(_) => 0,
+        Err(e) => set_error(e),
+    }
+}
+
+/// Free the hosts stored by `arpscan_scan` and reset `results` to empty.
+///
+/// # Safety
+///
+/// `results` must be NULL or point to results filled in by `arpscan_scan`
+/// that have not been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn arpscan_free_results(results: *mut ArpscanResults) {
+    let Some(results) = results.as_mut() else {
+        return;
+    };
+    if !results.hosts.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(results.hosts, results.len)));
+    }
+    results.hosts = ptr::null_mut();
+    results.len = 0;
+}
+
+/// The message for the last failure on this thread, or NULL if nothing has
+/// failed. The string stays valid until the next failing call on the same
+/// thread.
+#[no_mangle]
+pub extern "C" fn arpscan_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
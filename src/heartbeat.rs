@@ -0,0 +1,102 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use arp_scan::meta::format_timestamp;
+use arp_scan::monitor::MonitorStatus;
+
+use crate::control;
+
+// How often the heartbeat file is rewritten, and the longest gap between
+// watchdog pings
+const BEAT_EVERY: Duration = Duration::from_secs(10);
+const TICK: Duration = Duration::from_secs(1);
+
+// Whether systemd started us as a Type=notify service.
+pub fn notify_socket() -> bool {
+    env::var_os("NOTIFY_SOCKET").is_some_and(|socket| !socket.is_empty())
+}
+
+// Send one state change to systemd (READY=1, STOPPING=1, ...). Does
+// nothing when not running under it; failures are ignored, since the
+// service manager notices a silent service on its own.
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::net::UnixDatagram;
+
+        let Some(path) = env::var_os("NOTIFY_SOCKET").filter(|path| !path.is_empty()) else {
+            return;
+        };
+        let Ok(socket) = UnixDatagram::unbound() else {
+            return;
+        };
+        // A leading @ names a socket in the abstract namespace
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.as_encoded_bytes().strip_prefix(b"@") {
+            use std::os::linux::net::SocketAddrExt;
+            if let Ok(address) = std::os::unix::net::SocketAddr::from_abstract_name(name) {
+                let _ = socket.send_to_addr(state.as_bytes(), &address);
+            }
+            return;
+        }
+        let _ = socket.send_to(state.as_bytes(), &path);
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+// The watchdog interval systemd expects pings within, if it is watching
+// this process.
+fn watchdog() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.trim().parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.trim().parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+// Tell systemd and/or rewrite `file` while the monitor loop keeps making
+// progress. READY=1 goes out once the loop has started; after that a beat
+// is skipped whenever the loop hasn't moved since the last one and isn't
+// in the middle of a sweep, so a hung loop stops the heartbeat and the
+// supervisor can restart us.
+pub fn start(file: Option<PathBuf>, status: Arc<Mutex<MonitorStatus>>) {
+    // Half the watchdog interval, so one late beat isn't fatal
+    let every = watchdog().map_or(BEAT_EVERY, |interval| (interval / 2).min(BEAT_EVERY));
+    thread::spawn(move || {
+        let mut ready = false;
+        let mut beaten: Option<(Instant, SystemTime)> = None;
+        let mut warned = false;
+        loop {
+            thread::sleep(TICK.min(every));
+            let Ok(status) = status.lock().map(|status| status.clone()) else {
+                return;
+            };
+            let Some(alive) = status.alive else {
+                continue;
+            };
+            if let Some((at, progress)) = beaten {
+                if at.elapsed() < every || (progress == alive && !status.sweeping) {
+                    continue;
+                }
+            }
+            let state = if ready { "WATCHDOG=1" } else { "READY=1" };
+            notify(&format!("{}\nSTATUS={}", state, control::describe(&status)));
+            ready = true;
+            if let Some(file) = &file {
+                if let Err(e) = std::fs::write(file, format!("{}\n", format_timestamp(SystemTime::now()))) {
+                    if !warned {
+                        eprintln!("Warning: Failed to write heartbeat file {}: {}", file.display(), e);
+                        warned = true;
+                    }
+                }
+            }
+            beaten = Some((Instant::now(), alive));
+        }
+    });
+}
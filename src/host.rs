@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::SystemTime;
+
+use pnet_base::MacAddr;
+
+use crate::passive::PassiveResult;
+use crate::scan::ScanResult;
+
+// A device as opposed to an address: everything observed about one MAC,
+// however it was observed. Results elsewhere are keyed by IPv4 address,
+// which can't say that a printer answers for two addresses, or that the
+// IPv4 and IPv6 rows are the same laptop.
+//
+// Merging two records for the same MAC:
+//   * addresses, interfaces and labels are unions
+//   * first seen is the earliest, last seen the latest
+//   * each name source keeps its most recent name; `name` picks the most
+//     trusted source that has one (NameSource order)
+//   * the vendor is fixed by the MAC, so the first one found stays
+
+// Where a name came from, most trusted first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NameSource {
+    // The name the user gave the address by as a target
+    Target,
+    // Option 12 of the device's own DHCP messages
+    Dhcp,
+}
+
+impl fmt::Display for NameSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            NameSource::Target => "target",
+            NameSource::Dhcp => "dhcp",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    pub mac: MacAddr,
+    pub ipv4: BTreeSet<Ipv4Addr>,
+    pub ipv6: BTreeSet<Ipv6Addr>,
+    pub names: BTreeMap<NameSource, String>,
+    pub vendor: Option<String>,
+    pub labels: BTreeSet<String>,
+    pub interfaces: BTreeSet<String>,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+}
+
+impl Host {
+    pub fn new(mac: MacAddr, seen: SystemTime) -> Self {
+        Host {
+            mac,
+            ipv4: BTreeSet::new(),
+            ipv6: BTreeSet::new(),
+            names: BTreeMap::new(),
+            vendor: None,
+            labels: BTreeSet::new(),
+            interfaces: BTreeSet::new(),
+            first_seen: seen,
+            last_seen: seen,
+        }
+    }
+
+    // The most trusted name known for the device.
+    pub fn name(&self) -> Option<&str> {
+        self.names.values().next().map(String::as_str)
+    }
+
+    pub fn seen(&mut self, at: SystemTime) {
+        self.first_seen = self.first_seen.min(at);
+        self.last_seen = self.last_seen.max(at);
+    }
+
+    // Fold another record for the same device into this one. `other` is
+    // taken to be the newer observation where the two disagree.
+    pub fn merge(&mut self, other: Host) {
+        debug_assert_eq!(self.mac, other.mac);
+        self.ipv4.extend(other.ipv4);
+        self.ipv6.extend(other.ipv6);
+        self.names.extend(other.names);
+        self.vendor = self.vendor.take().or(other.vendor);
+        self.labels.extend(other.labels);
+        self.interfaces.extend(other.interfaces);
+        self.seen(other.first_seen);
+        self.seen(other.last_seen);
+    }
+}
+
+// Hosts keyed by MAC.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostTable {
+    hosts: BTreeMap<MacAddr, Host>,
+}
+
+impl HostTable {
+    pub fn new() -> Self {
+        HostTable::default()
+    }
+
+    pub fn add(&mut self, host: Host) {
+        match self.hosts.get_mut(&host.mac) {
+            Some(known) => known.merge(host),
+            None => {
+                self.hosts.insert(host.mac, host);
+            }
+        }
+    }
+
+    pub fn from_scans(scans: &[ScanResult]) -> Self {
+        let mut table = HostTable::new();
+        for scan in scans {
+            for reply in scan.replies.values() {
+                let mut host = Host::new(reply.mac, reply.received);
+                host.ipv4.insert(reply.ip);
+                host.interfaces.insert(reply.interface.clone());
+                if let Some(name) = &reply.hostname {
+                    host.names.insert(NameSource::Target, name.clone());
+                }
+                table.add(host);
+            }
+        }
+        table
+    }
+
+    pub fn from_passive(result: &PassiveResult) -> Self {
+        let mut table = HostTable::new();
+        let on = |mac: MacAddr, first: SystemTime, last: SystemTime| {
+            let mut host = Host::new(mac, first);
+            host.seen(last);
+            host.interfaces.insert(result.interface.clone());
+            host
+        };
+        for seen in result.hosts.values() {
+            let mut host = on(seen.mac, seen.first_seen, seen.last_seen);
+            host.ipv4.insert(seen.ip);
+            table.add(host);
+        }
+        for seen in result.hosts6.values() {
+            let mut host = on(seen.mac, seen.first_seen, seen.last_seen);
+            host.ipv6.insert(seen.ip);
+            table.add(host);
+        }
+        // DHCP names only attach to devices heard some other way; a
+        // DISCOVER on its own doesn't make a device present
+        for client in result.dhcp.values() {
+            if let (Some(host), Some(name)) = (table.hosts.get_mut(&client.mac), &client.hostname) {
+                host.names.insert(NameSource::Dhcp, name.clone());
+            }
+        }
+        table
+    }
+
+    // Attach `label` to every device using one of `ips`.
+    pub fn label(&mut self, ips: &[Ipv4Addr], label: &str) {
+        for host in self.hosts.values_mut() {
+            if host.ipv4.iter().any(|ip| ips.contains(ip)) {
+                host.labels.insert(label.to_string());
+            }
+        }
+    }
+
+    // Fill in vendors with `lookup`, for devices that have none yet.
+    pub fn fill_vendors<F: Fn(&MacAddr) -> Option<String>>(&mut self, lookup: F) {
+        for host in self.hosts.values_mut() {
+            if host.vendor.is_none() {
+                host.vendor = lookup(&host.mac);
+            }
+        }
+    }
+
+    pub fn get(&self, mac: &MacAddr) -> Option<&Host> {
+        self.hosts.get(mac)
+    }
+
+    // Devices in MAC order.
+    pub fn iter(&self) -> impl Iterator<Item = &Host> {
+        self.hosts.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hosts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use pnet::datalink::{self, NetworkInterface};
+
+// Whether an interface can be used for ARP scanning at all.
+pub fn is_usable(iface: &NetworkInterface) -> bool {
+    iface.is_up()
+        && !iface.is_loopback()
+        && !iface.ips.is_empty()
+        && iface.mac.is_some() // Ensure the interface has a MAC address
+}
+
+// On Windows pnet names interfaces by device GUID (\Device\NPF_{...}).
+// Extract the {...} part so it can be matched against other tools' output.
+fn device_guid(name: &str) -> Option<&str> {
+    let start = name.find('{')?;
+    let end = name[start..].find('}')? + start;
+    Some(&name[start..=end])
+}
+
+// Parse `getmac /v /fo csv /nh` output into GUID -> connection name
+// ("Ethernet", "Wi-Fi", ...). Columns are connection name, adapter,
+// physical address and transport name (\Device\Tcpip_{GUID}).
+pub fn parse_getmac_csv(text: &str) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split("\",\"").map(|f| f.trim_matches(|c| c == '"' || c == '\r')).collect();
+        if fields.len() < 4 {
+            continue;
+        }
+        if let Some(guid) = device_guid(fields[3]) {
+            names.insert(guid.to_uppercase(), fields[0].to_string());
+        }
+    }
+    names
+}
+
+// GUID -> friendly name for every adapter. Empty on platforms where the
+// interface name is already human readable.
+pub fn friendly_names() -> HashMap<String, String> {
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("getmac")
+            .args(["/v", "/fo", "csv", "/nh"])
+            .output()
+            .map(|out| parse_getmac_csv(&String::from_utf8_lossy(&out.stdout)))
+            .unwrap_or_default()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        HashMap::new()
+    }
+}
+
+// Received frames the kernel has dropped on an interface since it came up,
+// for lack of buffer space or anything to deliver them to. Only Linux
+// reports this so far.
+pub fn rx_dropped(name: &str) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string(format!("/sys/class/net/{}/statistics/rx_dropped", name))
+            .ok()
+            .and_then(|text| text.trim().parse().ok())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = name;
+        None
+    }
+}
+
+// The kernel driver behind an interface ("veth", "bridge", "macvlan",
+// "e1000e", ...), as ethtool -i reports it. Linux only.
+pub fn driver(name: &str) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::raw::{c_char, c_void};
+
+        const SIOCETHTOOL: libc::c_ulong = 0x8946;
+        const ETHTOOL_GDRVINFO: u32 = 0x3;
+        #[repr(C)]
+        struct DriverInfo {
+            cmd: u32,
+            driver: [u8; 32],
+            rest: [u8; 140],
+            counts: [u32; 5],
+        }
+        #[repr(C)]
+        struct IfReq {
+            name: [c_char; libc::IFNAMSIZ],
+            data: *mut c_void,
+            _pad: [u8; 16],
+        }
+
+        if name.len() >= libc::IFNAMSIZ {
+            return None;
+        }
+        let mut info = DriverInfo { cmd: ETHTOOL_GDRVINFO, driver: [0; 32], rest: [0; 140], counts: [0; 5] };
+        let mut request = IfReq { name: [0; libc::IFNAMSIZ], data: &mut info as *mut DriverInfo as *mut c_void, _pad: [0; 16] };
+        for (to, from) in request.name.iter_mut().zip(name.bytes()) {
+            *to = from as c_char;
+        }
+        let socket = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        if socket < 0 {
+            return None;
+        }
+        let result = unsafe { libc::ioctl(socket, SIOCETHTOOL as _, &mut request) };
+        unsafe { libc::close(socket) };
+        if result < 0 {
+            return None;
+        }
+        let len = info.driver.iter().position(|b| *b == 0).unwrap_or(info.driver.len());
+        Some(String::from_utf8_lossy(&info.driver[..len]).into_owned()).filter(|driver| !driver.is_empty())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = name;
+        None
+    }
+}
+
+// The container runtime this process runs under, if it can tell. Linux
+// only.
+pub fn container() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::path::Path;
+
+        if Path::new("/.dockerenv").exists() {
+            return Some("docker".to_string());
+        }
+        if Path::new("/run/.containerenv").exists() {
+            return Some("podman".to_string());
+        }
+        // Set by systemd-nspawn, LXC and podman
+        if let Some(name) = std::env::var("container").ok().filter(|name| !name.is_empty()) {
+            return Some(name);
+        }
+        let cgroup = std::fs::read_to_string("/proc/1/cgroup").unwrap_or_default();
+        ["kubepods", "docker", "containerd", "lxc"]
+            .into_iter()
+            .find(|runtime| cgroup.contains(runtime))
+            .map(str::to_string)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+// Inside a container, the usual interface is one end of a veth pair on a
+// bridge the host NATs for it. ARP doesn't cross that, so a scan only ever
+// finds the host's bridge address; explain that rather than let an empty
+// result speak for itself.
+pub fn container_warning(iface: &NetworkInterface) -> Option<String> {
+    let runtime = container()?;
+    let driver = driver(&iface.name)?;
+    if !matches!(driver.as_str(), "veth" | "bridge") {
+        return None;
+    }
+    Some(format!(
+        "Note: Running in a container ({}) on {}, a {} interface.\n\
+         ARP only reaches the container network behind it (usually just the host's bridge), not the host's LAN.\n\
+         To scan the LAN, run the container with the host's network (docker run --network host), give it a\n\
+         macvlan or ipvlan interface on the LAN, or scan from the host with --netns /proc/<pid>/ns/net.",
+        runtime, iface.name, driver
+    ))
+}
+
+// Move the process into another network namespace: a path such as
+// /proc/<pid>/ns/net, or a name created with ip netns add. Only the calling
+// thread moves, so this must happen before any other thread is started;
+// the threads started afterwards inherit it. Needs CAP_SYS_ADMIN. Linux
+// only.
+pub fn enter_netns(namespace: &str) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+
+        let path = if namespace.contains('/') { namespace.to_string() } else { format!("/run/netns/{}", namespace) };
+        let file = std::fs::File::open(&path).map_err(|e| format!("Failed to open network namespace {}: {}", path, e))?;
+        if unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+            return Err(format!("Failed to enter network namespace {}: {}", path, std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(format!("Network namespaces ({}) are only supported on Linux", namespace))
+    }
+}
+
+pub fn friendly_name<'a>(iface: &NetworkInterface, names: &'a HashMap<String, String>) -> Option<&'a str> {
+    let guid = device_guid(&iface.name)?;
+    names.get(&guid.to_uppercase()).map(String::as_str)
+}
+
+// Look an interface up by its system name, Windows friendly name, or (on
+// Windows) its bare GUID. Friendly names are matched case-insensitively.
+pub fn find_interface(wanted: &str) -> Result<NetworkInterface, String> {
+    let interfaces = datalink::interfaces();
+    let names = friendly_names();
+
+    let found = interfaces.iter()
+        .find(|iface| iface.name == wanted)
+        .or_else(|| interfaces.iter().find(|iface| {
+            friendly_name(iface, &names).is_some_and(|name| name.eq_ignore_ascii_case(wanted))
+        }))
+        .or_else(|| interfaces.iter().find(|iface| {
+            device_guid(&iface.name).is_some_and(|guid| guid.trim_matches(['{', '}']).eq_ignore_ascii_case(wanted.trim_matches(['{', '}'])))
+        }));
+
+    match found {
+        Some(iface) => Ok(iface.clone()),
+        None => Err(format!("Interface '{}' not found. Use --list-interfaces to see what is available.", wanted)),
+    }
+}
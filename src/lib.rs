@@ -0,0 +1,42 @@
+// Target parsing, packet building and the result and analysis types are
+// always available and don't need pnet's socket layer. Everything else is
+// optional: the raw-socket engine sits behind `engine`, and embedders can
+// turn features off with default-features = false.
+pub mod arp_cache;
+pub mod defend;
+pub mod dhcp;
+pub mod host;
+#[cfg(feature = "engine")]
+pub mod interfaces;
+pub mod mac;
+pub mod monitor;
+pub mod ndp;
+pub mod neighbor;
+pub mod notes;
+pub mod packet;
+pub mod passive;
+pub mod paths;
+pub mod privilege;
+pub mod ratelimit;
+pub mod resolve;
+pub mod route;
+pub mod sandbox;
+pub mod scan;
+pub mod snooping;
+pub mod stats;
+pub mod summary;
+pub mod target;
+pub mod topology;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "export")]
+pub mod meta;
+#[cfg(feature = "nmap")]
+pub mod nmap;
+#[cfg(feature = "export")]
+pub mod schema;
+#[cfg(feature = "export")]
+pub mod snapshot;
+#[cfg(feature = "vendor-db")]
+pub mod vendor;
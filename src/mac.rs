@@ -0,0 +1,312 @@
+use std::fmt;
+use std::str::FromStr;
+
+use pnet_base::MacAddr;
+
+// How hex digits are grouped when a MAC address is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MacStyle {
+    // aa:bb:cc:dd:ee:ff (IEEE, Linux)
+    #[default]
+    Colon,
+    // aa-bb-cc-dd-ee-ff (Windows)
+    Dash,
+    // aabb.ccdd.eeff (Cisco)
+    Dot,
+    // aabbccddeeff
+    Bare,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MacFormat {
+    pub style: MacStyle,
+    pub upper: bool,
+}
+
+impl MacFormat {
+    // A comma-separated mix of one style and one case, e.g. "dot" or
+    // "dash,upper". Whatever isn't given keeps its default.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut format = MacFormat::default();
+        for part in text.split(',').map(str::trim) {
+            match part {
+                "colon" => format.style = MacStyle::Colon,
+                "dash" => format.style = MacStyle::Dash,
+                "dot" => format.style = MacStyle::Dot,
+                "bare" => format.style = MacStyle::Bare,
+                "upper" => format.upper = true,
+                "lower" => format.upper = false,
+                other => {
+                    return Err(format!(
+                        "Unknown MAC format '{}'. Use: colon, dash, dot, bare, upper, lower",
+                        other
+                    ))
+                }
+            }
+        }
+        Ok(format)
+    }
+
+    pub fn format(&self, mac: &MacAddr) -> String {
+        self.group(&self.digits(mac, 6))
+    }
+
+    // Only the OUI is shown; the device-specific half becomes x's.
+    pub fn format_redacted(&self, mac: &MacAddr) -> String {
+        self.group(&self.digits(mac, 3))
+    }
+
+    // The first `shown` bytes as hex, padded out to 12 characters with x.
+    fn digits(&self, mac: &MacAddr, shown: usize) -> String {
+        let mut digits: String = mac.octets()[..shown].iter().map(|b| format!("{:02x}", b)).collect();
+        if self.upper {
+            digits.make_ascii_uppercase();
+        }
+        digits.extend(std::iter::repeat_n('x', 12 - digits.len()));
+        digits
+    }
+
+    fn group(&self, digits: &str) -> String {
+        let (size, separator) = match self.style {
+            MacStyle::Colon => (2, ":"),
+            MacStyle::Dash => (2, "-"),
+            MacStyle::Dot => (4, "."),
+            MacStyle::Bare => return digits.to_string(),
+        };
+        let groups: Vec<&str> = (0..digits.len()).step_by(size).map(|at| &digits[at..at + size]).collect();
+        groups.join(separator)
+    }
+}
+
+impl fmt::Display for MacFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let style = match self.style {
+            MacStyle::Colon => "colon",
+            MacStyle::Dash => "dash",
+            MacStyle::Dot => "dot",
+            MacStyle::Bare => "bare",
+        };
+        write!(f, "{},{}", style, if self.upper { "upper" } else { "lower" })
+    }
+}
+
+// A MAC address as people write it: colons or dashes between bytes
+// (leading zeros optional), Cisco dots between groups of four, or twelve
+// bare hex digits, in either case. Prints in the canonical lowercase colon
+// form; MacFormat gives the others.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MacAddress(pub MacAddr);
+
+impl MacAddress {
+    pub fn octets(&self) -> [u8; 6] {
+        self.0.octets()
+    }
+
+    pub fn is_broadcast(&self) -> bool {
+        self.octets() == [0xff; 6]
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.octets() == [0; 6]
+    }
+
+    // Group addresses, broadcast included, have the I/G bit set
+    pub fn is_multicast(&self) -> bool {
+        self.0 .0 & 0x01 != 0
+    }
+
+    // The U/L bit: set on randomized and most virtual MACs, clear when the
+    // first three bytes are a manufacturer's OUI
+    pub fn is_local(&self) -> bool {
+        self.0 .0 & 0x02 != 0
+    }
+
+    pub fn oui(&self) -> [u8; 3] {
+        [self.0 .0, self.0 .1, self.0 .2]
+    }
+
+    // A unicast address made of `prefix` followed by `random`. Without a
+    // prefix it is a locally administered one, safe to make up: it can't
+    // collide with a manufacturer's. A multicast prefix is an error rather
+    // than quietly becoming a different, unicast one.
+    pub fn random(prefix: Option<&[u8]>, random: [u8; 6]) -> Result<Self, String> {
+        let mut octets = random;
+        match prefix {
+            Some(prefix) if prefix.first().is_some_and(|first| first & 0x01 != 0) => {
+                return Err(format!(
+                    "{:02x} is a multicast prefix; host addresses need the lowest bit of the first byte clear",
+                    prefix[0]
+                ))
+            }
+            Some(prefix) => {
+                let len = prefix.len().min(6);
+                octets[..len].copy_from_slice(&prefix[..len]);
+            }
+            None => octets[0] = (octets[0] & !0x03) | 0x02,
+        }
+        Ok(MacAddress(MacAddr::new(octets[0], octets[1], octets[2], octets[3], octets[4], octets[5])))
+    }
+}
+
+impl From<MacAddr> for MacAddress {
+    fn from(mac: MacAddr) -> Self {
+        MacAddress(mac)
+    }
+}
+
+impl From<MacAddress> for MacAddr {
+    fn from(mac: MacAddress) -> Self {
+        mac.0
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&MacFormat::default().format(&self.0))
+    }
+}
+
+impl FromStr for MacAddress {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = |why: &str| format!("Invalid MAC address '{}': {}", text, why);
+        let trimmed = text.trim();
+        let octets = if trimmed.contains([':', '-']) {
+            let separator = if trimmed.contains(':') { ':' } else { '-' };
+            let parts: Vec<&str> = trimmed.split(separator).collect();
+            if parts.len() != 6 {
+                return Err(invalid("expected 6 bytes"));
+            }
+            parts
+                .iter()
+                .map(|part| match part.len() {
+                    1 | 2 => hex_bytes(&format!("{:0>2}", part)).map(|bytes| bytes[0]).ok_or_else(|| invalid("not hex")),
+                    _ => Err(invalid("each byte is one or two hex digits")),
+                })
+                .collect::<Result<Vec<u8>, String>>()?
+        } else {
+            let digits = if trimmed.contains('.') {
+                let groups: Vec<&str> = trimmed.split('.').collect();
+                if groups.len() != 3 || groups.iter().any(|group| group.len() != 4) {
+                    return Err(invalid("expected three groups of 4 hex digits"));
+                }
+                groups.concat()
+            } else {
+                trimmed.to_string()
+            };
+            if digits.len() != 12 {
+                return Err(invalid("expected 12 hex digits"));
+            }
+            hex_bytes(&digits).ok_or_else(|| invalid("not hex"))?
+        };
+        Ok(MacAddress(MacAddr::new(octets[0], octets[1], octets[2], octets[3], octets[4], octets[5])))
+    }
+}
+
+fn hex_bytes(digits: &str) -> Option<Vec<u8>> {
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len()).step_by(2).map(|at| u8::from_str_radix(&digits[at..at + 2], 16).ok()).collect()
+}
+
+// The leading bytes of an address, such as an OUI: 1 to 5 bytes written
+// like a MAC address (00:1b:21, 00-1B-21, 001b21).
+pub fn parse_prefix(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text.trim().chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+    match hex_bytes(&digits) {
+        Some(bytes) if (1..=5).contains(&bytes.len()) => Ok(bytes),
+        _ => Err(format!("Invalid MAC prefix '{}': expected 1 to 5 bytes of hex, e.g. 00:1b:21", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mac(text: &str) -> Result<[u8; 6], String> {
+        text.parse::<MacAddress>().map(|mac| mac.octets())
+    }
+
+    const AABB: [u8; 6] = [0xaa, 0xbb, 0xcc, 0x0d, 0xee, 0x0f];
+
+    #[test]
+    fn accepts_common_spellings() {
+        for text in [
+            "aa:bb:cc:0d:ee:0f",
+            "AA:BB:CC:0D:EE:0F",
+            "aa-bb-cc-0d-ee-0f",
+            "aa:bb:cc:d:ee:f",
+            "aabb.cc0d.ee0f",
+            "AABBCC0DEE0F",
+            "  aa:bb:cc:0d:ee:0f\n",
+        ] {
+            assert_eq!(mac(text), Ok(AABB), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_addresses() {
+        for text in [
+            "",
+            "aa:bb:cc:dd:ee",
+            "aa:bb:cc:dd:ee:ff:00",
+            "aa:bb:cc:dd:ee:fff",
+            "aa:bb:cc:dd:ee:",
+            "aa:bb:cc:dd:ee:gg",
+            "aa:bb-cc:dd:ee:ff",
+            "aabb.ccdd.eef",
+            "aabb.ccdd",
+            "aabbccddeef",
+            "aabbccddeeff00",
+            "+abbccddeeff",
+        ] {
+            assert!(mac(text).is_err(), "{} was accepted", text);
+        }
+    }
+
+    #[test]
+    fn formats_every_style_and_case() {
+        let addr = MacAddr::new(0x00, 0x1b, 0x21, 0xaa, 0xbc, 0x0d);
+        let cases = [
+            ("colon", "00:1b:21:aa:bc:0d", "00:1b:21:xx:xx:xx"),
+            ("dash,upper", "00-1B-21-AA-BC-0D", "00-1B-21-xx-xx-xx"),
+            ("dot", "001b.21aa.bc0d", "001b.21xx.xxxx"),
+            ("upper,bare", "001B21AABC0D", "001B21xxxxxx"),
+            ("dot,upper,lower", "001b.21aa.bc0d", "001b.21xx.xxxx"),
+        ];
+        for (spec, full, redacted) in cases {
+            let format = MacFormat::parse(spec).unwrap();
+            assert_eq!(format.format(&addr), full, "{}", spec);
+            assert_eq!(format.format_redacted(&addr), redacted, "{}", spec);
+            assert_eq!(MacFormat::parse(&format.to_string()), Ok(format));
+        }
+        assert!(MacFormat::parse("colons").is_err());
+        assert!(MacFormat::parse("dot,").is_err());
+    }
+
+    #[test]
+    fn random_keeps_prefix_and_stays_unicast() {
+        let random = [0xff; 6];
+        let local = MacAddress::random(None, random).unwrap();
+        assert!(local.is_local() && !local.is_multicast());
+        assert_eq!(local.octets()[1..], random[1..]);
+
+        let intel = MacAddress::random(Some(&[0x00, 0x1b, 0x21]), random).unwrap();
+        assert_eq!(intel.octets(), [0x00, 0x1b, 0x21, 0xff, 0xff, 0xff]);
+        assert!(!intel.is_multicast());
+
+        assert!(MacAddress::random(Some(&[0x01, 0x00, 0x5e]), random).is_err());
+        assert!(MacAddress::random(Some(&[0x33]), [0; 6]).is_err());
+    }
+
+    #[test]
+    fn prefixes_are_one_to_five_bytes() {
+        assert_eq!(parse_prefix("00:1b:21"), Ok(vec![0x00, 0x1b, 0x21]));
+        assert_eq!(parse_prefix("00-1B-21-A"), Err("Invalid MAC prefix '00-1B-21-A': expected 1 to 5 bytes of hex, e.g. 00:1b:21".to_string()));
+        assert_eq!(parse_prefix("001b.21aa.bc"), Ok(vec![0x00, 0x1b, 0x21, 0xaa, 0xbc]));
+        assert!(parse_prefix("").is_err());
+        assert!(parse_prefix("00:1b:21:aa:bc:0d").is_err());
+    }
+}
@@ -1,220 +1,688 @@
-use std::net::{IpAddr, Ipv4Addr};
-use std::time::{Duration, Instant};
-use std::io::{self, BufRead};
-use std::fs::File;
-use std::collections::HashMap;
-use std::str::FromStr;
-use std::path::Path;
-
-use pnet::datalink::{self, NetworkInterface};
-use pnet::packet::arp::{ArpHardwareTypes, ArpOperations, MutableArpPacket};
-use pnet::packet::ethernet::{EtherTypes, MutableEthernetPacket};
-use pnet::packet::{MutablePacket, Packet};
+use std::net::Ipv4Addr;
+use std::io::{self, IsTerminal};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
 use pnet::util::MacAddr;
 
-use lazy_static::lazy_static;
-
-lazy_static! {
-    static ref OUI_MAP: HashMap<String, String> = {
-        let mut map = HashMap::new();
-        if let Ok(file) = File::open("oui.txt") {
-            let reader = io::BufReader::new(file);
-            let mut current_oui = String::new();
-            
-            for line in reader.lines().filter_map(Result::ok) {
-                let line = line.trim();
-                if line.contains("(hex)") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 3 {
-                        current_oui = parts[0].replace("-", "").to_uppercase();
-                    }
-                } else if !current_oui.is_empty() && line.contains("base 16") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 4 {
-                        let manufacturer = parts[3..].join(" ");
-                        map.insert(current_oui.clone(), manufacturer);
-                    }
-                }
-            }
+use arp_scan::arp_cache;
+use arp_scan::interfaces;
+use arp_scan::mac::{MacAddress, MacFormat};
+use arp_scan::defend::{self, DefendConfig};
+use arp_scan::meta::ScanMeta;
+use arp_scan::nmap::{self, NmapHost};
+use arp_scan::monitor::{self, MonitorConfig, MonitorEvent, MonitorStatus};
+use arp_scan::notes::{self, Notes};
+use arp_scan::passive::{self, PassiveConfig};
+use arp_scan::resolve;
+use arp_scan::scan::{self, scan_interfaces, scan_network, ChannelTuning, HostReply, ScanConfig};
+use arp_scan::schema;
+use arp_scan::snapshot::Snapshot;
+use arp_scan::snooping::SnoopingTable;
+use arp_scan::target::{self, TargetBlock};
+use arp_scan::vendor::{self, normalize_vendor, PrefixDb};
+
+mod cli;
+mod control;
+mod email;
+mod enrich;
+mod heartbeat;
+mod output;
+mod selftest;
+mod service;
+
+use cli::{Command, MacAction, NoteAction, Options, OuiQuery, OutputFormat};
+
+struct Targets {
+    // The specs as given, for the export metadata
+    specs: Vec<String>,
+    ips: Vec<Ipv4Addr>,
+    // The entries of the specs, for reporting results per block
+    blocks: Vec<TargetBlock>,
+    // Host names given as targets, by the address they resolved to
+    names: HashMap<Ipv4Addr, String>,
+}
+
+fn resolve_targets(opts: &Options) -> Result<Targets, String> {
+    let specs = match opts.target.as_deref() {
+        Some("-") => target::read_targets(io::stdin().lock())?,
+        Some(target) => vec![target.to_string()],
+        None => vec![prompt_target(opts)?],
+    };
+
+    let mut spec = target::parse_targets(&specs, &mut |name| {
+        let ips = resolve::resolve_host(name)?;
+        let listed: Vec<String> = ips.iter().map(Ipv4Addr::to_string).collect();
+        status(opts, &format!("Resolved {} to {}", name, listed.join(", ")));
+        Ok(ips)
+    })?;
+    for excluded in &opts.exclude {
+        for entry in excluded.split(',').filter(|entry| !entry.trim().is_empty()) {
+            spec.exclude(entry.trim().trim_start_matches('!'))?;
         }
-        map
+    }
+    // Several blocks are probed side by side, sharing the one rate limit;
+    // shuffling mixes them anyway
+    let ips = if opts.shuffle {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        spec.shuffled(seed).collect()
+    } else {
+        spec.interleaved().collect()
     };
+    let names = spec.names().iter().cloned().collect();
+    Ok(Targets { specs, ips, blocks: spec.blocks(), names })
 }
 
-fn get_manufacturer(mac: &MacAddr) -> String {
-    let mac_prefix = format!("{:02X}{:02X}{:02X}", mac.0, mac.1, mac.2);
-    OUI_MAP.get(&mac_prefix)
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| "Unknown".to_string())
+// Ask for a target on the terminal. Never blocks on a pipe or /dev/null, so
+// wrappers and CI jobs that forget the target get an error instead of a
+// hang.
+fn prompt_target(opts: &Options) -> Result<String, String> {
+    let stdin = io::stdin();
+    if opts.no_prompt || !stdin.is_terminal() {
+        return Err("No target given. Pass TARGET or --target TARGET (e.g., 192.168.1.0/24)".to_string());
+    }
+    eprintln!("Enter network to scan (e.g., 192.168.1.0/24):");
+    let mut input = String::new();
+    let read = stdin.read_line(&mut input).map_err(|e| format!("Failed to read input: {}", e))?;
+    let input = input.trim();
+    if read == 0 || input.is_empty() {
+        return Err("No target given".to_string());
+    }
+    Ok(input.to_string())
 }
 
-fn get_default_interface() -> Option<NetworkInterface> {
-    let interfaces = datalink::interfaces();
-    interfaces
-        .into_iter()
-        .find(|iface| {
-            iface.is_up() 
-            && !iface.is_loopback() 
-            && !iface.ips.is_empty()
-            && iface.mac.is_some() // Ensure the interface has a MAC address
-        })
+// Informational messages go to stderr when stdout carries machine-readable
+// output, so they don't corrupt it.
+fn status(opts: &Options, message: &str) {
+    if opts.output == OutputFormat::Table && opts.format.is_none() && opts.export.is_none() {
+        println!("{}", message);
+    } else {
+        eprintln!("{}", message);
+    }
 }
 
-fn parse_cidr(cidr: &str) -> Result<(Ipv4Addr, u32), String> {
-    let parts: Vec<&str> = cidr.split('/').collect();
-    if parts.len() != 2 {
-        return Err("Invalid CIDR format. Use: x.x.x.x/n".to_string());
+// Why a scan from inside a container may only find the host's bridge
+fn print_container_note(opts: &Options) {
+    let names: Vec<Option<&str>> = if opts.interfaces.is_empty() {
+        vec![None]
+    } else {
+        opts.interfaces.iter().map(|name| Some(name.as_str())).collect()
+    };
+    for name in names {
+        if let Some(note) = scan::resolve_interface(name).ok().as_ref().and_then(interfaces::container_warning) {
+            status(opts, &note);
+        }
     }
+}
 
-    let ip = Ipv4Addr::from_str(parts[0])
-        .map_err(|e| format!("Invalid IP address: {}", e))?;
-    let mask = parts[1]
-        .parse::<u32>()
-        .map_err(|e| format!("Invalid subnet mask: {}", e))?;
-
-    if mask > 32 {
-        return Err("Subnet mask must be between 0 and 32".to_string());
+fn print_privilege_note(opts: &Options) {
+    // The socket was opened by whoever handed it over
+    if opts.channel.fd.is_some() {
+        return;
     }
-
-    Ok((ip, mask))
+    status(opts, "Note: This program requires elevated privileges.");
+    status(opts, if cfg!(target_os = "windows") {
+        "On Windows, run as Administrator (e.g., from an elevated Command Prompt or PowerShell)."
+    } else {
+        "On Linux, run with sudo (e.g., 'sudo ./arp_scan')."
+    });
 }
 
-fn ip_to_u32(ip: Ipv4Addr) -> u32 {
-    u32::from_be_bytes(ip.octets())
+fn scan_config(opts: &Options) -> ScanConfig {
+    ScanConfig {
+        pad_to: opts.pad_to,
+        rounds: opts.rounds.max(1),
+        max_hosts: opts.max_hosts,
+        quick: opts.quick,
+        pps: opts.pps,
+        adaptive_rate: opts.adaptive_rate,
+        probe_style: opts.probe_style,
+        dup_policy: opts.dup_policy,
+        channel: opts.channel,
+        vlan: opts.vlan,
+        timeout: opts.timeout(),
+        max_duration: opts.max_duration.map(|max| max - max / 20),
+        lock_dir: opts.lock_dir.clone(),
+        ..ScanConfig::default()
+    }
 }
 
-fn u32_to_ip(n: u32) -> Ipv4Addr {
-    Ipv4Addr::from(n.to_be_bytes())
-}
+// An explicit --oui-file must load; otherwise take the first file found on
+// the search path, falling back to the embedded copy when built with it.
+fn find_vendor_db(opts: &Options) -> Result<PrefixDb, String> {
+    if let Some(path) = &opts.oui_file {
+        return PrefixDb::load(path)
+            .map_err(|e| format!("Failed to read vendor database {}: {}", path.display(), e));
+    }
 
-fn scan_network(cidr: &str) -> Result<(), String> {
-    if !Path::new("oui.txt").exists() {
-        return Err("oui.txt file not found. Ensure it’s in the same directory as the executable.".to_string());
+    let paths = vendor::search_paths();
+    if let Some(path) = paths.iter().find(|path| path.is_file()) {
+        return PrefixDb::load(path)
+            .map_err(|e| format!("Failed to read vendor database {}: {}", path.display(), e));
     }
 
-    let (network, mask) = parse_cidr(cidr)?;
-    let interface = get_default_interface()
-        .ok_or_else(|| {
-            let os_msg = if cfg!(target_os = "windows") {
-                "No suitable network interface found. Ensure you’re running with administrative privileges."
-            } else {
-                "No suitable network interface found. Ensure you’re running with root privileges (e.g., sudo)."
-            };
-            os_msg.to_string()
-        })?;
-
-    let source_ip = interface.ips.iter()
-        .find(|ip| ip.is_ipv4())
-        .map(|ip| match ip.ip() {
-            IpAddr::V4(ip) => ip,
-            _ => Ipv4Addr::new(0, 0, 0, 0),
-        })
-        .unwrap_or(Ipv4Addr::new(0, 0, 0, 0));
-
-    let source_mac = interface.mac.unwrap_or(MacAddr::zero());
-    let mut results = HashMap::new();
-
-    let network_u32 = ip_to_u32(network) & !(0xFFFFFFFF >> mask);
-    let host_count = 1 << (32 - mask);
-    
-    let (mut tx, mut rx) = match datalink::channel(&interface, Default::default()) {
-        Ok(datalink::Channel::Ethernet(tx, rx)) => (tx, rx),
-        Ok(_) => return Err("Unhandled channel type".to_string()),
+    #[cfg(feature = "embed-oui")]
+    {
+        PrefixDb::from_reader(vendor::EMBEDDED_OUI)
+            .map_err(|e| format!("Failed to read embedded vendor database: {}", e))
+    }
+    #[cfg(not(feature = "embed-oui"))]
+    {
+        let searched: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+        Err(format!(
+            "oui.txt file not found. Searched: {}. Use --oui-file or set ARPSCAN_OUI.",
+            searched.join(", ")
+        ))
+    }
+}
+
+// Vendor names are cosmetic, so a missing database only costs a warning
+// unless --require-oui asks for the strict behavior.
+fn load_vendor_db(opts: &Options) -> Result<PrefixDb, String> {
+    match find_vendor_db(opts) {
+        Ok(db) => Ok(db),
+        Err(e) if opts.require_oui => Err(e),
         Err(e) => {
-            let os_msg = if cfg!(target_os = "windows") {
-                format!("Failed to create channel: {}. Ensure you’re running as Administrator.", e)
-            } else {
-                format!("Failed to create channel: {}. Ensure you’re running with sudo.", e)
-            };
-            return Err(os_msg);
+            eprintln!("Warning: {} Vendors will be shown as Unknown.", e);
+            Ok(PrefixDb::default())
         }
+    }
+}
+
+// Notes are an extra; an unreadable notes file shouldn't stop a scan.
+fn load_notes() -> Notes {
+    let Some(path) = notes::default_path() else {
+        return Notes::default();
     };
+    Notes::load(&path).unwrap_or_else(|e| {
+        eprintln!("Warning: {}", e);
+        Notes::default()
+    })
+}
+
+fn run_note(action: &NoteAction) -> Result<(), String> {
+    let path = notes::default_path()
+        .ok_or("No place to keep notes: set ARPSCAN_NOTES or HOME")?;
+    let mut notes = Notes::load(&path)?;
+    match action {
+        NoteAction::List => {
+            if notes.is_empty() {
+                println!("No notes in {}", path.display());
+            }
+            for (mac, note) in notes.iter() {
+                println!("{}  {}", mac, note);
+            }
+        }
+        NoteAction::Show(mac) => match notes.get(mac) {
+            Some(note) => println!("{}", note),
+            None => return Err(format!("No note for {}", mac)),
+        },
+        NoteAction::Set(mac, text) => {
+            notes.set(*mac, text);
+            notes.save(&path)?;
+        }
+        NoteAction::Clear(mac) => {
+            if notes.remove(mac).is_none() {
+                return Err(format!("No note for {}", mac));
+            }
+            notes.save(&path)?;
+        }
+    }
+    Ok(())
+}
 
-    let start_time = Instant::now();
-    
-    for i in 1..host_count - 1 {
-        let target_ip = u32_to_ip(network_u32 + i);
-        
-        let mut ethernet_buffer = [0u8; 42];
-        let mut ethernet_packet = MutableEthernetPacket::new(&mut ethernet_buffer)
-            .ok_or("Failed to create ethernet packet")?;
-
-        ethernet_packet.set_destination(MacAddr::broadcast());
-        ethernet_packet.set_source(source_mac);
-        ethernet_packet.set_ethertype(EtherTypes::Arp);
-
-        let mut arp_buffer = [0u8; 28];
-        let mut arp_packet = MutableArpPacket::new(&mut arp_buffer)
-            .ok_or("Failed to create ARP packet")?;
-
-        arp_packet.set_hardware_type(ArpHardwareTypes::Ethernet);
-        arp_packet.set_protocol_type(EtherTypes::Ipv4);
-        arp_packet.set_hw_addr_len(6);
-        arp_packet.set_proto_addr_len(4);
-        arp_packet.set_operation(ArpOperations::Request);
-        arp_packet.set_sender_hw_addr(source_mac);
-        arp_packet.set_sender_proto_addr(source_ip);
-        arp_packet.set_target_hw_addr(MacAddr::zero());
-        arp_packet.set_target_proto_addr(target_ip);
-
-        ethernet_packet.set_payload(arp_packet.packet_mut());
-
-        if tx.send_to(ethernet_packet.packet(), None).is_none() {
-            println!("Warning: Failed to send packet to {}", target_ip);
-        }
-    }
-
-    while start_time.elapsed() < Duration::from_secs(5) {
-        match rx.next() {
-            Ok(packet) => {
-                if let Some(ethernet) = pnet::packet::ethernet::EthernetPacket::new(packet) {
-                    if ethernet.get_ethertype() == EtherTypes::Arp {
-                        if let Some(arp) = pnet::packet::arp::ArpPacket::new(ethernet.payload()) {
-                            if arp.get_operation() == ArpOperations::Reply {
-                                let ip = arp.get_sender_proto_addr();
-                                let mac = arp.get_sender_hw_addr();
-                                results.insert(ip, mac);
-                            }
-                        }
+fn run_mac(action: &MacAction, format: &MacFormat) -> Result<(), String> {
+    match action {
+        MacAction::Validate(addresses) => {
+            let mut invalid = 0;
+            for text in addresses {
+                match text.parse::<MacAddress>() {
+                    Ok(mac) => println!("{}  {}", format.format(&mac.0), describe_mac(&mac)),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        invalid += 1;
                     }
                 }
             }
-            Err(e) => {
-                println!("Warning: Failed to receive packet: {}", e);
-                continue;
+            if invalid > 0 {
+                return Err(format!("{} of {} addresses are invalid", invalid, addresses.len()));
+            }
+        }
+        MacAction::Random { prefix, count } => {
+            for _ in 0..*count {
+                let mac = MacAddress::random(prefix.as_deref(), rand::random())?;
+                println!("{}", format.format(&mac.0));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe_mac(mac: &MacAddress) -> String {
+    if mac.is_broadcast() {
+        return "broadcast".to_string();
+    }
+    if mac.is_zero() {
+        return "all zeros (not a usable address)".to_string();
+    }
+    let kind = if mac.is_multicast() { "multicast" } else { "unicast" };
+    if mac.is_local() {
+        format!("{}, locally administered", kind)
+    } else {
+        let oui = mac.oui();
+        format!("{}, universally administered (OUI {:02x}:{:02x}:{:02x})", kind, oui[0], oui[1], oui[2])
+    }
+}
+
+fn run_oui(query: &OuiQuery, oui_file: Option<PathBuf>) -> Result<(), String> {
+    let vendors = find_vendor_db(&Options { oui_file, ..Options::default() })?;
+    match query {
+        OuiQuery::Lookup(prefixes) => {
+            let mut unknown = 0;
+            for (prefix, bits) in prefixes {
+                let vendor = vendors.lookup_prefix(*prefix, *bits).map(|name| normalize_vendor(name, false));
+                if vendor.is_none() {
+                    unknown += 1;
+                }
+                println!("{}  {}", vendor::format_prefix(*prefix, *bits), vendor.as_deref().unwrap_or("Unknown"));
+            }
+            if unknown > 0 {
+                return Err(format!("No vendor found for {} of {} addresses", unknown, prefixes.len()));
+            }
+        }
+        OuiQuery::Search(text) => {
+            let found = vendors.search(text);
+            if found.is_empty() {
+                return Err(format!("No vendor matches '{}'", text));
+            }
+            for (prefix, bits, name) in found {
+                println!("{}  {}", vendor::format_prefix(prefix, bits), normalize_vendor(name, false));
             }
         }
     }
+    Ok(())
+}
 
-    println!("\nScan Results:");
-    println!("{:<16} {:<18} {}", "IP Address", "MAC Address", "Manufacturer");
-    println!("{:-<16} {:-<18} {:-<30}", "", "", "");
-    for (ip, mac) in results {
-        let manufacturer = get_manufacturer(&mac);
-        println!("{:<16} {:<18} {}", ip, mac, manufacturer);
+// Confirm the OS ARP cache entries for one interface with unicast requests.
+// TARGET is optional here and only narrows down which entries are checked.
+fn run_unicast_verify(opts: &Options) -> Result<(), String> {
+    let interface = scan::resolve_interface(opts.interfaces.first().map(String::as_str))?;
+    let mut entries: Vec<arp_cache::CacheEntry> = arp_cache::entries()
+        .into_iter()
+        .filter(|entry| entry.interface == interface.name)
+        .collect();
+    if opts.target.is_some() {
+        let targets = resolve_targets(opts)?.ips;
+        let wanted: HashSet<Ipv4Addr> = targets.into_iter().collect();
+        entries.retain(|entry| wanted.contains(&entry.ip));
+    }
+    if entries.is_empty() {
+        return Err(format!("No ARP cache entries to verify on {}", interface.name));
     }
+    entries.sort_by_key(|entry| entry.ip);
+    print_privilege_note(opts);
+    print_container_note(opts);
+    let vendors = load_vendor_db(opts)?;
 
+    let config = ScanConfig {
+        interface: Some(interface.name.clone()),
+        unicast: entries.iter().map(|entry| (entry.ip, entry.mac)).collect(),
+        ..scan_config(opts)
+    };
+    let ips: Vec<Ipv4Addr> = entries.iter().map(|entry| entry.ip).collect();
+    let scan = scan_network(&ips, &config)?;
+    output::print_send_failures(std::slice::from_ref(&scan), opts);
+    output::print_rate_limit_hints(std::slice::from_ref(&scan));
+    output::print_verify_results(&entries, &scan, &vendors, opts);
     Ok(())
 }
 
-fn main() {
-    println!("Enter network to scan (e.g., 192.168.1.0/24):");
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read input");
+// What is left of --max-duration for the scan itself. Whatever name
+// resolution and loading the vendor database took is already gone, a
+// quarter of the rest is held back for --enrich-exec, and a twentieth for
+// printing the results.
+fn scan_budget(opts: &Options, started: Instant) -> Option<Duration> {
+    let max = opts.max_duration?;
+    let left = (max - max / 20).saturating_sub(started.elapsed());
+    Some(if opts.enrich_exec.is_some() { left * 3 / 4 } else { left })
+}
 
-    let cidr = input.trim();
+// The results, with the number of targets and their blocks when they came
+// from a scan rather than a snapshot.
+fn collect_results(opts: &Options, started: Instant) -> Result<(Snapshot, Option<usize>, Vec<TargetBlock>), String> {
+    if !opts.load.is_empty() {
+        let snapshots = opts.load.iter().map(|path| Snapshot::load(path)).collect::<Result<Vec<_>, _>>()?;
+        let merged = Snapshot::merge_with(snapshots, opts.roam_window.filter(|window| !window.is_zero()))?;
+        return Ok((merged, None, Vec::new()));
+    }
+    let Targets { specs, ips: targets, blocks, names } = resolve_targets(opts)?;
+    print_privilege_note(opts);
+    print_container_note(opts);
 
-    println!("Note: This program requires elevated privileges.");
-    println!("{}", if cfg!(target_os = "windows") {
-        "On Windows, run as Administrator (e.g., from an elevated Command Prompt or PowerShell)."
-    } else {
-        "On Linux, run with sudo (e.g., 'sudo ./arp_scan')."
+    let config = ScanConfig {
+        max_duration: scan_budget(opts, started),
+        ..scan_config(opts)
+    };
+    let mut scans = scan_interfaces(&targets, &config, &opts.interfaces)?;
+    output::print_send_failures(&scans, opts);
+    output::print_rate_limit_hints(&scans);
+    for reply in scans.iter_mut().flat_map(|scan| scan.replies.values_mut()) {
+        reply.hostname = names.get(&reply.ip).cloned();
+    }
+    let mut meta = ScanMeta::from_scans(&scans);
+    meta.targets = specs;
+    meta.settings = opts.settings();
+    Ok((Snapshot { meta, scans }, Some(targets.len()), blocks))
+}
+
+fn run_scan(opts: &Options) -> Result<(), String> {
+    if opts.unicast_verify {
+        return run_unicast_verify(opts);
+    }
+    let started = Instant::now();
+    let vendors = load_vendor_db(opts)?;
+    let (snapshot, target_count, blocks) = collect_results(opts, started)?;
+    // Per-block sections only say something with more than one block
+    let blocks = if blocks.len() > 1 { blocks } else { Vec::new() };
+    if let Some(path) = &opts.save {
+        snapshot.save(path)?;
+        status(opts, &format!("Saved results to {}", path.display()));
+    }
+    let Snapshot { meta, mut scans } = snapshot;
+
+    let notes = load_notes();
+    for reply in scans.iter_mut().flat_map(|scan| scan.replies.values_mut()) {
+        reply.note = notes.get(&reply.mac).map(str::to_string);
+    }
+    if let Some(path) = &opts.dhcp_snooping {
+        let snooping = SnoopingTable::load(path)?;
+        for reply in scans.iter_mut().flat_map(|scan| scan.replies.values_mut()) {
+            reply.switch_port = snooping.get(&reply.mac).cloned();
+        }
+    }
+    let extras = match &opts.enrich_exec {
+        Some(command) => {
+            let deadline = opts.max_duration.map(|max| started + max - max / 20);
+            enrich::enrich(command, &scans, deadline, |reply| output::enrich_input(reply, &vendors, opts))
+        }
+        None => enrich::Enrichment::new(),
+    };
+    match (&opts.format, opts.output) {
+        _ if opts.export.is_some() => output::print_export(&scans, &vendors, opts),
+        (Some(template), _) => output::print_template(&scans, template, &vendors, opts),
+        (None, OutputFormat::Table) => {
+            if blocks.is_empty() {
+                output::print_results(&scans, &vendors, opts);
+            } else {
+                output::print_block_results(&scans, &blocks, &vendors, opts);
+            }
+            output::print_notes(&scans, opts);
+            output::print_enrichment(&scans, &extras, opts);
+            output::print_latency_summary(&scans);
+            if let (true, Some(target_count)) = (opts.quick, target_count) {
+                let seen: usize = scans.iter().map(|scan| scan.replies.len()).sum();
+                println!(
+                    "\nQuick mode: {} hosts seen among {} targets. Only likely gateways were probed, so treat this as a rough estimate.",
+                    seen,
+                    target_count
+                );
+            }
+            if opts.analyze_padding {
+                output::print_padding_report(&scans, opts);
+            }
+            if opts.topology_hints {
+                output::print_topology_hints(&scans, opts);
+            }
+            if opts.summary {
+                output::print_summary(&scans, &vendors, opts);
+            }
+        }
+        (None, OutputFormat::Json) => output::print_json(&meta, &scans, &blocks, &extras, &vendors, opts),
+        (None, OutputFormat::Csv) => output::print_csv(&meta, &scans, &extras, &vendors, opts),
+        (None, OutputFormat::Markdown) => output::print_markdown(&meta, &scans, &vendors, opts),
+        (None, OutputFormat::Html) => {
+            if let Some(path) = &opts.report {
+                output::write_html(path, &meta, &scans, &vendors, opts)?;
+                status(opts, &format!("Wrote report to {}", path.display()));
+            }
+        }
+    }
+    if target_count.is_some() {
+        status(opts, "\nScan completed successfully");
+    }
+    Ok(())
+}
+
+fn run_compare(opts: &Options, nmap_path: &Path) -> Result<(), String> {
+    let nmap_hosts = nmap::load_hosts(nmap_path)?;
+    let targets = resolve_targets(opts)?.ips;
+    print_privilege_note(opts);
+    print_container_note(opts);
+    let vendors = load_vendor_db(opts)?;
+
+    // nmap has no notion of interfaces, so the first interface to see an
+    // address speaks for it.
+    let scans = scan_interfaces(&targets, &scan_config(opts), &opts.interfaces)?;
+    output::print_send_failures(&scans, opts);
+    output::print_rate_limit_hints(&scans);
+    let mut results: HashMap<Ipv4Addr, HostReply> = HashMap::new();
+    for scan in scans {
+        for (ip, reply) in scan.replies {
+            results.entry(ip).or_insert(reply);
+        }
+    }
+    let in_scope: HashSet<Ipv4Addr> = targets.iter().copied().collect();
+    let nmap_up: HashMap<Ipv4Addr, &NmapHost> = nmap_hosts.iter()
+        .filter(|host| host.up && in_scope.contains(&host.ip))
+        .map(|host| (host.ip, host))
+        .collect();
+
+    let mut both: Vec<&HostReply> = results.values().filter(|r| nmap_up.contains_key(&r.ip)).collect();
+    let mut arp_only: Vec<&HostReply> = results.values().filter(|r| !nmap_up.contains_key(&r.ip)).collect();
+    let mut nmap_only: Vec<&NmapHost> = nmap_up.values().copied().filter(|h| !results.contains_key(&h.ip)).collect();
+    both.sort_by_key(|r| r.ip);
+    arp_only.sort_by_key(|r| r.ip);
+    nmap_only.sort_by_key(|h| h.ip);
+
+    println!("\nComparison with {}:", nmap_path.display());
+    println!("{:<16} {:<18} {:<10} Manufacturer", "IP Address", "MAC Address", "Found by");
+    println!("{:-<16} {:-<18} {:-<10} {:-<30}", "", "", "", "");
+    for reply in &both {
+        output::print_compare_row(reply.ip, Some(&reply.mac), "both", &vendors, opts);
+    }
+    for reply in &arp_only {
+        output::print_compare_row(reply.ip, Some(&reply.mac), "arp only", &vendors, opts);
+    }
+    for host in &nmap_only {
+        let mac = host.mac.as_deref().and_then(|m| m.parse::<MacAddr>().ok());
+        output::print_compare_row(host.ip, mac.as_ref(), "nmap only", &vendors, opts);
+    }
+
+    let out_of_scope = nmap_hosts.iter().filter(|h| h.up && !in_scope.contains(&h.ip)).count();
+    println!("\n{} found by both, {} only by ARP, {} only by nmap", both.len(), arp_only.len(), nmap_only.len());
+    if out_of_scope > 0 {
+        println!("{} nmap hosts were outside the scanned targets and were ignored", out_of_scope);
+    }
+    Ok(())
+}
+
+fn run_passive(opts: &Options) -> Result<(), String> {
+    print_privilege_note(opts);
+    print_container_note(opts);
+    let vendors = load_vendor_db(opts)?;
+    let snooping = match &opts.dhcp_snooping {
+        Some(path) => SnoopingTable::load(path)?,
+        None => SnoopingTable::default(),
+    };
+    let config = PassiveConfig {
+        interface: opts.interfaces.first().cloned(),
+        duration: opts.duration,
+        storm: opts.storm,
+        channel: opts.channel,
+        subnets: opts.local_subnets.clone(),
+    };
+
+    match opts.duration {
+        Some(duration) => println!("\nListening for {} seconds...", duration.as_secs()),
+        None => println!("\nListening until interrupted (Ctrl-C)..."),
+    }
+    let result = passive::listen(&config, |event| output::print_passive_event(event, &vendors, opts))?;
+    output::print_passive_summary(&result, &snooping, &vendors, opts);
+    output::print_receive_errors(&result.interface, result.receive_errors);
+    Ok(())
+}
+
+fn run_defend(opts: &Options) -> Result<(), String> {
+    print_privilege_note(opts);
+    print_container_note(opts);
+    let vendors = load_vendor_db(opts)?;
+    let config = DefendConfig {
+        interface: opts.interfaces.first().cloned(),
+        duration: opts.duration,
+        policy: opts.defend_policy,
+        channel: opts.channel,
+    };
+    defend::defend(&config, |event| output::print_defend_event(event, &vendors, opts))
+}
+
+// Every sweep opens a new channel, which only the first could do once
+// --drop-privileges has given up root. Open the socket now instead and
+// sweep on it as with --fd; it stays open for the rest of the process.
+#[cfg(target_os = "linux")]
+fn monitor_fd(opts: &Options) -> Result<Option<i32>, String> {
+    if !opts.channel.drop_privileges || opts.channel.fd.is_some() {
+        return Ok(opts.channel.fd);
+    }
+    scan::open_packet_socket().map(Some)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn monitor_fd(opts: &Options) -> Result<Option<i32>, String> {
+    Ok(opts.channel.fd)
+}
+
+fn run_monitor(opts: &Options) -> Result<(), String> {
+    let mut targets = resolve_targets(opts)?.ips;
+    targets.retain(|ip| !opts.traps.contains(ip));
+    print_privilege_note(opts);
+    print_container_note(opts);
+    let vendors = load_vendor_db(opts)?;
+    let config = MonitorConfig {
+        scan: ScanConfig {
+            interface: opts.interfaces.first().cloned(),
+            channel: ChannelTuning { fd: monitor_fd(opts)?, ..opts.channel },
+            ..scan_config(opts)
+        },
+        interval: opts.interval.unwrap_or(MonitorConfig::default().interval),
+        duration: opts.duration,
+        flap: opts.flap,
+        storm: opts.storm,
+        debounce: opts.debounce,
+        presence: opts.presence,
+        quiet_hours: opts.quiet_hours,
+        traps: opts.traps.clone(),
+    };
+
+    if let Some(hours) = config.quiet_hours {
+        println!("\nNo sweeps during quiet hours {}", hours);
+    }
+    if !config.traps.is_empty() {
+        println!("\nWatching {} trap addresses", config.traps.len());
+    }
+    let mailer = (!opts.email_to.is_empty()).then(|| {
+        let scope = opts.target.clone().or_else(|| opts.interfaces.first().cloned()).unwrap_or_else(|| "local network".to_string());
+        email::Mailer::start(opts.email_to.clone(), scope)
     });
+    let report = |event: &MonitorEvent| {
+        output::print_monitor_event(event, &vendors, opts);
+        if let Some(mailer) = &mailer {
+            mailer.add(event, output::monitor_event_line(event, &vendors, opts));
+        }
+    };
+    let heartbeat = opts.heartbeat_file.is_some() || heartbeat::notify_socket();
+    if opts.control_socket.is_none() && !heartbeat {
+        println!("\nMonitoring {} targets, sweeping every {} seconds...", targets.len(), config.interval.as_secs());
+        let result = monitor::monitor(&targets, &config, report);
+        if let Some(mailer) = &mailer {
+            mailer.flush();
+        }
+        return result;
+    }
 
-    match scan_network(cidr) {
-        Ok(()) => println!("\nScan completed successfully"),
-        Err(e) => println!("Error: {}", e),
+    let (requests, controls) = mpsc::channel();
+    let status = Arc::new(Mutex::new(MonitorStatus::default()));
+    if let Some(socket) = &opts.control_socket {
+        control::serve(socket, requests, Arc::clone(&status))?;
+    }
+    println!("\nMonitoring {} targets, sweeping every {} seconds...", targets.len(), config.interval.as_secs());
+    if let Some(socket) = &opts.control_socket {
+        println!("Control socket: {} (arp_scan ctl rescan|pause|resume|status)", socket.display());
     }
-}
\ No newline at end of file
+    if heartbeat {
+        heartbeat::start(opts.heartbeat_file.clone(), Arc::clone(&status));
+    }
+    let result = monitor::monitor_controlled(&targets, &config, &controls, &status, report);
+    if let Some(mailer) = &mailer {
+        mailer.flush();
+    }
+    heartbeat::notify("STOPPING=1");
+    if let Some(socket) = &opts.control_socket {
+        let _ = std::fs::remove_file(socket);
+    }
+    result
+}
+
+fn run_ctl(command: &str, socket: &Path) -> Result<(), String> {
+    let reply = control::send(socket, command)?;
+    println!("{}", reply);
+    Ok(())
+}
+
+fn main() {
+    let parsed = cli::parse_args(std::env::args().skip(1));
+    // Before any thread starts, so they all run in the namespace too
+    if let Some(namespace) = parsed.as_ref().ok().and_then(Command::netns) {
+        if let Err(e) = interfaces::enter_netns(namespace) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    let result = match parsed {
+        Ok(Command::Scan(opts)) => run_scan(&opts),
+        Ok(Command::Compare(opts, nmap_path)) => run_compare(&opts, &nmap_path),
+        Ok(Command::Monitor(opts)) => run_monitor(&opts),
+        Ok(Command::Passive(opts)) => run_passive(&opts),
+        Ok(Command::Defend(opts)) => run_defend(&opts),
+        Ok(Command::Note(action)) => run_note(&action),
+        Ok(Command::Mac(action, format)) => run_mac(&action, &format),
+        Ok(Command::Oui(query, oui_file)) => run_oui(&query, oui_file),
+        Ok(Command::Ctl(command, socket)) => run_ctl(&command, &socket),
+        Ok(Command::InstallService(args, print)) => service::install(&args, print),
+        Ok(Command::UninstallService) => service::uninstall(),
+        Ok(Command::SelfTest) => load_vendor_db(&Options::default()).and_then(|vendors| selftest::run(&vendors)),
+        Ok(Command::Schema) => {
+            print!("{}", schema::JSON_SCHEMA);
+            return;
+        }
+        Ok(Command::ListInterfaces(_)) => {
+            output::print_interfaces();
+            return;
+        }
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error: {}\nRun with --help for usage.", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Fail visibly to whatever is driving us, not just to a human reader
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
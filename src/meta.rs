@@ -0,0 +1,92 @@
+use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pnet_base::MacAddr;
+
+use crate::scan::ScanResult;
+
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Provenance recorded alongside every export so archived results can be
+// traced back to the run that produced them.
+#[derive(Debug, Clone)]
+pub struct ScanMeta {
+    pub scan_id: String,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    pub interface: String,
+    pub source_ip: Ipv4Addr,
+    pub source_mac: MacAddr,
+    pub targets: Vec<String>,
+    pub settings: Vec<(String, String)>,
+}
+
+impl ScanMeta {
+    pub fn from_scan(scan: &ScanResult) -> Self {
+        ScanMeta {
+            scan_id: new_scan_id(),
+            started: scan.started,
+            finished: scan.finished,
+            interface: scan.interface.clone(),
+            source_ip: scan.source_ip,
+            source_mac: scan.source_mac,
+            targets: Vec::new(),
+            settings: Vec::new(),
+        }
+    }
+
+    // Metadata for a scan run on several interfaces at once: the overall
+    // time span, the interface names comma separated, and the source
+    // addresses of the first interface.
+    pub fn from_scans(scans: &[ScanResult]) -> Self {
+        let mut meta = Self::from_scan(&scans[0]);
+        for scan in &scans[1..] {
+            meta.started = meta.started.min(scan.started);
+            meta.finished = meta.finished.max(scan.finished);
+            meta.interface.push(',');
+            meta.interface.push_str(&scan.interface);
+        }
+        meta
+    }
+}
+
+// Random (version 4) UUID in the usual hyphenated form.
+pub fn new_scan_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+// Convert days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// Format a timestamp as RFC 3339 in UTC with millisecond precision.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
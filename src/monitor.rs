@@ -0,0 +1,941 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::net::Ipv4Addr;
+#[cfg(feature = "engine")]
+use std::sync::{mpsc, Mutex};
+#[cfg(feature = "engine")]
+use std::time::Instant;
+use std::time::{Duration, SystemTime};
+
+use pnet_base::MacAddr;
+
+use crate::passive::{StormAlert, StormThresholds};
+#[cfg(feature = "engine")]
+use crate::passive::{self, PassiveConfig, PassiveEvent};
+use crate::scan::ScanConfig;
+#[cfg(feature = "engine")]
+use crate::interfaces;
+#[cfg(feature = "engine")]
+use crate::scan::scan_network;
+
+// How many binding changes within `window` count as flapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlapThresholds {
+    pub changes: usize,
+    pub window: Duration,
+}
+
+impl Default for FlapThresholds {
+    fn default() -> Self {
+        FlapThresholds {
+            changes: 3,
+            window: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlapAlert {
+    // One IP keeps moving between MACs (HA pair fighting, ARP spoofing)
+    Ip {
+        ip: Ipv4Addr,
+        macs: Vec<MacAddr>,
+        changes: usize,
+        first: SystemTime,
+        last: SystemTime,
+    },
+    // One MAC keeps turning up with different IPs
+    Mac {
+        mac: MacAddr,
+        ips: Vec<Ipv4Addr>,
+        changes: usize,
+        first: SystemTime,
+        last: SystemTime,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum FlapKey {
+    Ip(Ipv4Addr),
+    Mac(MacAddr),
+}
+
+// Remembers recent binding changes per IP and per MAC. Once a subject has
+// changed `changes` times within the window it alerts, then stays quiet
+// for a window so a sustained flap doesn't alert on every change.
+#[derive(Debug)]
+pub struct FlapDetector {
+    thresholds: FlapThresholds,
+    ip_changes: HashMap<Ipv4Addr, VecDeque<(SystemTime, MacAddr, MacAddr)>>,
+    mac_changes: HashMap<MacAddr, VecDeque<(SystemTime, BTreeSet<Ipv4Addr>)>>,
+    last_alert: HashMap<FlapKey, SystemTime>,
+}
+
+impl FlapDetector {
+    pub fn new(thresholds: FlapThresholds) -> Self {
+        FlapDetector {
+            thresholds,
+            ip_changes: HashMap::new(),
+            mac_changes: HashMap::new(),
+            last_alert: HashMap::new(),
+        }
+    }
+
+    fn within(&self, since: SystemTime, at: SystemTime) -> bool {
+        at.duration_since(since).unwrap_or_default() < self.thresholds.window
+    }
+
+    fn cooled_down(&mut self, key: FlapKey, at: SystemTime) -> bool {
+        if let Some(last) = self.last_alert.get(&key) {
+            if self.within(*last, at) {
+                return false;
+            }
+        }
+        self.last_alert.insert(key, at);
+        true
+    }
+
+    // `ip` moved from MAC `old` to `new`.
+    pub fn ip_changed(&mut self, ip: Ipv4Addr, old: MacAddr, new: MacAddr, at: SystemTime) -> Option<FlapAlert> {
+        let window = self.thresholds.window;
+        let history = self.ip_changes.entry(ip).or_default();
+        history.push_back((at, old, new));
+        while history.front().is_some_and(|(seen, _, _)| at.duration_since(*seen).unwrap_or_default() >= window) {
+            history.pop_front();
+        }
+        if history.len() < self.thresholds.changes {
+            return None;
+        }
+        let mut macs: Vec<MacAddr> = history.iter().flat_map(|(_, old, new)| [*old, *new]).collect();
+        macs.sort();
+        macs.dedup();
+        let (changes, first, last) = (history.len(), history[0].0, at);
+        if !self.cooled_down(FlapKey::Ip(ip), at) {
+            return None;
+        }
+        Some(FlapAlert::Ip { ip, macs, changes, first, last })
+    }
+
+    // `mac` answered for a different set of IPs than in the previous sweep.
+    // Comparing whole sets keeps hosts that legitimately own several
+    // addresses from looking like they flap.
+    pub fn mac_changed(&mut self, mac: MacAddr, ips: BTreeSet<Ipv4Addr>, at: SystemTime) -> Option<FlapAlert> {
+        let window = self.thresholds.window;
+        let history = self.mac_changes.entry(mac).or_default();
+        history.push_back((at, ips));
+        while history.front().is_some_and(|(seen, _)| at.duration_since(*seen).unwrap_or_default() >= window) {
+            history.pop_front();
+        }
+        if history.len() < self.thresholds.changes {
+            return None;
+        }
+        let ips: BTreeSet<Ipv4Addr> = history.iter().flat_map(|(_, ips)| ips.iter().copied()).collect();
+        let (changes, first, last) = (history.len(), history[0].0, at);
+        if !self.cooled_down(FlapKey::Mac(mac), at) {
+            return None;
+        }
+        Some(FlapAlert::Mac { mac, ips: ips.into_iter().collect(), changes, first, last })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrapKind {
+    // Someone sent a request for the trap address
+    Asked,
+    // Someone answered for it or used it as their own address
+    Claimed,
+}
+
+// A trap address is an unused IP that nothing legitimate has a reason to
+// talk to, so any ARP for it suggests someone sweeping the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrapAlert {
+    pub trap: Ipv4Addr,
+    pub kind: TrapKind,
+    pub mac: MacAddr,
+    // Sender address of the packet; 0.0.0.0 for a DAD probe
+    pub from: Ipv4Addr,
+    pub at: SystemTime,
+}
+
+// Alerts on every touch of a trap address, except that the same sender
+// doing the same thing to the same trap is only reported once a minute.
+#[derive(Debug)]
+pub struct TrapDetector {
+    traps: HashSet<Ipv4Addr>,
+    cooldown: Duration,
+    last_alert: HashMap<(Ipv4Addr, TrapKind, MacAddr), SystemTime>,
+}
+
+impl TrapDetector {
+    pub fn new(traps: &[Ipv4Addr]) -> Self {
+        TrapDetector {
+            traps: traps.iter().copied().collect(),
+            cooldown: Duration::from_secs(60),
+            last_alert: HashMap::new(),
+        }
+    }
+
+    pub fn is_trap(&self, ip: &Ipv4Addr) -> bool {
+        self.traps.contains(ip)
+    }
+
+    pub fn touched(&mut self, trap: Ipv4Addr, kind: TrapKind, mac: MacAddr, from: Ipv4Addr, at: SystemTime) -> Option<TrapAlert> {
+        if !self.is_trap(&trap) {
+            return None;
+        }
+        if let Some(last) = self.last_alert.get(&(trap, kind, mac)) {
+            if at.duration_since(*last).unwrap_or_default() < self.cooldown {
+                return None;
+            }
+        }
+        self.last_alert.insert((trap, kind, mac), at);
+        Some(TrapAlert { trap, kind, mac, from, at })
+    }
+}
+
+// How sure monitor has to be before reporting a host as gone or new, so a
+// client that dozes through a sweep doesn't flap between the two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Debounce {
+    // Consecutive sweeps a known host must miss before it is gone...
+    pub misses: u32,
+    // ...and how long it must have gone unheard, by sweeps or passively
+    pub grace: Duration,
+    // Consecutive sweeps a new host must answer before it is reported
+    pub arrivals: u32,
+}
+
+impl Default for Debounce {
+    fn default() -> Self {
+        Debounce {
+            misses: 1,
+            grace: Duration::ZERO,
+            arrivals: 1,
+        }
+    }
+}
+
+// An alternative to Debounce: each host has a presence score between 0 and
+// 1 that jumps towards 1 whenever it is heard and decays with its half-life
+// in between. A host becomes present when its score reaches `present` and
+// away when it falls below `away`; the gap between the two keeps a phone
+// that answers one sweep in three from coming and going all day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PresenceModel {
+    pub half_life: Duration,
+    pub present: f64,
+    pub away: f64,
+}
+
+impl Default for PresenceModel {
+    fn default() -> Self {
+        PresenceModel {
+            half_life: Duration::from_secs(600),
+            present: 0.6,
+            away: 0.2,
+        }
+    }
+}
+
+// How far a sighting moves the score towards 1. A sweep reply is direct
+// evidence; passive traffic might be a stale cache talking.
+const SWEEP_WEIGHT: f64 = 0.7;
+const SIGHTING_WEIGHT: f64 = 0.5;
+// Scores of hosts never reported are dropped below this
+const FORGET_SCORE: f64 = 0.01;
+
+impl PresenceModel {
+    pub fn parse_thresholds(text: &str) -> Result<(f64, f64), String> {
+        let (present, away) = text.split_once(',')
+            .ok_or_else(|| format!("Invalid presence thresholds '{}': expected PRESENT,AWAY, e.g. 0.6,0.2", text))?;
+        let parse = |value: &str| match value.trim().parse::<f64>() {
+            Ok(score) if (0.0..=1.0).contains(&score) => Ok(score),
+            _ => Err(format!("Invalid presence score '{}': expected a number from 0 to 1", value.trim())),
+        };
+        let (present, away) = (parse(present)?, parse(away)?);
+        if away >= present {
+            return Err(format!("The away score ({}) must be below the present score ({})", away, present));
+        }
+        Ok((present, away))
+    }
+
+    fn decayed(&self, score: f64, since: SystemTime, at: SystemTime) -> f64 {
+        let elapsed = at.duration_since(since).unwrap_or_default();
+        score * 0.5f64.powf(elapsed.as_secs_f64() / self.half_life.as_secs_f64().max(f64::EPSILON))
+    }
+}
+
+// A host's score as of the last time it changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Presence {
+    score: f64,
+    at: SystemTime,
+}
+
+// A daily window during which monitor sends no probes, e.g. 22:00-06:00.
+// Times are local on Unix and UTC elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    // Minutes after midnight; the window may wrap past midnight
+    start: u32,
+    end: u32,
+}
+
+impl QuietHours {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let minutes = |time: &str| -> Option<u32> {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+            (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+        };
+        let invalid = || format!("Invalid quiet hours '{}'. Use HH:MM-HH:MM, e.g. 22:00-06:00", text);
+        let (start, end) = text.split_once('-').ok_or_else(invalid)?;
+        let (start, end) = (minutes(start).ok_or_else(invalid)?, minutes(end).ok_or_else(invalid)?);
+        if start == end {
+            return Err(format!("Quiet hours '{}' are empty", text));
+        }
+        Ok(QuietHours { start, end })
+    }
+
+    pub fn contains(&self, at: SystemTime) -> bool {
+        let minute = minute_of_day(at);
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}-{:02}:{:02}", self.start / 60, self.start % 60, self.end / 60, self.end % 60)
+    }
+}
+
+#[cfg(unix)]
+fn minute_of_day(at: SystemTime) -> u32 {
+    let secs = at.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs() as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm we pass it
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return ((secs % 86_400) / 60) as u32;
+    }
+    (tm.tm_hour * 60 + tm.tm_min) as u32
+}
+
+#[cfg(not(unix))]
+fn minute_of_day(at: SystemTime) -> u32 {
+    let secs = at.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    ((secs % 86_400) / 60) as u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorEvent {
+    New(Ipv4Addr, MacAddr, SystemTime),
+    Gone(Ipv4Addr, MacAddr, SystemTime),
+    Changed { ip: Ipv4Addr, old: MacAddr, new: MacAddr, at: SystemTime },
+    Flapping(FlapAlert),
+    Storm(StormAlert),
+    Trap(TrapAlert),
+    // Quiet hours started (true) or ended (false)
+    Quiet(bool, SystemTime),
+    // A sweep or listening failed; the monitor carries on
+    Failed(String, SystemTime),
+}
+
+// Current IP to MAC bindings for the monitored targets, turned into events
+// as sweeps and passive sightings come in.
+#[derive(Debug)]
+pub struct Tracker {
+    known: HashMap<Ipv4Addr, MacAddr>,
+    last_sweep: HashMap<MacAddr, BTreeSet<Ipv4Addr>>,
+    flaps: FlapDetector,
+    debounce: Debounce,
+    last_heard: HashMap<Ipv4Addr, SystemTime>,
+    // Sweeps in a row a known host has missed
+    missed: HashMap<Ipv4Addr, u32>,
+    // Sweeps in a row a not yet reported host has answered
+    arriving: HashMap<Ipv4Addr, u32>,
+    // Replaces the debounce rules when set
+    presence: Option<PresenceModel>,
+    scores: HashMap<Ipv4Addr, Presence>,
+}
+
+impl Tracker {
+    pub fn new(flap: FlapThresholds, debounce: Debounce) -> Self {
+        Tracker {
+            known: HashMap::new(),
+            last_sweep: HashMap::new(),
+            flaps: FlapDetector::new(flap),
+            debounce,
+            last_heard: HashMap::new(),
+            missed: HashMap::new(),
+            arriving: HashMap::new(),
+            presence: None,
+            scores: HashMap::new(),
+        }
+    }
+
+    // Decide presence by score rather than by counting sweeps.
+    pub fn with_presence(mut self, model: Option<PresenceModel>) -> Self {
+        self.presence = model;
+        self
+    }
+
+    // Raise the score of `ip` for a sighting of the given weight and
+    // return the new score.
+    fn heard(&mut self, model: &PresenceModel, ip: Ipv4Addr, weight: f64, at: SystemTime) -> f64 {
+        let score = self.score(model, ip, at);
+        let score = score + (1.0 - score) * weight;
+        self.scores.insert(ip, Presence { score, at });
+        score
+    }
+
+    fn score(&self, model: &PresenceModel, ip: Ipv4Addr, at: SystemTime) -> f64 {
+        self.scores.get(&ip).map_or(0.0, |presence| model.decayed(presence.score, presence.at, at))
+    }
+
+    // The presence score of `ip` now, when scoring is enabled.
+    pub fn presence(&self, ip: Ipv4Addr, at: SystemTime) -> Option<f64> {
+        self.presence.as_ref().map(|model| self.score(model, ip, at))
+    }
+
+    fn bind(&mut self, ip: Ipv4Addr, mac: MacAddr, at: SystemTime, events: &mut Vec<MonitorEvent>) {
+        match self.known.insert(ip, mac) {
+            None => events.push(MonitorEvent::New(ip, mac, at)),
+            Some(old) if old != mac => {
+                events.push(MonitorEvent::Changed { ip, old, new: mac, at });
+                if let Some(alert) = self.flaps.ip_changed(ip, old, mac, at) {
+                    events.push(MonitorEvent::Flapping(alert));
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    // A full sweep is authoritative, within the debounce limits: a known
+    // host that didn't answer is gone once it has missed enough sweeps and
+    // been silent long enough.
+    pub fn sweep(&mut self, bindings: &HashMap<Ipv4Addr, MacAddr>, at: SystemTime) -> Vec<MonitorEvent> {
+        let mut events = match self.presence {
+            Some(model) => self.sweep_scored(&model, bindings, at),
+            None => self.sweep_debounced(bindings, at),
+        };
+        self.flap_check(bindings, at, &mut events);
+        events
+    }
+
+    // With a presence model a sweep is just more evidence: answering hosts
+    // score higher, and anyone whose score has decayed below the away
+    // threshold is gone.
+    fn sweep_scored(&mut self, model: &PresenceModel, bindings: &HashMap<Ipv4Addr, MacAddr>, at: SystemTime) -> Vec<MonitorEvent> {
+        let mut events = Vec::new();
+        let mut ips: Vec<&Ipv4Addr> = bindings.keys().collect();
+        ips.sort();
+        for ip in ips {
+            self.last_heard.insert(*ip, at);
+            let score = self.heard(model, *ip, SWEEP_WEIGHT, at);
+            if self.known.contains_key(ip) || score >= model.present {
+                self.bind(*ip, bindings[ip], at, &mut events);
+            }
+        }
+
+        let mut absent: Vec<(Ipv4Addr, MacAddr)> = self.known.iter()
+            .filter(|(ip, _)| !bindings.contains_key(ip))
+            .map(|(ip, mac)| (*ip, *mac))
+            .collect();
+        absent.sort();
+        for (ip, mac) in absent {
+            if self.score(model, ip, at) < model.away {
+                self.known.remove(&ip);
+                self.last_heard.remove(&ip);
+                self.scores.remove(&ip);
+                events.push(MonitorEvent::Gone(ip, mac, at));
+            }
+        }
+        let scores: Vec<Ipv4Addr> = self.scores.keys().copied().collect();
+        for ip in scores {
+            if !self.known.contains_key(&ip) && self.score(model, ip, at) < FORGET_SCORE {
+                self.scores.remove(&ip);
+            }
+        }
+        events
+    }
+
+    fn sweep_debounced(&mut self, bindings: &HashMap<Ipv4Addr, MacAddr>, at: SystemTime) -> Vec<MonitorEvent> {
+        let mut events = Vec::new();
+        let mut ips: Vec<&Ipv4Addr> = bindings.keys().collect();
+        ips.sort();
+        for ip in ips {
+            self.last_heard.insert(*ip, at);
+            self.missed.remove(ip);
+            if !self.known.contains_key(ip) {
+                let seen = self.arriving.entry(*ip).or_insert(0);
+                *seen += 1;
+                if *seen < self.debounce.arrivals {
+                    continue;
+                }
+                self.arriving.remove(ip);
+            }
+            self.bind(*ip, bindings[ip], at, &mut events);
+        }
+        self.arriving.retain(|ip, _| bindings.contains_key(ip));
+
+        let mut absent: Vec<(Ipv4Addr, MacAddr)> = self.known.iter()
+            .filter(|(ip, _)| !bindings.contains_key(ip))
+            .map(|(ip, mac)| (*ip, *mac))
+            .collect();
+        absent.sort();
+        for (ip, mac) in absent {
+            let missed = self.missed.entry(ip).or_insert(0);
+            *missed += 1;
+            let silent = self.last_heard.get(&ip)
+                .map_or(Duration::MAX, |heard| at.duration_since(*heard).unwrap_or_default());
+            if *missed < self.debounce.misses || silent < self.debounce.grace {
+                continue;
+            }
+            self.known.remove(&ip);
+            self.missed.remove(&ip);
+            self.last_heard.remove(&ip);
+            events.push(MonitorEvent::Gone(ip, mac, at));
+        }
+        events
+    }
+
+    fn flap_check(&mut self, bindings: &HashMap<Ipv4Addr, MacAddr>, at: SystemTime, events: &mut Vec<MonitorEvent>) {
+        let mut by_mac: HashMap<MacAddr, BTreeSet<Ipv4Addr>> = HashMap::new();
+        for (ip, mac) in bindings {
+            by_mac.entry(*mac).or_default().insert(*ip);
+        }
+        let mut macs: Vec<&MacAddr> = by_mac.keys().collect();
+        macs.sort();
+        for mac in macs {
+            let ips = &by_mac[mac];
+            if self.last_sweep.get(mac).is_some_and(|previous| previous != ips) {
+                if let Some(alert) = self.flaps.mac_changed(*mac, ips.clone(), at) {
+                    events.push(MonitorEvent::Flapping(alert));
+                }
+            }
+        }
+        self.last_sweep = by_mac;
+    }
+
+    // Number of hosts currently bound.
+    pub fn known(&self) -> usize {
+        self.known.len()
+    }
+
+    // Traffic heard between sweeps. Silence proves nothing, so sightings
+    // only ever add or change bindings. When arrivals are debounced, new
+    // hosts are left for the sweeps to confirm; scored, they count for a
+    // little less than a reply.
+    pub fn sighting(&mut self, ip: Ipv4Addr, mac: MacAddr, at: SystemTime) -> Vec<MonitorEvent> {
+        let mut events = Vec::new();
+        if let Some(model) = self.presence {
+            let score = self.heard(&model, ip, SIGHTING_WEIGHT, at);
+            if self.known.contains_key(&ip) || score >= model.present {
+                self.last_heard.insert(ip, at);
+                self.bind(ip, mac, at, &mut events);
+            }
+            return events;
+        }
+        if !self.known.contains_key(&ip) && self.debounce.arrivals > 1 {
+            return events;
+        }
+        self.last_heard.insert(ip, at);
+        self.missed.remove(&ip);
+        self.bind(ip, mac, at, &mut events);
+        events
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitorConfig {
+    pub scan: ScanConfig,
+    // Time between the end of one sweep and the start of the next, spent
+    // listening passively
+    pub interval: Duration,
+    // Stop after this long; run until interrupted when None
+    pub duration: Option<Duration>,
+    pub flap: FlapThresholds,
+    pub storm: StormThresholds,
+    pub debounce: Debounce,
+    // Presence scoring in place of `debounce`
+    pub presence: Option<PresenceModel>,
+    // No sweeps during these hours; passive listening continues
+    pub quiet_hours: Option<QuietHours>,
+    // Unused addresses to watch: they are probed with every sweep, and any
+    // request for them or reply from them raises a Trap event
+    pub traps: Vec<Ipv4Addr>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        MonitorConfig {
+            scan: ScanConfig::default(),
+            interval: Duration::from_secs(60),
+            duration: None,
+            flap: FlapThresholds::default(),
+            storm: StormThresholds::default(),
+            debounce: Debounce::default(),
+            presence: None,
+            quiet_hours: None,
+            traps: Vec::new(),
+        }
+    }
+}
+
+// Requests a running monitor accepts, e.g. from a control socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    // Sweep now instead of waiting for the interval
+    Rescan,
+    // Stop sending probes; passive listening carries on
+    Pause,
+    Resume,
+}
+
+// How the monitor itself is doing, as opposed to the network: rising
+// counts here mean the probe is the problem. Totals since it started.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Health {
+    // Probes the channel refused to send, even after a retry
+    pub send_errors: u64,
+    // Probes skipped to finish a sweep within --max-duration
+    pub unsent: u64,
+    // Failed reads from the capture channel, sweeping or listening
+    pub receive_errors: u64,
+    // Frames the kernel dropped on the interface, where it reports them
+    pub dropped: Option<u64>,
+    pub last_sweep_took: Option<Duration>,
+    // The rate --adaptive-rate slowed the last sweep to
+    pub reduced_pps: Option<u32>,
+    // Sweeps that failed outright, e.g. with the interface locked
+    pub skipped_sweeps: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MonitorStatus {
+    pub paused: bool,
+    pub quiet: bool,
+    pub sweeps: u64,
+    pub last_sweep: Option<SystemTime>,
+    pub hosts: usize,
+    pub health: Health,
+    // When the monitor loop last made progress: after each sweep and every
+    // few moments while listening. A hung monitor stops updating it.
+    pub alive: Option<SystemTime>,
+    // A sweep is running. It may take longer than a watchdog interval at a
+    // low --pps, and ends by itself once the reply timeout has passed.
+    pub sweeping: bool,
+}
+
+// Sweep the targets, then listen passively until the next sweep is due,
+// reporting binding changes, flapping and ARP storms as they happen.
+#[cfg(feature = "engine")]
+pub fn monitor<F: FnMut(&MonitorEvent)>(targets: &[Ipv4Addr], config: &MonitorConfig, on_event: F) -> Result<(), String> {
+    let (_requests, controls) = mpsc::channel();
+    monitor_controlled(targets, config, &controls, &Mutex::new(MonitorStatus::default()), on_event)
+}
+
+// Like monitor, but takes Control requests from `controls` between and
+// during sweeps and keeps `status` up to date for whoever asks.
+#[cfg(feature = "engine")]
+pub fn monitor_controlled<F: FnMut(&MonitorEvent)>(
+    targets: &[Ipv4Addr],
+    config: &MonitorConfig,
+    controls: &mpsc::Receiver<Control>,
+    status: &Mutex<MonitorStatus>,
+    mut on_event: F,
+) -> Result<(), String> {
+    let mut traps = TrapDetector::new(&config.traps);
+    let wanted: HashSet<Ipv4Addr> = targets.iter().copied().filter(|ip| !traps.is_trap(ip)).collect();
+    let mut sweep: Vec<Ipv4Addr> = targets.iter().copied().filter(|ip| wanted.contains(ip)).collect();
+    sweep.extend(&config.traps);
+    let deadline = config.duration.map(|duration| Instant::now() + duration);
+    let mut tracker = Tracker::new(config.flap, config.debounce).with_presence(config.presence);
+    let mut paused = false;
+    let mut quiet = false;
+    // The interface's drop counter when the first sweep ended
+    let mut dropped_before: Option<u64> = None;
+    let update = |change: &dyn Fn(&mut MonitorStatus)| {
+        if let Ok(mut status) = status.lock() {
+            change(&mut status);
+        }
+    };
+
+    loop {
+        let now = SystemTime::now();
+        update(&|status| status.alive = Some(now));
+        if config.quiet_hours.is_some_and(|hours| hours.contains(now)) != quiet {
+            quiet = !quiet;
+            on_event(&MonitorEvent::Quiet(quiet, now));
+            update(&|status| status.quiet = quiet);
+        }
+        if !paused && !quiet {
+            // A lock held by another run or a channel error costs one sweep,
+            // not the monitor; the next one is only an interval away
+            update(&|status| status.sweeping = true);
+            let result = scan_network(&sweep, &config.scan);
+            update(&|status| status.sweeping = false);
+            match result {
+                Ok(scan) => {
+                    let mut bindings: HashMap<Ipv4Addr, MacAddr> = HashMap::new();
+                    for (ip, reply) in &scan.replies {
+                        if !traps.is_trap(ip) {
+                            bindings.insert(*ip, reply.mac);
+                        } else if let Some(alert) = traps.touched(*ip, TrapKind::Claimed, reply.mac, *ip, reply.received) {
+                            on_event(&MonitorEvent::Trap(alert));
+                        }
+                    }
+                    for event in tracker.sweep(&bindings, scan.finished) {
+                        on_event(&event);
+                    }
+                    let hosts = tracker.known();
+                    let dropped = interfaces::rx_dropped(&scan.interface);
+                    dropped_before = dropped_before.or(dropped);
+                    update(&|status| {
+                        status.sweeps += 1;
+                        status.last_sweep = Some(scan.finished);
+                        status.hosts = hosts;
+                        let health = &mut status.health;
+                        health.send_errors += scan.send_failures.len() as u64;
+                        health.unsent += scan.unsent as u64;
+                        health.receive_errors += scan.receive_errors as u64;
+                        health.dropped = dropped.zip(dropped_before).map(|(now, before)| now.saturating_sub(before));
+                        health.last_sweep_took = Some(scan.finished.duration_since(scan.started).unwrap_or_default());
+                        health.reduced_pps = scan.reduced_pps;
+                        status.alive = Some(SystemTime::now());
+                    });
+                }
+                Err(e) => {
+                    on_event(&MonitorEvent::Failed(format!("sweep skipped: {}", e), SystemTime::now()));
+                    update(&|status| {
+                        status.health.skipped_sweeps += 1;
+                        status.health.last_error = Some(e.clone());
+                    });
+                }
+            }
+        }
+
+        let mut listen_for = config.interval;
+        if let Some(deadline) = deadline {
+            listen_for = listen_for.min(deadline.saturating_duration_since(Instant::now()));
+        }
+        if !listen_for.is_zero() {
+            let passive_config = PassiveConfig {
+                interface: config.scan.interface.clone(),
+                duration: Some(listen_for),
+                storm: config.storm,
+                channel: config.scan.channel,
+                subnets: Vec::new(),
+            };
+            let mut on_passive = |event: &PassiveEvent| match event {
+                PassiveEvent::Seen(ip, mac, at) if wanted.contains(ip) => {
+                    for event in tracker.sighting(*ip, *mac, *at) {
+                        on_event(&event);
+                    }
+                }
+                PassiveEvent::Seen(ip, mac, at) => {
+                    if let Some(alert) = traps.touched(*ip, TrapKind::Claimed, *mac, *ip, *at) {
+                        on_event(&MonitorEvent::Trap(alert));
+                    }
+                }
+                // A gratuitous ARP for a trap is already reported as a claim
+                PassiveEvent::Asked { ip, by, from, at } if ip != from => {
+                    if let Some(alert) = traps.touched(*ip, TrapKind::Asked, *by, *from, *at) {
+                        on_event(&MonitorEvent::Trap(alert));
+                    }
+                }
+                PassiveEvent::Storm(alert) => on_event(&MonitorEvent::Storm(alert.clone())),
+                _ => {}
+            };
+            // Rescan and resume cut the wait short so the sweep happens now
+            let mut should_stop = || {
+                update(&|status| status.alive = Some(SystemTime::now()));
+                let mut sweep_now = false;
+                while let Ok(control) = controls.try_recv() {
+                    match control {
+                        Control::Rescan => sweep_now = true,
+                        Control::Pause => paused = true,
+                        Control::Resume => {
+                            sweep_now |= paused;
+                            paused = false;
+                        }
+                    }
+                    let paused = paused;
+                    update(&|status| status.paused = paused);
+                }
+                sweep_now
+            };
+            let started = Instant::now();
+            match passive::listen_until(&passive_config, &mut on_passive, &mut should_stop) {
+                Ok(heard) => update(&|status| status.health.receive_errors += heard.receive_errors as u64),
+                Err(e) => {
+                    on_event(&MonitorEvent::Failed(format!("listening stopped: {}", e), SystemTime::now()));
+                    update(&|status| status.health.last_error = Some(e.clone()));
+                    // Wait out the interval anyway, still taking requests
+                    while started.elapsed() < listen_for && !should_stop() {
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                }
+            }
+        }
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+    }
+
+    fn ip(last: u8) -> Ipv4Addr {
+        Ipv4Addr::new(192, 0, 2, last)
+    }
+
+    fn mac(last: u8) -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, last)
+    }
+
+    fn bindings(hosts: &[(u8, u8)]) -> HashMap<Ipv4Addr, MacAddr> {
+        hosts.iter().map(|(host, device)| (ip(*host), mac(*device))).collect()
+    }
+
+    fn tracker(debounce: Debounce) -> Tracker {
+        Tracker::new(FlapThresholds::default(), debounce)
+    }
+
+    #[test]
+    fn gone_after_enough_misses() {
+        let mut tracker = tracker(Debounce { misses: 2, ..Debounce::default() });
+        assert_eq!(tracker.sweep(&bindings(&[(1, 1)]), at(0)), vec![MonitorEvent::New(ip(1), mac(1), at(0))]);
+        assert_eq!(tracker.sweep(&bindings(&[]), at(60)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[]), at(120)), vec![MonitorEvent::Gone(ip(1), mac(1), at(120))]);
+        assert_eq!(tracker.known(), 0);
+    }
+
+    #[test]
+    fn gone_waits_out_the_grace_period() {
+        let mut tracker = tracker(Debounce { misses: 2, grace: Duration::from_secs(300), arrivals: 1 });
+        tracker.sweep(&bindings(&[(1, 1)]), at(0));
+        assert_eq!(tracker.sweep(&bindings(&[]), at(100)), vec![]);
+        // Enough misses, but only 299 seconds of silence
+        assert_eq!(tracker.sweep(&bindings(&[]), at(299)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[]), at(300)), vec![MonitorEvent::Gone(ip(1), mac(1), at(300))]);
+    }
+
+    #[test]
+    fn sighting_resets_misses() {
+        let mut tracker = tracker(Debounce { misses: 2, ..Debounce::default() });
+        tracker.sweep(&bindings(&[(1, 1)]), at(0));
+        assert_eq!(tracker.sweep(&bindings(&[]), at(60)), vec![]);
+        assert_eq!(tracker.sighting(ip(1), mac(1), at(90)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[]), at(120)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[]), at(180)), vec![MonitorEvent::Gone(ip(1), mac(1), at(180))]);
+    }
+
+    #[test]
+    fn arrivals_need_consecutive_sweeps() {
+        let mut tracker = tracker(Debounce { arrivals: 3, ..Debounce::default() });
+        assert_eq!(tracker.sweep(&bindings(&[(1, 1)]), at(0)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[(1, 1)]), at(60)), vec![]);
+        // Missing one sweep starts the count again
+        assert_eq!(tracker.sweep(&bindings(&[]), at(120)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[(1, 1)]), at(180)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[(1, 1)]), at(240)), vec![]);
+        // Passive traffic doesn't count towards arrival
+        assert_eq!(tracker.sighting(ip(1), mac(1), at(250)), vec![]);
+        assert_eq!(tracker.sweep(&bindings(&[(1, 1)]), at(300)), vec![MonitorEvent::New(ip(1), mac(1), at(300))]);
+    }
+
+    #[test]
+    fn ip_flaps_at_the_change_threshold() {
+        let mut tracker = tracker(Debounce::default());
+        tracker.sweep(&bindings(&[(1, 1)]), at(0));
+        tracker.sweep(&bindings(&[(1, 2)]), at(10));
+        tracker.sweep(&bindings(&[(1, 1)]), at(20));
+        let events = tracker.sweep(&bindings(&[(1, 2)]), at(30));
+        assert_eq!(events, vec![
+            MonitorEvent::Changed { ip: ip(1), old: mac(1), new: mac(2), at: at(30) },
+            MonitorEvent::Flapping(FlapAlert::Ip { ip: ip(1), macs: vec![mac(1), mac(2)], changes: 3, first: at(10), last: at(30) }),
+        ]);
+        // Still flapping, but within the window of the last alert
+        let events = tracker.sweep(&bindings(&[(1, 1)]), at(40));
+        assert_eq!(events, vec![MonitorEvent::Changed { ip: ip(1), old: mac(2), new: mac(1), at: at(40) }]);
+    }
+
+    #[test]
+    fn ip_changes_outside_the_window_are_forgotten() {
+        let mut flaps = FlapDetector::new(FlapThresholds { changes: 3, window: Duration::from_secs(300) });
+        assert_eq!(flaps.ip_changed(ip(1), mac(1), mac(2), at(0)), None);
+        assert_eq!(flaps.ip_changed(ip(1), mac(2), mac(1), at(150)), None);
+        // The first change is exactly one window old and no longer counts
+        assert_eq!(flaps.ip_changed(ip(1), mac(1), mac(2), at(300)), None);
+        assert!(flaps.ip_changed(ip(1), mac(2), mac(1), at(449)).is_some());
+    }
+
+    #[test]
+    fn mac_flaps_across_addresses() {
+        let mut tracker = tracker(Debounce::default());
+        tracker.sweep(&bindings(&[(1, 1)]), at(0));
+        assert!(!tracker.sweep(&bindings(&[(2, 1)]), at(10)).iter().any(|event| matches!(event, MonitorEvent::Flapping(_))));
+        assert!(!tracker.sweep(&bindings(&[(3, 1)]), at(20)).iter().any(|event| matches!(event, MonitorEvent::Flapping(_))));
+        let events = tracker.sweep(&bindings(&[(1, 1)]), at(30));
+        assert_eq!(
+            events.last(),
+            Some(&MonitorEvent::Flapping(FlapAlert::Mac { mac: mac(1), ips: vec![ip(1), ip(2), ip(3)], changes: 3, first: at(10), last: at(30) }))
+        );
+    }
+
+    #[test]
+    fn multihomed_host_does_not_flap() {
+        let mut tracker = tracker(Debounce::default());
+        for sweep in 0..5 {
+            let events = tracker.sweep(&bindings(&[(1, 1), (2, 1)]), at(sweep * 10));
+            assert!(!events.iter().any(|event| matches!(event, MonitorEvent::Flapping(_))));
+        }
+    }
+
+    #[test]
+    fn presence_crosses_thresholds() {
+        let model = PresenceModel { half_life: Duration::from_secs(60), present: 0.6, away: 0.2 };
+        let mut tracker = tracker(Debounce::default()).with_presence(Some(model));
+        // One reply scores 0.7, over the present threshold
+        assert_eq!(tracker.sweep(&bindings(&[(1, 1)]), at(0)), vec![MonitorEvent::New(ip(1), mac(1), at(0))]);
+        // Halved to 0.35 after one half-life: between the thresholds
+        assert_eq!(tracker.sweep(&bindings(&[]), at(60)), vec![]);
+        assert!((tracker.presence(ip(1), at(60)).unwrap() - 0.35).abs() < 1e-9);
+        // 0.175 after two, below away
+        assert_eq!(tracker.sweep(&bindings(&[]), at(120)), vec![MonitorEvent::Gone(ip(1), mac(1), at(120))]);
+    }
+
+    #[test]
+    fn presence_needs_more_than_one_sighting() {
+        let model = PresenceModel::default();
+        let mut tracker = tracker(Debounce::default()).with_presence(Some(model));
+        // 0.5 is short of 0.6; a second sighting brings it to 0.75
+        assert_eq!(tracker.sighting(ip(1), mac(1), at(0)), vec![]);
+        assert_eq!(tracker.sighting(ip(1), mac(1), at(0)), vec![MonitorEvent::New(ip(1), mac(1), at(0))]);
+    }
+
+    #[test]
+    fn trap_alerts_once_a_minute_per_sender() {
+        let mut traps = TrapDetector::new(&[ip(250)]);
+        assert!(traps.touched(ip(1), TrapKind::Asked, mac(1), ip(2), at(0)).is_none());
+        assert!(traps.touched(ip(250), TrapKind::Asked, mac(1), ip(2), at(0)).is_some());
+        assert!(traps.touched(ip(250), TrapKind::Asked, mac(1), ip(2), at(59)).is_none());
+        assert!(traps.touched(ip(250), TrapKind::Claimed, mac(1), ip(250), at(59)).is_some());
+        assert!(traps.touched(ip(250), TrapKind::Asked, mac(2), ip(3), at(59)).is_some());
+        assert!(traps.touched(ip(250), TrapKind::Asked, mac(1), ip(2), at(60)).is_some());
+    }
+}
@@ -0,0 +1,198 @@
+// IPv6 Neighbor Discovery (RFC 4861) heard on the segment: the IPv6
+// counterpart of ARP. Solicitations and advertisements tie IPv6 addresses
+// to MACs, and router advertisements name the routers and the prefixes
+// they hand out.
+
+use std::net::Ipv6Addr;
+
+use pnet_base::MacAddr;
+
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const NEXT_HEADER_ICMPV6: u8 = 58;
+const IPV6_HEADER_LEN: usize = 40;
+
+const ROUTER_SOLICITATION: u8 = 133;
+const ROUTER_ADVERTISEMENT: u8 = 134;
+const NEIGHBOR_SOLICITATION: u8 = 135;
+const NEIGHBOR_ADVERTISEMENT: u8 = 136;
+
+// Options
+const SOURCE_LINK_ADDRESS: u8 = 1;
+const TARGET_LINK_ADDRESS: u8 = 2;
+const PREFIX_INFORMATION: u8 = 3;
+
+// Flag in a neighbor advertisement saying the sender is a router
+const NA_ROUTER_FLAG: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NdpMessage {
+    RouterSolicitation,
+    RouterAdvertisement,
+    NeighborSolicitation,
+    NeighborAdvertisement,
+}
+
+// One IPv6 address to MAC binding learned from an NDP message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NdpSighting {
+    pub message: NdpMessage,
+    pub ip: Ipv6Addr,
+    pub mac: MacAddr,
+    // The sender said it is a router (an RA, or an NA with the R flag)
+    pub router: bool,
+    // On-link prefixes announced in an RA
+    pub prefixes: Vec<(Ipv6Addr, u8)>,
+}
+
+fn u16_at(buf: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*buf.get(at)?, *buf.get(at + 1)?]))
+}
+
+fn ipv6_at(buf: &[u8], at: usize) -> Option<Ipv6Addr> {
+    let octets: [u8; 16] = buf.get(at..at + 16)?.try_into().ok()?;
+    Some(Ipv6Addr::from(octets))
+}
+
+// The ICMPv6 message and IPv6 source of a frame. NDP messages carry no
+// extension headers and must arrive with a hop limit of 255, which also
+// proves they weren't routed in from elsewhere.
+fn icmpv6(frame: &[u8]) -> Option<(Ipv6Addr, &[u8])> {
+    if u16_at(frame, 12)? != ETHERTYPE_IPV6 {
+        return None;
+    }
+    let ip = frame.get(14..)?;
+    if *ip.get(6)? != NEXT_HEADER_ICMPV6 || *ip.get(7)? != 255 {
+        return None;
+    }
+    Some((ipv6_at(ip, 8)?, ip.get(IPV6_HEADER_LEN..)?))
+}
+
+// Whether a frame is an NDP message, without decoding it.
+pub fn is_ndp_frame(frame: &[u8]) -> bool {
+    icmpv6(frame).and_then(|(_, icmp)| icmp.first())
+        .is_some_and(|kind| (ROUTER_SOLICITATION..=NEIGHBOR_ADVERTISEMENT).contains(kind))
+}
+
+// Walk the options after the fixed part of a message. Lengths are in
+// units of 8 bytes, and zero is invalid.
+fn options(mut options: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut found = Vec::new();
+    while !options.is_empty() {
+        let len = *options.get(1)? as usize * 8;
+        if len == 0 {
+            return None;
+        }
+        found.push((options[0], options.get(2..len)?));
+        options = &options[len..];
+    }
+    Some(found)
+}
+
+fn link_address(value: &[u8]) -> Option<MacAddr> {
+    let octets: [u8; 6] = value.get(..6)?.try_into().ok()?;
+    Some(MacAddr::from(octets))
+}
+
+// Decode an NDP message into the binding it reveals. Returns None for
+// anything else, for DAD probes (sent from ::, so they bind nothing) and
+// for messages that carry no link-layer address to bind to.
+pub fn parse_ndp_frame(frame: &[u8]) -> Option<NdpSighting> {
+    let (source, icmp) = icmpv6(frame)?;
+    let eth_source = link_address(frame.get(6..12)?)?;
+    let (message, fixed_len) = match *icmp.first()? {
+        ROUTER_SOLICITATION => (NdpMessage::RouterSolicitation, 8),
+        ROUTER_ADVERTISEMENT => (NdpMessage::RouterAdvertisement, 16),
+        NEIGHBOR_SOLICITATION => (NdpMessage::NeighborSolicitation, 24),
+        NEIGHBOR_ADVERTISEMENT => (NdpMessage::NeighborAdvertisement, 24),
+        _ => return None,
+    };
+    let options = options(icmp.get(fixed_len..)?)?;
+    let option = |kind: u8| options.iter().find(|(k, _)| *k == kind).and_then(|(_, value)| link_address(value));
+
+    let (ip, mac, router) = match message {
+        // The target is the address being advertised; its MAC is in the
+        // target link-layer option, or failing that the frame's source
+        NdpMessage::NeighborAdvertisement => {
+            let router = icmp.get(4)? & NA_ROUTER_FLAG != 0;
+            (ipv6_at(icmp, 8)?, option(TARGET_LINK_ADDRESS).unwrap_or(eth_source), router)
+        }
+        NdpMessage::RouterAdvertisement => (source, option(SOURCE_LINK_ADDRESS).unwrap_or(eth_source), true),
+        _ => (source, option(SOURCE_LINK_ADDRESS)?, false),
+    };
+    if ip.is_unspecified() || ip.is_multicast() {
+        return None;
+    }
+    let prefixes = options.iter()
+        .filter(|(kind, value)| *kind == PREFIX_INFORMATION && value.len() >= 30)
+        .filter_map(|(_, value)| Some((ipv6_at(value, 14)?, value[0])))
+        .collect();
+    Some(NdpSighting { message, ip, mac, router, prefixes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(dump: &str) -> Vec<u8> {
+        dump.split_whitespace().map(|byte| u8::from_str_radix(byte, 16).unwrap()).collect()
+    }
+
+    // A router announcing 2001:db8:1::/64, a host answering it, and the
+    // duplicate address probe the host sent before taking the address
+    const NDP_ROUTER_ADVERTISEMENT: &str = "33 33 00 00 00 01 00 00 5e 00 01 01 86 dd 60 00 \
+        00 00 00 40 3a ff fe 80 00 00 00 00 00 00 02 00 \
+        5e ff fe 00 01 01 ff 02 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 01 86 00 30 e8 40 00 07 08 00 00 \
+        00 00 00 00 00 00 01 01 00 00 5e 00 01 01 05 01 \
+        00 00 00 00 05 dc 03 04 40 c0 00 27 8d 00 00 09 \
+        3a 80 00 00 00 00 20 01 0d b8 00 01 00 00 00 00 \
+        00 00 00 00 00 00";
+    const NDP_NEIGHBOR_ADVERTISEMENT: &str = "00 00 5e 00 01 01 3c d9 2b 10 20 30 86 dd 60 00 \
+        00 00 00 20 3a ff 20 01 0d b8 00 01 00 00 00 00 \
+        00 00 00 00 00 23 fe 80 00 00 00 00 00 00 02 00 \
+        5e ff fe 00 01 01 88 00 d3 4d 60 00 00 00 20 01 \
+        0d b8 00 01 00 00 00 00 00 00 00 00 00 23 02 01 \
+        3c d9 2b 10 20 30";
+    const NDP_DAD_PROBE: &str = "33 33 ff 00 00 23 3c d9 2b 10 20 30 86 dd 60 00 \
+        00 00 00 18 3a ff 00 00 00 00 00 00 00 00 00 00 \
+        00 00 00 00 00 00 ff 02 00 00 00 00 00 00 00 00 \
+        00 01 ff 00 00 23 87 00 4c a8 00 00 00 00 20 01 \
+        0d b8 00 01 00 00 00 00 00 00 00 00 00 23";
+
+    #[test]
+    fn decodes_a_router_advertisement() {
+        let sighting = parse_ndp_frame(&frame(NDP_ROUTER_ADVERTISEMENT)).unwrap();
+        assert_eq!(sighting.message, NdpMessage::RouterAdvertisement);
+        assert_eq!(sighting.ip, "fe80::200:5eff:fe00:101".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(sighting.mac, MacAddr::new(0x00, 0x00, 0x5e, 0x00, 0x01, 0x01));
+        assert!(sighting.router);
+        assert_eq!(sighting.prefixes, vec![("2001:db8:1::".parse().unwrap(), 64)]);
+    }
+
+    #[test]
+    fn decodes_a_neighbor_advertisement() {
+        let sighting = parse_ndp_frame(&frame(NDP_NEIGHBOR_ADVERTISEMENT)).unwrap();
+        assert_eq!(sighting.message, NdpMessage::NeighborAdvertisement);
+        assert_eq!(sighting.ip, "2001:db8:1::23".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(sighting.mac, MacAddr::new(0x3c, 0xd9, 0x2b, 0x10, 0x20, 0x30));
+        assert!(!sighting.router);
+        assert!(sighting.prefixes.is_empty());
+    }
+
+    #[test]
+    fn skips_dad_probes_and_routed_messages() {
+        let probe = frame(NDP_DAD_PROBE);
+        assert!(is_ndp_frame(&probe));
+        assert_eq!(parse_ndp_frame(&probe), None);
+        // Hop limit below 255: forwarded by a router, not from this link
+        let mut routed = frame(NDP_NEIGHBOR_ADVERTISEMENT);
+        routed[14 + 7] = 64;
+        assert!(!is_ndp_frame(&routed));
+        assert_eq!(parse_ndp_frame(&routed), None);
+        // An option with length zero
+        let mut looping = frame(NDP_NEIGHBOR_ADVERTISEMENT);
+        let last = looping.len() - 7;
+        looping[last] = 0;
+        assert_eq!(parse_ndp_frame(&looping), None);
+    }
+}
@@ -0,0 +1,328 @@
+// Switch announcements heard on the segment: LLDP (IEEE 802.1AB) and
+// Cisco's CDP. Either one tells us the name of the switch we're plugged
+// into, which port, and usually the port's VLAN.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use pnet_base::MacAddr;
+
+pub const ETHERTYPE_LLDP: u16 = 0x88CC;
+// CDP is 802.3 with an LLC/SNAP header rather than an EtherType
+const CDP_DESTINATION: [u8; 6] = [0x01, 0x00, 0x0c, 0xcc, 0xcc, 0xcc];
+const CDP_SNAP: [u8; 8] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x0c, 0x20, 0x00];
+// Ethernet header, then LLC/SNAP, then version, TTL and checksum
+const CDP_TLVS_START: usize = 14 + CDP_SNAP.len() + 4;
+
+// LLDP TLV types
+const LLDP_END: u8 = 0;
+const LLDP_CHASSIS_ID: u8 = 1;
+const LLDP_PORT_ID: u8 = 2;
+const LLDP_TTL: u8 = 3;
+const LLDP_PORT_DESCRIPTION: u8 = 4;
+const LLDP_SYSTEM_NAME: u8 = 5;
+const LLDP_SYSTEM_DESCRIPTION: u8 = 6;
+const LLDP_MANAGEMENT_ADDRESS: u8 = 8;
+const LLDP_ORG_SPECIFIC: u8 = 127;
+// IEEE 802.1 organizationally specific TLVs, subtype 1 is the port VLAN ID
+const IEEE_8021_OUI: [u8; 3] = [0x00, 0x80, 0xc2];
+const IEEE_8021_PORT_VLAN: u8 = 1;
+
+// CDP TLV types
+const CDP_DEVICE_ID: u16 = 0x0001;
+const CDP_ADDRESSES: u16 = 0x0002;
+const CDP_PORT_ID: u16 = 0x0003;
+const CDP_SOFTWARE_VERSION: u16 = 0x0005;
+const CDP_PLATFORM: u16 = 0x0006;
+const CDP_NATIVE_VLAN: u16 = 0x000a;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NeighborProtocol {
+    Lldp,
+    Cdp,
+}
+
+impl fmt::Display for NeighborProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            NeighborProtocol::Lldp => "LLDP",
+            NeighborProtocol::Cdp => "CDP",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbor {
+    pub protocol: NeighborProtocol,
+    // Ethernet source of the announcement, normally the switch port's MAC
+    pub source: MacAddr,
+    // LLDP chassis ID or CDP device ID
+    pub chassis: Option<String>,
+    pub system_name: Option<String>,
+    // LLDP system description, or CDP platform and software version
+    pub description: Option<String>,
+    pub port: Option<String>,
+    pub port_description: Option<String>,
+    pub vlan: Option<u16>,
+    pub management_ip: Option<Ipv4Addr>,
+    // How long the announcement stays valid, in seconds
+    pub ttl: Option<u16>,
+}
+
+impl Neighbor {
+    fn new(protocol: NeighborProtocol, source: MacAddr) -> Self {
+        Neighbor {
+            protocol,
+            source,
+            chassis: None,
+            system_name: None,
+            description: None,
+            port: None,
+            port_description: None,
+            vlan: None,
+            management_ip: None,
+            ttl: None,
+        }
+    }
+
+    // The best name for the device: its system name, else its chassis ID.
+    pub fn name(&self) -> Option<&str> {
+        self.system_name.as_deref().or(self.chassis.as_deref())
+    }
+}
+
+fn text(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn mac_text(bytes: &[u8]) -> Option<String> {
+    let octets: [u8; 6] = bytes.try_into().ok()?;
+    Some(MacAddr::from(octets).to_string())
+}
+
+fn u16_at(buf: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*buf.get(at)?, *buf.get(at + 1)?]))
+}
+
+// An LLDP chassis or port ID: a subtype byte, then a MAC address (subtype
+// 4 for chassis, 3 for port), a network address (5 and 4), or text.
+fn lldp_id(value: &[u8], mac_subtype: u8, address_subtype: u8) -> Option<String> {
+    let (&subtype, id) = value.split_first()?;
+    if subtype == mac_subtype {
+        mac_text(id)
+    } else if subtype == address_subtype {
+        // Address family 1 is IPv4
+        match id {
+            [1, a, b, c, d] => Some(Ipv4Addr::new(*a, *b, *c, *d).to_string()),
+            _ => None,
+        }
+    } else {
+        text(id)
+    }
+}
+
+// A management address TLV: length, family, address, and then interface
+// numbering we don't need.
+fn lldp_management_ip(value: &[u8]) -> Option<Ipv4Addr> {
+    match value {
+        [5, 1, a, b, c, d, ..] => Some(Ipv4Addr::new(*a, *b, *c, *d)),
+        _ => None,
+    }
+}
+
+fn parse_lldp(frame: &[u8], source: MacAddr) -> Option<Neighbor> {
+    let mut neighbor = Neighbor::new(NeighborProtocol::Lldp, source);
+    let mut at = 14;
+    while let Some(header) = u16_at(frame, at) {
+        let (kind, len) = ((header >> 9) as u8, (header & 0x01ff) as usize);
+        let value = frame.get(at + 2..at + 2 + len)?;
+        match kind {
+            LLDP_END => break,
+            LLDP_CHASSIS_ID => neighbor.chassis = lldp_id(value, 4, 5),
+            LLDP_PORT_ID => neighbor.port = lldp_id(value, 3, 4),
+            LLDP_TTL => neighbor.ttl = u16_at(value, 0),
+            LLDP_PORT_DESCRIPTION => neighbor.port_description = text(value),
+            LLDP_SYSTEM_NAME => neighbor.system_name = text(value),
+            LLDP_SYSTEM_DESCRIPTION => neighbor.description = text(value),
+            LLDP_MANAGEMENT_ADDRESS => neighbor.management_ip = neighbor.management_ip.or(lldp_management_ip(value)),
+            LLDP_ORG_SPECIFIC if value.get(..3) == Some(&IEEE_8021_OUI[..]) && value.get(3) == Some(&IEEE_8021_PORT_VLAN) => {
+                neighbor.vlan = u16_at(value, 4).filter(|vlan| *vlan != 0);
+            }
+            _ => {}
+        }
+        at += 2 + len;
+    }
+    // The first three TLVs are mandatory
+    neighbor.chassis.is_some().then_some(neighbor)
+}
+
+// The first IPv4 address in a CDP address TLV: a count, then per address
+// a protocol type, protocol length, protocol (0xcc for IP), address
+// length and address.
+fn cdp_first_ip(value: &[u8]) -> Option<Ipv4Addr> {
+    match value.get(4..)? {
+        [1, 1, 0xcc, 0, 4, a, b, c, d, ..] => Some(Ipv4Addr::new(*a, *b, *c, *d)),
+        _ => None,
+    }
+}
+
+fn parse_cdp(frame: &[u8], source: MacAddr) -> Option<Neighbor> {
+    let mut neighbor = Neighbor::new(NeighborProtocol::Cdp, source);
+    neighbor.ttl = frame.get(CDP_TLVS_START - 3).map(|ttl| *ttl as u16);
+    let (mut platform, mut version) = (None, None);
+    let mut at = CDP_TLVS_START;
+    while let (Some(kind), Some(len)) = (u16_at(frame, at), u16_at(frame, at + 2)) {
+        let len = len as usize;
+        if len < 4 {
+            return None;
+        }
+        let value = frame.get(at + 4..at + len)?;
+        match kind {
+            CDP_DEVICE_ID => neighbor.chassis = text(value),
+            CDP_ADDRESSES => neighbor.management_ip = cdp_first_ip(value),
+            CDP_PORT_ID => neighbor.port = text(value),
+            CDP_SOFTWARE_VERSION => version = text(value).and_then(|v| v.lines().next().map(str::to_string)),
+            CDP_PLATFORM => platform = text(value),
+            CDP_NATIVE_VLAN => neighbor.vlan = u16_at(value, 0),
+            _ => {}
+        }
+        at += len;
+    }
+    neighbor.description = match (platform, version) {
+        (Some(platform), Some(version)) => Some(format!("{}, {}", platform, version)),
+        (platform, version) => platform.or(version),
+    };
+    neighbor.chassis.is_some().then_some(neighbor)
+}
+
+// Whether a frame is LLDP or CDP, without decoding it.
+pub fn is_neighbor_frame(frame: &[u8]) -> bool {
+    u16_at(frame, 12) == Some(ETHERTYPE_LLDP)
+        || (frame.get(..6) == Some(&CDP_DESTINATION[..]) && frame.get(14..22) == Some(&CDP_SNAP[..]))
+}
+
+// Decode an LLDP or CDP frame. Returns None for anything else, and for
+// announcements too broken to say which device sent them.
+pub fn parse_neighbor_frame(frame: &[u8]) -> Option<Neighbor> {
+    let source: [u8; 6] = frame.get(6..12)?.try_into().ok()?;
+    let source = MacAddr::from(source);
+    if u16_at(frame, 12) == Some(ETHERTYPE_LLDP) {
+        parse_lldp(frame, source)
+    } else if is_neighbor_frame(frame) {
+        parse_cdp(frame, source)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(dump: &str) -> Vec<u8> {
+        dump.split_whitespace().map(|byte| u8::from_str_radix(byte, 16).unwrap()).collect()
+    }
+
+    // The same Catalyst access port announcing itself over LLDP and CDP
+    const LLDP: &str = "01 80 c2 00 00 0e 00 1b 54 c2 80 0c 88 cc 02 07 \
+        04 00 1b 54 c2 80 00 04 09 05 47 69 31 2f 30 2f \
+        31 32 06 02 00 78 08 15 47 69 67 61 62 69 74 45 \
+        74 68 65 72 6e 65 74 31 2f 30 2f 31 32 0a 18 73 \
+        77 2d 61 63 63 65 73 73 2d 30 32 2e 65 78 61 6d \
+        70 6c 65 2e 6e 65 74 0c 4d 43 69 73 63 6f 20 49 \
+        4f 53 20 53 6f 66 74 77 61 72 65 2c 20 43 32 39 \
+        36 30 58 20 53 6f 66 74 77 61 72 65 20 28 43 32 \
+        39 36 30 58 2d 55 4e 49 56 45 52 53 41 4c 4b 39 \
+        2d 4d 29 2c 20 56 65 72 73 69 6f 6e 20 31 35 2e \
+        32 28 37 29 45 38 0e 04 00 14 00 04 10 0c 05 01 \
+        c0 00 02 02 02 00 00 00 01 00 fe 06 00 80 c2 01 \
+        00 14 00 00";
+    const CDP: &str = "01 00 0c cc cc cc 00 1b 54 c2 80 0c 00 f8 aa aa \
+        03 00 00 0c 20 00 02 b4 84 e3 00 01 00 1c 73 77 \
+        2d 61 63 63 65 73 73 2d 30 32 2e 65 78 61 6d 70 \
+        6c 65 2e 6e 65 74 00 02 00 11 00 00 00 01 01 01 \
+        cc 00 04 c0 00 02 02 00 03 00 19 47 69 67 61 62 \
+        69 74 45 74 68 65 72 6e 65 74 31 2f 30 2f 31 32 \
+        00 05 00 85 43 69 73 63 6f 20 49 4f 53 20 53 6f \
+        66 74 77 61 72 65 2c 20 43 32 39 36 30 58 20 53 \
+        6f 66 74 77 61 72 65 20 28 43 32 39 36 30 58 2d \
+        55 4e 49 56 45 52 53 41 4c 4b 39 2d 4d 29 2c 20 \
+        56 65 72 73 69 6f 6e 20 31 35 2e 32 28 37 29 45 \
+        38 0a 54 65 63 68 6e 69 63 61 6c 20 53 75 70 70 \
+        6f 72 74 3a 20 68 74 74 70 3a 2f 2f 77 77 77 2e \
+        63 69 73 63 6f 2e 63 6f 6d 2f 74 65 63 68 73 75 \
+        70 70 6f 72 74 00 06 00 1b 63 69 73 63 6f 20 57 \
+        53 2d 43 32 39 36 30 58 2d 34 38 46 50 44 2d 4c \
+        00 0a 00 06 00 14";
+
+    const SWITCH_PORT_MAC: MacAddr = MacAddr(0x00, 0x1b, 0x54, 0xc2, 0x80, 0x0c);
+
+    #[test]
+    fn decodes_lldp() {
+        let frame = frame(LLDP);
+        assert!(is_neighbor_frame(&frame));
+        let neighbor = parse_neighbor_frame(&frame).unwrap();
+        assert_eq!(
+            neighbor,
+            Neighbor {
+                protocol: NeighborProtocol::Lldp,
+                source: SWITCH_PORT_MAC,
+                chassis: Some("00:1b:54:c2:80:00".to_string()),
+                system_name: Some("sw-access-02.example.net".to_string()),
+                description: Some(
+                    "Cisco IOS Software, C2960X Software (C2960X-UNIVERSALK9-M), Version 15.2(7)E8".to_string()
+                ),
+                port: Some("Gi1/0/12".to_string()),
+                port_description: Some("GigabitEthernet1/0/12".to_string()),
+                vlan: Some(20),
+                management_ip: Some(Ipv4Addr::new(192, 0, 2, 2)),
+                ttl: Some(120),
+            }
+        );
+        assert_eq!(neighbor.name(), Some("sw-access-02.example.net"));
+    }
+
+    #[test]
+    fn decodes_cdp() {
+        let frame = frame(CDP);
+        assert!(is_neighbor_frame(&frame));
+        let neighbor = parse_neighbor_frame(&frame).unwrap();
+        assert_eq!(
+            neighbor,
+            Neighbor {
+                protocol: NeighborProtocol::Cdp,
+                source: SWITCH_PORT_MAC,
+                chassis: Some("sw-access-02.example.net".to_string()),
+                system_name: None,
+                description: Some(
+                    "cisco WS-C2960X-48FPD-L, Cisco IOS Software, C2960X Software (C2960X-UNIVERSALK9-M), Version 15.2(7)E8"
+                        .to_string()
+                ),
+                port: Some("GigabitEthernet1/0/12".to_string()),
+                port_description: None,
+                vlan: Some(20),
+                management_ip: Some(Ipv4Addr::new(192, 0, 2, 2)),
+                ttl: Some(180),
+            }
+        );
+        assert_eq!(neighbor.name(), Some("sw-access-02.example.net"));
+    }
+
+    #[test]
+    fn rejects_broken_announcements() {
+        let lldp = frame(LLDP);
+        // Cut off inside the chassis ID
+        assert_eq!(parse_neighbor_frame(&lldp[..20]), None);
+        // A TLV running past the end of the frame
+        assert_eq!(parse_neighbor_frame(&lldp[..lldp.len() - 8]), None);
+        let mut cdp = frame(CDP);
+        // A TLV length shorter than its own header
+        cdp[CDP_TLVS_START + 3] = 2;
+        assert_eq!(parse_neighbor_frame(&cdp), None);
+        let mut arp = lldp.clone();
+        arp[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert!(!is_neighbor_frame(&arp));
+        assert_eq!(parse_neighbor_frame(&arp), None);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NmapHost {
+    pub ip: Ipv4Addr,
+    pub mac: Option<String>,
+    pub up: bool,
+}
+
+// Pull the value of attr="..." out of a single tag.
+fn attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let needle = format!("{}=\"", name);
+    let start = tag.find(&needle)? + needle.len();
+    let end = tag[start..].find('"')? + start;
+    Some(&tag[start..end])
+}
+
+// Collect the opening tags named `name` in `xml`, e.g. <address .../>.
+fn tags<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    xml.match_indices(&open)
+        .filter_map(|(i, _)| {
+            let rest = &xml[i + open.len()..];
+            // Make sure we matched <address and not <addressfoo
+            if !rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+                return None;
+            }
+            let end = rest.find('>')?;
+            Some(&rest[..end])
+        })
+        .collect()
+}
+
+// Parse the <host> entries of an nmap XML report (-oX). Only the pieces
+// needed for comparison are extracted: IPv4 address, MAC and up/down state.
+pub fn parse_hosts(xml: &str) -> Result<Vec<NmapHost>, String> {
+    if !xml.contains("<nmaprun") {
+        return Err("Not an nmap XML report (missing <nmaprun>)".to_string());
+    }
+
+    let mut hosts = Vec::new();
+    for block in xml.split("<host").skip(1) {
+        if !block.starts_with(|c: char| c.is_whitespace() || c == '>') {
+            continue;
+        }
+        let block = block.split("</host>").next().unwrap_or(block);
+
+        let up = tags(block, "status")
+            .first()
+            .and_then(|tag| attr(tag, "state"))
+            .map(|state| state == "up")
+            .unwrap_or(false);
+
+        let mut ip = None;
+        let mut mac = None;
+        for tag in tags(block, "address") {
+            match attr(tag, "addrtype") {
+                Some("ipv4") => ip = attr(tag, "addr").and_then(|a| Ipv4Addr::from_str(a).ok()),
+                Some("mac") => mac = attr(tag, "addr").map(|a| a.to_lowercase()),
+                _ => {}
+            }
+        }
+
+        if let Some(ip) = ip {
+            hosts.push(NmapHost { ip, mac, up });
+        }
+    }
+    Ok(hosts)
+}
+
+pub fn load_hosts(path: &Path) -> Result<Vec<NmapHost>, String> {
+    let xml = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_hosts(&xml)
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use pnet_base::MacAddr;
+
+use crate::paths;
+
+pub const NOTES_FILE_NAME: &str = "notes.txt";
+
+// Free-form notes attached to MAC addresses, kept between scans in a plain
+// text file: one "MAC<TAB>note" per line, '#' starting a comment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Notes {
+    notes: BTreeMap<MacAddr, String>,
+}
+
+impl Notes {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut notes = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (mac, note) = line.split_once('\t')
+                .ok_or_else(|| format!("line {}: expected MAC<TAB>note", number + 1))?;
+            let mac = mac.trim().parse::<MacAddr>()
+                .map_err(|_| format!("line {}: invalid MAC address '{}'", number + 1, mac.trim()))?;
+            notes.insert(mac, note.to_string());
+        }
+        Ok(Notes { notes })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# arp_scan host notes: MAC<TAB>note\n");
+        for (mac, note) in &self.notes {
+            text.push_str(&format!("{}\t{}\n", mac, note));
+        }
+        text
+    }
+
+    // A missing file just means no notes have been written yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Notes::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Notes::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        fs::write(path, self.to_text()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn get(&self, mac: &MacAddr) -> Option<&str> {
+        self.notes.get(mac).map(String::as_str)
+    }
+
+    // Notes are single lines, so tabs and line breaks become spaces.
+    pub fn set(&mut self, mac: MacAddr, note: &str) {
+        let note: String = note.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+        self.notes.insert(mac, note.trim().to_string());
+    }
+
+    pub fn remove(&mut self, mac: &MacAddr) -> Option<String> {
+        self.notes.remove(mac)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&MacAddr, &str)> {
+        self.notes.iter().map(|(mac, note)| (mac, note.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+}
+
+// $ARPSCAN_NOTES, or notes.txt in the per-user data directory.
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("ARPSCAN_NOTES")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| paths::data_dir().map(|dir| dir.join(NOTES_FILE_NAME)))
+}
@@ -0,0 +1,1773 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::time::Duration;
+
+use pnet::datalink;
+use pnet::util::MacAddr;
+
+use arp_scan::arp_cache::CacheEntry;
+use arp_scan::defend::{DefendAction, DefendEvent};
+use arp_scan::dhcp::DhcpClient;
+use arp_scan::host::{Host, HostTable};
+use arp_scan::interfaces;
+use arp_scan::meta::{format_timestamp, ScanMeta, TOOL_VERSION};
+use arp_scan::monitor::{FlapAlert, MonitorEvent, TrapKind};
+use arp_scan::neighbor::Neighbor;
+use arp_scan::passive::{PassiveEvent, PassiveResult, StormAlert};
+use arp_scan::ratelimit;
+use arp_scan::route;
+use arp_scan::scan::{get_default_interface, HostReply, ReplyKind, ScanResult};
+use arp_scan::schema::SCHEMA_VERSION;
+use arp_scan::snooping::{RelayAgent, SnoopingTable, SwitchPort};
+use arp_scan::stats::LatencyStats;
+use arp_scan::summary::{self, BlockSummary, Summary};
+use arp_scan::target::TargetBlock;
+use arp_scan::topology::{self, Placement};
+use arp_scan::vendor::{normalize_vendor, VendorDb};
+
+use crate::cli::{ExportFormat, Options};
+use crate::enrich::{self, Enrichment};
+
+fn get_manufacturer(vendors: &dyn VendorDb, mac: &MacAddr) -> String {
+    vendors.lookup(mac)
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+fn display_mac(mac: &MacAddr, opts: &Options) -> String {
+    if opts.redact_mac {
+        opts.mac_format.format_redacted(mac)
+    } else {
+        opts.mac_format.format(mac)
+    }
+}
+
+// Zero-padded to a fixed 15 characters with --pad-ip, so columns line up
+// and a plain text sort is also a numeric one.
+fn display_ip(ip: Ipv4Addr, opts: &Options) -> String {
+    if opts.pad_ip {
+        let [a, b, c, d] = ip.octets();
+        format!("{:03}.{:03}.{:03}.{:03}", a, b, c, d)
+    } else {
+        ip.to_string()
+    }
+}
+
+fn display_vendor(vendors: &dyn VendorDb, mac: &MacAddr, opts: &Options) -> String {
+    if opts.redact_vendor {
+        "[redacted]".to_string()
+    } else if opts.raw_vendor {
+        get_manufacturer(vendors, mac)
+    } else {
+        normalize_vendor(&get_manufacturer(vendors, mac), opts.strip_suffixes)
+    }
+}
+
+// Hidden along with the vendor, since it narrows the vendor down.
+fn display_country(vendors: &dyn VendorDb, mac: &MacAddr, opts: &Options) -> Option<String> {
+    if opts.redact_vendor {
+        None
+    } else {
+        vendors.country(mac).map(str::to_string)
+    }
+}
+
+fn responses(scan: &ScanResult, reply: &HostReply) -> u32 {
+    scan.responses.get(&reply.ip).copied().unwrap_or(0)
+}
+
+// Other interfaces the same MAC answered on. Usually means two of the
+// scanned interfaces are bridged onto one segment. Compared per reply, as
+// merged results hold several interfaces in one scan.
+fn also_seen_on<'a>(scans: &'a [ScanResult], reply: &HostReply) -> Vec<&'a str> {
+    let mut interfaces: Vec<&str> = scans.iter()
+        .flat_map(|scan| scan.replies.values())
+        .filter(|other| other.mac == reply.mac && other.interface != reply.interface)
+        .map(|other| other.interface.as_str())
+        .collect();
+    interfaces.sort();
+    interfaces.dedup();
+    interfaces
+}
+
+// Anomalies worth a second look, shown in the Warnings column.
+fn warnings(scans: &[ScanResult], scan: &ScanResult, reply: &HostReply, opts: &Options) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let answered = responses(scan, reply);
+    if answered < scan.rounds {
+        warnings.push(format!("answered {}/{} rounds", answered, scan.rounds));
+    }
+    if reply.eth_source_mismatch() {
+        warnings.push(format!("ethernet source {} differs from ARP sender", display_mac(&reply.eth_source, opts)));
+    }
+    let elsewhere = also_seen_on(scans, reply);
+    if !elsewhere.is_empty() {
+        warnings.push(format!("same MAC also seen on {}", elsewhere.join(", ")));
+    }
+    for roam in &reply.roamed_from {
+        warnings.push(format!(
+            "roamed from {} on {} at {}",
+            display_ip(roam.ip, opts),
+            roam.interface,
+            format_timestamp(roam.at)
+        ));
+    }
+    if !reply.duplicate_macs.is_empty() {
+        let macs: Vec<String> = reply.duplicate_macs.iter().map(|mac| display_mac(mac, opts)).collect();
+        warnings.push(format!("also answered by {}", macs.join(", ")));
+    }
+    if let Some(attempt) = reply.attempt.filter(|attempt| opts.verbose && *attempt > 1) {
+        warnings.push(format!("first answered attempt {}", attempt));
+    }
+    if opts.verbose && reply.duplicates > 0 {
+        warnings.push(format!("duplicate replies: {}", reply.duplicates));
+    }
+    warnings
+}
+
+// One table per target block, and per interface within it when several
+// were scanned, then the blocks side by side.
+pub fn print_block_results(scans: &[ScanResult], blocks: &[TargetBlock], vendors: &dyn VendorDb, opts: &Options) {
+    for (index, block) in blocks.iter().enumerate() {
+        for scan in scans {
+            let mut section = scan.clone();
+            section.replies.retain(|ip, _| summary::block_of(blocks, *ip) == Some(index));
+            if scans.len() > 1 {
+                println!("\nScan Results ({} on {}):", block.label, scan.interface);
+            } else {
+                println!("\nScan Results ({}):", block.label);
+            }
+            print_table(scans, &section, vendors, opts);
+        }
+    }
+    // Replies from addresses nobody asked about, e.g. gratuitous ARP
+    for scan in scans {
+        let mut section = scan.clone();
+        section.replies.retain(|ip, _| summary::block_of(blocks, *ip).is_none());
+        if !section.replies.is_empty() {
+            println!("\nOther replies ({}):", scan.interface);
+            print_table(scans, &section, vendors, opts);
+        }
+    }
+
+    let counts = summary::by_block(scans, blocks);
+    let width = counts.iter().map(|block| block.label.len()).max().unwrap_or(0).max(5);
+    println!("\nBlocks:");
+    println!("  {:<width$} {:>8} {:>8} {:>8}", "Block", "Probed", "Hosts", "Rate", width = width);
+    let rate = |rate: Option<f64>| rate.map(|rate| format!("{:.1}%", rate)).unwrap_or_else(|| "-".to_string());
+    for block in &counts {
+        println!("  {:<width$} {:>8} {:>8} {:>8}", block.label, block.probed, block.hosts, rate(block.response_rate()), width = width);
+    }
+    let total = BlockSummary {
+        label: "Total".to_string(),
+        probed: counts.iter().map(|block| block.probed).sum(),
+        hosts: scans.iter().map(|scan| scan.replies.len()).sum(),
+    };
+    println!("  {:<width$} {:>8} {:>8} {:>8}", total.label, total.probed, total.hosts, rate(total.response_rate()), width = width);
+}
+
+// One table per interface when several were scanned.
+pub fn print_results(scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) {
+    for scan in scans {
+        if scans.len() > 1 {
+            println!("\nScan Results ({}):", scan.interface);
+        } else {
+            println!("\nScan Results:");
+        }
+        print_table(scans, scan, vendors, opts);
+    }
+}
+
+fn print_table(scans: &[ScanResult], scan: &ScanResult, vendors: &dyn VendorDb, opts: &Options) {
+    // Optional columns only appear when they have something to say
+    let show_names = scan.replies.values().any(|reply| reply.hostname.is_some());
+    let show_ports = scan.replies.values().any(|reply| reply.switch_port.is_some());
+    let mut header = format!("{:<16} {:<18} {:<30} ", "IP Address", "MAC Address", "Manufacturer");
+    let mut rule = format!("{:-<16} {:-<18} {:-<30} ", "", "", "");
+    if opts.show_country {
+        header.push_str(&format!("{:<7} ", "Country"));
+        rule.push_str(&format!("{:-<7} ", ""));
+    }
+    if show_names {
+        header.push_str(&format!("{:<24} ", "Name"));
+        rule.push_str(&format!("{:-<24} ", ""));
+    }
+    if show_ports {
+        header.push_str(&format!("{:<24} ", "Switch port"));
+        rule.push_str(&format!("{:-<24} ", ""));
+    }
+    if scan.rounds > 1 {
+        header.push_str(&format!("{:<9} ", "Responses"));
+        rule.push_str(&format!("{:-<9} ", ""));
+    }
+    println!("{}Warnings", header);
+    println!("{}{:-<8}", rule, "");
+    let mut replies: Vec<&HostReply> = scan.replies.values().collect();
+    replies.sort_by_key(|reply| reply.ip);
+    for reply in replies {
+        let mut line = format!(
+            "{:<16} {:<18} {:<30} ",
+            display_ip(reply.ip, opts),
+            display_mac(&reply.mac, opts),
+            display_vendor(vendors, &reply.mac, opts)
+        );
+        if opts.show_country {
+            line.push_str(&format!("{:<7} ", display_country(vendors, &reply.mac, opts).unwrap_or_default()));
+        }
+        if show_names {
+            line.push_str(&format!("{:<24} ", reply.hostname.as_deref().unwrap_or("")));
+        }
+        if show_ports {
+            line.push_str(&format!("{:<24} ", reply.switch_port.as_ref().map(SwitchPort::to_string).unwrap_or_default()));
+        }
+        if scan.rounds > 1 {
+            line.push_str(&format!("{:<9} ", format!("{}/{}", responses(scan, reply), scan.rounds)));
+        }
+        line.push_str(&warnings(scans, scan, reply, opts).join("; "));
+        println!("{}", line.trim_end());
+    }
+}
+
+// What an --enrich-exec command receives on stdin for one host.
+pub fn enrich_input(reply: &HostReply, vendors: &dyn VendorDb, opts: &Options) -> String {
+    format!(
+        "{{\"ip\": {}, \"mac\": {}, \"vendor\": {}, \"interface\": {}}}\n",
+        json_string(&reply.ip.to_string()),
+        json_string(&reply.mac.to_string()),
+        json_string(&display_vendor(vendors, &reply.mac, opts)),
+        json_string(&reply.interface)
+    )
+}
+
+fn extra_fields<'a>(extras: &'a Enrichment, reply: &HostReply) -> &'a [(String, String)] {
+    extras.get(&(reply.interface.clone(), reply.ip)).map(Vec::as_slice).unwrap_or(&[])
+}
+
+pub fn print_enrichment(scans: &[ScanResult], extras: &Enrichment, opts: &Options) {
+    if extras.values().all(Vec::is_empty) {
+        return;
+    }
+    println!("\nEnrichment:");
+    for (_, reply) in sorted(scans) {
+        let fields: Vec<String> = extra_fields(extras, reply).iter()
+            .map(|(key, value)| format!("{}={}", key, enrich::display_value(value)))
+            .collect();
+        if !fields.is_empty() {
+            println!("{:<16} {}", display_ip(reply.ip, opts), fields.join(", "));
+        }
+    }
+}
+
+pub fn print_notes(scans: &[ScanResult], opts: &Options) {
+    let noted: Vec<&HostReply> = sorted(scans).into_iter()
+        .map(|(_, reply)| reply)
+        .filter(|reply| reply.note.is_some())
+        .collect();
+    if noted.is_empty() {
+        return;
+    }
+    println!("\nNotes:");
+    for reply in noted {
+        println!("{:<16} {:<18} {}", display_ip(reply.ip, opts), display_mac(&reply.mac, opts), reply.note.as_deref().unwrap_or(""));
+    }
+}
+
+// Send failures go to stderr as one summary per interface rather than a
+// line per target, which on a flaky link would bury the results.
+pub fn print_send_failures(scans: &[ScanResult], opts: &Options) {
+    for scan in scans {
+        if let Some(pps) = scan.reduced_pps {
+            eprintln!(
+                "Note: the send buffer on {} filled up; slowed down to {} probes per second",
+                scan.interface,
+                pps
+            );
+        }
+        if scan.unsent > 0 {
+            eprintln!(
+                "Warning: ran out of time on {}; {} probes were not sent",
+                scan.interface,
+                scan.unsent
+            );
+        }
+        print_receive_errors(&scan.interface, scan.receive_errors);
+        let Some(first) = scan.send_failures.first() else {
+            continue;
+        };
+        eprintln!(
+            "Warning: {} sends failed on {}, first error: {}",
+            scan.send_failures.len(),
+            scan.interface,
+            first.error
+        );
+        if opts.verbose {
+            for failure in &scan.send_failures {
+                eprintln!("  {:<16} {}", failure.target, failure.error);
+            }
+        }
+    }
+}
+
+pub fn print_rate_limit_hints(scans: &[ScanResult]) {
+    for scan in scans {
+        if let Some(hint) = ratelimit::detect(scan) {
+            eprintln!(
+                "Hint: possible DAI/rate limiting on {}: {}. Try --pps {}",
+                scan.interface,
+                hint.reason,
+                hint.suggested_pps
+            );
+        }
+    }
+}
+
+pub fn print_verify_results(entries: &[CacheEntry], scan: &ScanResult, vendors: &dyn VendorDb, opts: &Options) {
+    println!("\nUnicast Verification ({}):", scan.interface);
+    println!("{:<16} {:<18} {:<30} Result", "IP Address", "Cached MAC", "Manufacturer");
+    println!("{:-<16} {:-<18} {:-<30} {:-<6}", "", "", "", "");
+    let mut failed = 0;
+    for entry in entries {
+        let result = match scan.replies.get(&entry.ip) {
+            Some(reply) if reply.mac == entry.mac => "confirmed".to_string(),
+            Some(reply) => {
+                failed += 1;
+                format!("answered by {}", display_mac(&reply.mac, opts))
+            }
+            None => {
+                failed += 1;
+                "no reply".to_string()
+            }
+        };
+        println!(
+            "{:<16} {:<18} {:<30} {}",
+            display_ip(entry.ip, opts),
+            display_mac(&entry.mac, opts),
+            display_vendor(vendors, &entry.mac, opts),
+            result
+        );
+    }
+    println!("\n{} of {} cache entries confirmed, {} failed to confirm", entries.len() - failed, entries.len(), failed);
+}
+
+pub fn print_padding_report(scans: &[ScanResult], opts: &Options) {
+    println!("\nPadding Analysis:");
+    println!("{:<16} {:<18} {:>9} {:>8} {:>8}  Padding bytes", "IP Address", "MAC Address", "Frame len", "Padding", "Non-zero");
+    println!("{:-<16} {:-<18} {:-<9} {:-<8} {:-<8}  {:-<13}", "", "", "", "", "", "");
+    let mut anomalies = 0;
+    let replies = sorted(scans);
+    for (_, reply) in &replies {
+        // Show the first bytes in hex; that is enough to spot leaked data.
+        let preview: Vec<String> = reply.padding.iter().take(16).map(|b| format!("{:02x}", b)).collect();
+        let more = if reply.padding.len() > 16 { " ..." } else { "" };
+        let flag = if reply.padding_anomaly() {
+            anomalies += 1;
+            "  <- anomaly"
+        } else {
+            ""
+        };
+        println!(
+            "{:<16} {:<18} {:>9} {:>8} {:>8}  {}{}{}",
+            display_ip(reply.ip, opts),
+            display_mac(&reply.mac, opts),
+            reply.frame_len,
+            reply.padding.len(),
+            reply.padding_nonzero(),
+            preview.join(" "),
+            more,
+            flag
+        );
+    }
+    println!("{} of {} hosts sent unusual frame sizes or non-zero padding", anomalies, replies.len());
+}
+
+pub fn print_topology_hints(scans: &[ScanResult], opts: &Options) {
+    let groups = topology::group_hosts(scans);
+    if groups.is_empty() {
+        return;
+    }
+    println!("\nTopology hints (a guess from reply latency):");
+    for group in &groups {
+        let description = match group.placement {
+            Placement::Nearest => "probably on the same switch as this machine".to_string(),
+            Placement::Farther { hops: 1 } => "probably one switch further away".to_string(),
+            Placement::Farther { hops } => format!("probably {} switches further away", hops),
+            Placement::Wireless => "slow or jittery: likely Wi-Fi, a mesh hop or a busy host".to_string(),
+        };
+        let fastest = group.hosts.first().map_or(Duration::ZERO, |host| host.median);
+        let slowest = group.hosts.last().map_or(Duration::ZERO, |host| host.median);
+        println!(
+            "  {} hosts at {}-{} ms, {}:",
+            group.hosts.len(),
+            fmt_ms(fastest),
+            fmt_ms(slowest),
+            description
+        );
+        for host in &group.hosts {
+            println!(
+                "    {:<16} median {} ms, jitter {} ms over {} replies",
+                display_ip(host.ip, opts),
+                fmt_ms(host.median),
+                fmt_ms(host.jitter),
+                host.samples
+            );
+        }
+    }
+    if scans.iter().all(|scan| scan.rounds < 3) {
+        println!("  (Scanning with --rounds 3 or more gives better hints.)");
+    }
+}
+
+// List every interface with its address and whether it can be scanned from.
+// System names go last since on Windows they are long device paths; the
+// friendly name column only appears where the OS has one.
+pub fn print_interfaces() {
+    let names = interfaces::friendly_names();
+    let default = get_default_interface().map(|iface| iface.name);
+    let show_friendly = !names.is_empty();
+
+    if show_friendly {
+        print!("{:<24} ", "Friendly name");
+    }
+    println!("{:<18} {:<18} {:<8} Name", "MAC Address", "IPv4 Address", "Status");
+    if show_friendly {
+        print!("{:-<24} ", "");
+    }
+    println!("{:-<18} {:-<18} {:-<8} {:-<4}", "", "", "", "");
+
+    for iface in datalink::interfaces() {
+        let mac = iface.mac.map(|mac| mac.to_string()).unwrap_or_else(|| "-".to_string());
+        let ipv4 = iface.ips.iter()
+            .find(|ip| ip.is_ipv4())
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let status = if default.as_deref() == Some(iface.name.as_str()) {
+            "default"
+        } else if interfaces::is_usable(&iface) {
+            "usable"
+        } else {
+            "-"
+        };
+        if show_friendly {
+            print!("{:<24} ", interfaces::friendly_name(&iface, &names).unwrap_or("-"));
+        }
+        println!("{:<18} {:<18} {:<8} {}", mac, ipv4, status, iface.name);
+    }
+    if let Some(note) = get_default_interface().as_ref().and_then(interfaces::container_warning) {
+        println!("\n{}", note);
+    }
+}
+
+pub fn print_passive_event(event: &PassiveEvent, vendors: &dyn VendorDb, opts: &Options) {
+    match event {
+        PassiveEvent::NewHost(host, kind) => {
+            let heard = match kind {
+                ReplyKind::Reply => "reply",
+                ReplyKind::Request => "request",
+            };
+            println!(
+                "[{}] New host {:<16} {:<18} {} (from {})",
+                format_timestamp(host.first_seen),
+                host.ip,
+                display_mac(&host.mac, opts),
+                display_vendor(vendors, &host.mac, opts),
+                heard
+            );
+            if host.off_subnet {
+                println!(
+                    "[{}] ALERT: {} uses {}{}, which is outside every local subnet",
+                    format_timestamp(host.first_seen),
+                    display_mac(&host.mac, opts),
+                    host.ip,
+                    if host.ip.is_private() { "" } else { " (a public address)" }
+                );
+            }
+        }
+        PassiveEvent::NewHost6(host) => {
+            println!(
+                "[{}] New host {} {:<18} {}{}",
+                format_timestamp(host.first_seen),
+                host.ip,
+                display_mac(&host.mac, opts),
+                display_vendor(vendors, &host.mac, opts),
+                if host.router { " (router)" } else { "" }
+            );
+        }
+        PassiveEvent::Storm(alert) => print_storm_alert(alert, vendors, opts),
+        PassiveEvent::Neighbor(neighbor, at) => {
+            println!("[{}] Switch   {} via {}", format_timestamp(*at), describe_neighbor(neighbor, opts), neighbor.protocol)
+        }
+        PassiveEvent::Dhcp(client, at) => {
+            println!(
+                "[{}] DHCP     {:<18} {} {}",
+                format_timestamp(*at),
+                display_mac(&client.mac, opts),
+                client.message,
+                describe_dhcp_client(client)
+            )
+        }
+        PassiveEvent::Seen(..) | PassiveEvent::Asked { .. } => {}
+    }
+}
+
+pub fn print_defend_event(event: &DefendEvent, vendors: &dyn VendorDb, opts: &Options) {
+    match event {
+        DefendEvent::Started(interface, ips) => {
+            let ips: Vec<String> = ips.iter().map(Ipv4Addr::to_string).collect();
+            println!("\nDefending {} on {} (policy {})...", ips.join(", "), interface, opts.defend_policy);
+        }
+        DefendEvent::Conflict(conflict) => println!(
+            "[{}] CONFLICT {:<16} claimed by {} ({}), {}",
+            format_timestamp(conflict.at),
+            conflict.ip,
+            display_mac(&conflict.mac, opts),
+            display_vendor(vendors, &conflict.mac, opts),
+            match conflict.action {
+                DefendAction::Defended => "defended",
+                DefendAction::Logged => "logged",
+                DefendAction::GaveUp => "not defended, the other host keeps claiming it",
+            }
+        ),
+        DefendEvent::SendFailed(ip, error) => eprintln!("Warning: could not defend {}: {}", ip, error),
+    }
+}
+
+fn print_storm_alert(alert: &StormAlert, vendors: &dyn VendorDb, opts: &Options) {
+    println!("{}", storm_alert_line(alert, vendors, opts));
+}
+
+fn storm_alert_line(alert: &StormAlert, vendors: &dyn VendorDb, opts: &Options) -> String {
+    let source = match alert.source {
+        Some(mac) => format!("{} ({}) sent", display_mac(&mac, opts), display_vendor(vendors, &mac, opts)),
+        None => "the segment carried".to_string(),
+    };
+    format!(
+        "[{}] ALERT: ARP storm / possible scan or attack in progress: {} {} requests in {} ms",
+        format_timestamp(alert.at),
+        source,
+        alert.requests,
+        alert.window.as_millis()
+    )
+}
+
+pub fn print_monitor_event(event: &MonitorEvent, vendors: &dyn VendorDb, opts: &Options) {
+    match event {
+        MonitorEvent::Failed(..) => eprintln!("{}", monitor_event_line(event, vendors, opts)),
+        _ => println!("{}", monitor_event_line(event, vendors, opts)),
+    }
+}
+
+// The log line for a monitor event, as printed and as mailed.
+pub fn monitor_event_line(event: &MonitorEvent, vendors: &dyn VendorDb, opts: &Options) -> String {
+    match event {
+        MonitorEvent::New(ip, mac, at) => format!(
+            "[{}] NEW      {:<16} {:<18} {}",
+            format_timestamp(*at),
+            ip,
+            display_mac(mac, opts),
+            display_vendor(vendors, mac, opts)
+        ),
+        MonitorEvent::Gone(ip, mac, at) => format!(
+            "[{}] GONE     {:<16} {:<18} {}",
+            format_timestamp(*at),
+            ip,
+            display_mac(mac, opts),
+            display_vendor(vendors, mac, opts)
+        ),
+        MonitorEvent::Changed { ip, old, new, at } => format!(
+            "[{}] CHANGED  {:<16} {} -> {} ({})",
+            format_timestamp(*at),
+            ip,
+            display_mac(old, opts),
+            display_mac(new, opts),
+            display_vendor(vendors, new, opts)
+        ),
+        MonitorEvent::Flapping(FlapAlert::Ip { ip, macs, changes, first, last }) => {
+            let macs: Vec<String> = macs.iter().map(|mac| display_mac(mac, opts)).collect();
+            format!(
+                "[{}] FLAPPING {:<16} changed MAC {} times between {} (first {}, last {})",
+                format_timestamp(*last),
+                ip,
+                changes,
+                macs.join(", "),
+                format_timestamp(*first),
+                format_timestamp(*last)
+            )
+        }
+        MonitorEvent::Flapping(FlapAlert::Mac { mac, ips, changes, first, last }) => {
+            let ips: Vec<String> = ips.iter().map(|ip| ip.to_string()).collect();
+            format!(
+                "[{}] FLAPPING {:<16} changed IPs {} times among {} (first {}, last {})",
+                format_timestamp(*last),
+                display_mac(mac, opts),
+                changes,
+                ips.join(", "),
+                format_timestamp(*first),
+                format_timestamp(*last)
+            )
+        }
+        MonitorEvent::Storm(alert) => storm_alert_line(alert, vendors, opts),
+        MonitorEvent::Trap(alert) => {
+            let from = if alert.from.is_unspecified() { "a DAD probe".to_string() } else { alert.from.to_string() };
+            format!(
+                "[{}] TRAP     {:<16} {} by {} ({}) from {}",
+                format_timestamp(alert.at),
+                alert.trap,
+                match alert.kind {
+                    TrapKind::Asked => "asked for",
+                    TrapKind::Claimed => "claimed",
+                },
+                display_mac(&alert.mac, opts),
+                display_vendor(vendors, &alert.mac, opts),
+                from
+            )
+        }
+        MonitorEvent::Quiet(true, at) => format!("[{}] QUIET    quiet hours started, sweeps paused", format_timestamp(*at)),
+        MonitorEvent::Quiet(false, at) => format!("[{}] QUIET    quiet hours over, sweeps resumed", format_timestamp(*at)),
+        MonitorEvent::Failed(error, at) => format!("[{}] ERROR    {}", format_timestamp(*at), error),
+    }
+}
+
+// One line for a switch announcement: who, which port, which VLAN.
+fn describe_neighbor(neighbor: &Neighbor, opts: &Options) -> String {
+    let mut parts = vec![neighbor.name().unwrap_or("unnamed").to_string()];
+    if let Some(port) = &neighbor.port {
+        match &neighbor.port_description {
+            Some(description) if description != port => parts.push(format!("port {} ({})", port, description)),
+            _ => parts.push(format!("port {}", port)),
+        }
+    }
+    if let Some(vlan) = neighbor.vlan {
+        parts.push(format!("VLAN {}", vlan));
+    }
+    if let Some(ip) = neighbor.management_ip {
+        parts.push(format!("management {}", ip));
+    }
+    parts.push(format!("from {}", display_mac(&neighbor.source, opts)));
+    parts.join(", ")
+}
+
+fn describe_dhcp_client(client: &DhcpClient) -> String {
+    let mut parts = Vec::new();
+    if let Some(hostname) = &client.hostname {
+        parts.push(format!("hostname {}", hostname));
+    }
+    if let Some(vendor_class) = &client.vendor_class {
+        parts.push(format!("class '{}'", vendor_class));
+    }
+    if let Some(ip) = client.requested_ip {
+        parts.push(format!("asking for {}", ip));
+    }
+    if let Some(fingerprint) = client.fingerprint() {
+        parts.push(format!("fingerprint {}", fingerprint));
+    }
+    if let Some(agent) = &client.relay_agent {
+        parts.push(format!("relayed from {}", agent.switch_port()));
+    }
+    parts.join(", ")
+}
+
+// Where a host heard in passive mode is plugged in: option 82 from its own
+// relayed DHCP beats an imported table, which may be out of date.
+fn passive_switch_port(result: &PassiveResult, snooping: &SnoopingTable, mac: &MacAddr) -> Option<SwitchPort> {
+    result.dhcp.get(mac)
+        .and_then(|client| client.relay_agent.as_ref())
+        .map(RelayAgent::switch_port)
+        .or_else(|| snooping.get(mac).cloned())
+}
+
+// Like send failures, failed reads are counted and reported once.
+pub fn print_receive_errors(interface: &str, errors: usize) {
+    if errors > 0 {
+        eprintln!("Warning: {} reads from the capture channel failed on {}", errors, interface);
+    }
+}
+
+pub fn print_passive_summary(result: &PassiveResult, snooping: &SnoopingTable, vendors: &dyn VendorDb, opts: &Options) {
+    // One row per device, by its lowest IPv4 address; devices only heard
+    // over IPv6 come last. Further addresses go on the lines below.
+    let devices = HostTable::from_passive(result);
+    let mut devices: Vec<&Host> = devices.iter().collect();
+    devices.sort_by_key(|device| (device.ipv4.first().is_none(), device.ipv4.first().copied(), device.mac));
+    let off_subnet = |ip: &Ipv4Addr| result.hosts.get(ip).is_some_and(|host| host.off_subnet);
+
+    println!("\nHosts heard on {}:", result.interface);
+    println!("{:<16} {:<18} {:<30} {:>7}  Last seen", "IP Address", "MAC Address", "Manufacturer", "Packets");
+    println!("{:-<16} {:-<18} {:-<30} {:-<7}  {:-<9}", "", "", "", "", "");
+    for device in &devices {
+        let packets: u64 = result.hosts.values().filter(|host| host.mac == device.mac).map(|host| host.packets)
+            .chain(result.hosts6.values().filter(|host| host.mac == device.mac).map(|host| host.packets))
+            .sum();
+        let mut ipv4 = device.ipv4.iter();
+        let first = ipv4.next();
+        println!(
+            "{:<16} {:<18} {:<30} {:>7}  {}{}{}{}",
+            first.map_or("-".to_string(), Ipv4Addr::to_string),
+            display_mac(&device.mac, opts),
+            display_vendor(vendors, &device.mac, opts),
+            packets,
+            format_timestamp(device.last_seen),
+            if first.is_some_and(off_subnet) { "  off-subnet" } else { "" },
+            match device.name() {
+                Some(name) => format!("  {}", name),
+                None => String::new(),
+            },
+            match passive_switch_port(result, snooping, &device.mac) {
+                Some(port) => format!("  at {}", port),
+                None => String::new(),
+            }
+        );
+        for ip in ipv4 {
+            println!("{:<16}{}", ip, if off_subnet(ip) { "  off-subnet" } else { "" });
+        }
+        for ip in &device.ipv6 {
+            let router = result.hosts6.get(ip).is_some_and(|host| host.router);
+            println!("{:<16} IPv6 {}{}", "", ip, if router { " (router)" } else { "" });
+        }
+    }
+    let off_subnet = result.hosts.values().filter(|host| host.off_subnet).count();
+    if result.hosts6.is_empty() {
+        println!("\n{} hosts, {} storm alerts", result.hosts.len(), result.alerts.len());
+    } else {
+        println!(
+            "\n{} devices, {} IPv4 and {} IPv6 addresses, {} storm alerts",
+            devices.len(),
+            result.hosts.len(),
+            result.hosts6.len(),
+            result.alerts.len()
+        );
+    }
+    if !result.prefixes.is_empty() {
+        let prefixes: Vec<String> = result.prefixes.iter().map(|(prefix, len)| format!("{}/{}", prefix, len)).collect();
+        println!("IPv6 prefixes advertised: {}", prefixes.join(", "));
+    }
+    if off_subnet > 0 {
+        let subnets: Vec<String> = result.local.subnets().iter().map(|subnet| subnet.to_string()).collect();
+        println!("{} hosts use addresses outside the local subnets ({})", off_subnet, subnets.join(", "));
+    }
+    if !result.neighbors.is_empty() {
+        let mut neighbors: Vec<_> = result.neighbors.iter().collect();
+        neighbors.sort_by_key(|neighbor| (neighbor.name().map(str::to_string), neighbor.source));
+        println!("\nSwitches heard on {}:", result.interface);
+        for neighbor in neighbors {
+            println!("  {:<4}  {}", neighbor.protocol, describe_neighbor(neighbor, opts));
+            if let Some(description) = &neighbor.description {
+                println!("        {}", description.lines().next().unwrap_or_default());
+            }
+        }
+    }
+    if !result.dhcp.is_empty() {
+        let mut clients: Vec<_> = result.dhcp.values().collect();
+        clients.sort_by_key(|client| client.mac);
+        println!("\nDHCP clients heard on {}:", result.interface);
+        for client in clients {
+            // The address the client holds per ARP, else the one it asked for
+            let ip = devices.iter()
+                .find(|device| device.mac == client.mac)
+                .and_then(|device| device.ipv4.first().copied())
+                .or(client.requested_ip)
+                .map_or("-".to_string(), |ip| ip.to_string());
+            println!(
+                "  {:<16} {:<18} {:<24} {}",
+                ip,
+                display_mac(&client.mac, opts),
+                client.hostname.as_deref().unwrap_or("-"),
+                client.vendor_class.as_deref().unwrap_or("")
+            );
+            if let Some(fingerprint) = client.fingerprint() {
+                println!("  {:<16} fingerprint {}", "", fingerprint);
+            }
+            if let Some(port) = passive_switch_port(result, snooping, &client.mac) {
+                println!("  {:<16} at {}", "", port);
+            }
+        }
+    }
+}
+
+pub fn print_compare_row(ip: Ipv4Addr, mac: Option<&MacAddr>, found_by: &str, vendors: &dyn VendorDb, opts: &Options) {
+    let (mac, vendor) = match mac {
+        Some(mac) => (display_mac(mac, opts), display_vendor(vendors, mac, opts)),
+        None => ("-".to_string(), "-".to_string()),
+    };
+    println!("{:<16} {:<18} {:<10} {}", display_ip(ip, opts), mac, found_by, vendor);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Field {
+    Ip,
+    Mac,
+    Vendor,
+    Rtt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+// A parsed --format string. Placeholders are written as {name}; '{{' and
+// '}}' produce literal braces and \t, \n and \\ are unescaped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = spec.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                    None => text.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("Unclosed placeholder '{{{}' in format", name)),
+                        }
+                    }
+                    let field = match name.as_str() {
+                        "ip" => Field::Ip,
+                        "mac" => Field::Mac,
+                        "vendor" => Field::Vendor,
+                        "rtt" => Field::Rtt,
+                        other => {
+                            return Err(format!(
+                                "Unknown placeholder '{{{}}}'. Use: {{ip}}, {{mac}}, {{vendor}}, {{rtt}}",
+                                other
+                            ))
+                        }
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err("Unmatched '}' in format. Use '}}' for a literal brace".to_string()),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+
+        Ok(Template { pieces })
+    }
+
+    pub fn render(&self, reply: &HostReply, vendors: &dyn VendorDb, opts: &Options) -> String {
+        let mut line = String::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(text) => line.push_str(text),
+                Piece::Field(Field::Ip) => line.push_str(&reply.ip.to_string()),
+                Piece::Field(Field::Mac) => line.push_str(&display_mac(&reply.mac, opts)),
+                Piece::Field(Field::Vendor) => line.push_str(&display_vendor(vendors, &reply.mac, opts)),
+                Piece::Field(Field::Rtt) => line.push_str(&display_rtt(reply)),
+            }
+        }
+        line
+    }
+}
+
+fn fmt_ms(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+// Round-trip time in milliseconds, or '-' if the reply couldn't be matched
+// to a request we sent.
+fn display_rtt(reply: &HostReply) -> String {
+    reply.rtt.map(fmt_ms).unwrap_or_else(|| "-".to_string())
+}
+
+pub fn print_template(scans: &[ScanResult], template: &Template, vendors: &dyn VendorDb, opts: &Options) {
+    for (_, reply) in sorted(scans) {
+        println!("{}", template.render(reply, vendors, opts));
+    }
+}
+
+// Static configuration built from the responders, with the vendor as a
+// comment so the file is reviewable by eye. dnsmasq only treats whole lines
+// as comments, so there it goes on the line above.
+pub fn print_export(scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) {
+    match opts.export {
+        Some(ExportFormat::AnsibleInventory) => return print_ansible_inventory(scans, vendors, opts),
+        Some(ExportFormat::Graph) => return print_dot(&network_map(scans, vendors, opts)),
+        Some(ExportFormat::Mermaid) => return print_mermaid(&network_map(scans, vendors, opts)),
+        _ => {}
+    }
+    for (_, reply) in sorted(scans) {
+        let vendor = display_vendor(vendors, &reply.mac, opts);
+        match opts.export {
+            Some(ExportFormat::Ethers) => println!("{} {}\t# {}", opts.mac_format.format(&reply.mac), reply.ip, vendor),
+            Some(ExportFormat::Dnsmasq) => {
+                println!("# {}\ndhcp-host={},{}", vendor, opts.mac_format.format(&reply.mac), reply.ip)
+            }
+            _ => {}
+        }
+    }
+}
+
+// Ansible group names must be valid identifiers.
+fn group_name(name: &str) -> String {
+    let mut group = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            group.push(c.to_ascii_lowercase());
+        } else if !group.is_empty() && !group.ends_with('_') {
+            group.push('_');
+        }
+    }
+    let group = group.trim_end_matches('_');
+    match group.chars().next() {
+        None => "unknown".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("_{}", group),
+        Some(_) => group.to_string(),
+    }
+}
+
+// YAML inventory with one child group per --label or vendor. JSON strings
+// are valid YAML double-quoted scalars, so values reuse json_string.
+fn print_ansible_inventory(scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) {
+    let mut groups: Vec<(String, Vec<&HostReply>)> = Vec::new();
+    for (_, reply) in sorted(scans) {
+        let group = group_name(&host_group(reply, vendors, opts));
+        match groups.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, hosts)) => hosts.push(reply),
+            None => groups.push((group, vec![reply])),
+        }
+    }
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    println!("all:");
+    println!("  children:");
+    for (group, hosts) in &groups {
+        println!("    {}:", group);
+        println!("      hosts:");
+        for reply in hosts {
+            println!("        {}:", reply.ip);
+            println!("          mac: {}", json_string(&display_mac(&reply.mac, opts)));
+            println!("          vendor: {}", json_string(&display_vendor(vendors, &reply.mac, opts)));
+        }
+    }
+}
+
+// The --label group a host falls in, or its vendor's.
+fn host_group(reply: &HostReply, vendors: &dyn VendorDb, opts: &Options) -> String {
+    opts.labels.iter()
+        .find(|(ips, _)| ips.contains(&reply.ip))
+        .map(|(_, label)| label.clone())
+        .unwrap_or_else(|| display_vendor(vendors, &reply.mac, opts))
+}
+
+struct MapNode {
+    id: String,
+    lines: Vec<String>,
+    gateway: bool,
+    // Part of a conflict edge
+    conflict: bool,
+}
+
+struct MapEdge {
+    from: String,
+    to: String,
+    label: Option<String>,
+    conflict: bool,
+}
+
+// One segment per scanned interface: the scanning host, the gateway and
+// the hosts around them in groups.
+struct MapSegment {
+    id: String,
+    lines: Vec<String>,
+    groups: Vec<(String, Vec<MapNode>)>,
+}
+
+struct NetworkMap {
+    segments: Vec<MapSegment>,
+    // Nodes outside any group, e.g. a MAC that only appeared as a frame
+    // source
+    strays: Vec<MapNode>,
+    edges: Vec<MapEdge>,
+}
+
+fn map_id(interface: &str, ip: Ipv4Addr) -> String {
+    let clean: String = interface.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    format!("h_{}_{}", clean, u32::from(ip))
+}
+
+// Everything the graph and mermaid exports draw. The gateway is this
+// machine's default route on the scanned interface, so it's only marked
+// when it answered; conflicts are a MAC answering for several addresses
+// and frames whose Ethernet source isn't the ARP sender.
+fn network_map(scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) -> NetworkMap {
+    let routes = route::default_routes();
+    let mut map = NetworkMap { segments: Vec::new(), strays: Vec::new(), edges: Vec::new() };
+    for scan in scans {
+        let clean: String = scan.interface.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let mut segment = MapSegment {
+            id: format!("seg_{}", clean),
+            lines: vec![scan.interface.clone(), format!("{} (this host)", scan.source_ip)],
+            groups: Vec::new(),
+        };
+        let gateway = routes.iter().find(|route| route.interface == scan.interface).map(|route| route.gateway);
+        let mut replies: Vec<&HostReply> = scan.replies.values().collect();
+        replies.sort_by_key(|reply| reply.ip);
+
+        let mut by_mac: BTreeMap<MacAddr, Vec<Ipv4Addr>> = BTreeMap::new();
+        for reply in &replies {
+            by_mac.entry(reply.mac).or_default().push(reply.ip);
+        }
+        let shared = |mac: &MacAddr| by_mac.get(mac).is_some_and(|ips| ips.len() > 1);
+
+        for reply in &replies {
+            let id = map_id(&scan.interface, reply.ip);
+            let mut lines = vec![display_ip(reply.ip, opts), display_mac(&reply.mac, opts)];
+            if let Some(name) = &reply.hostname {
+                lines.push(name.clone());
+            }
+            let node = MapNode {
+                id: id.clone(),
+                lines,
+                gateway: Some(reply.ip) == gateway,
+                conflict: shared(&reply.mac) || reply.eth_source_mismatch(),
+            };
+            map.edges.push(MapEdge {
+                from: segment.id.clone(),
+                to: id.clone(),
+                label: node.gateway.then(|| "gateway".to_string()),
+                conflict: false,
+            });
+            let group = host_group(reply, vendors, opts);
+            match segment.groups.iter_mut().find(|(name, _)| *name == group) {
+                Some((_, nodes)) => nodes.push(node),
+                None => segment.groups.push((group, vec![node])),
+            }
+
+            if reply.eth_source_mismatch() {
+                let source = match replies.iter().find(|other| other.mac == reply.eth_source) {
+                    Some(other) => map_id(&scan.interface, other.ip),
+                    None => {
+                        let stray = format!("mac_{}_{}", clean, reply.eth_source.to_string().replace(':', ""));
+                        if !map.strays.iter().any(|node| node.id == stray) {
+                            map.strays.push(MapNode {
+                                id: stray.clone(),
+                                lines: vec![display_mac(&reply.eth_source, opts), "frame source".to_string()],
+                                gateway: false,
+                                conflict: true,
+                            });
+                        }
+                        stray
+                    }
+                };
+                map.edges.push(MapEdge { from: source, to: id.clone(), label: Some("sent ARP for".to_string()), conflict: true });
+            }
+        }
+        // Chain the addresses sharing a MAC rather than drawing every pair
+        let mut shared_macs: Vec<&Vec<Ipv4Addr>> = by_mac.values().filter(|ips| ips.len() > 1).collect();
+        shared_macs.sort();
+        for ips in shared_macs {
+            for pair in ips.windows(2) {
+                map.edges.push(MapEdge {
+                    from: map_id(&scan.interface, pair[0]),
+                    to: map_id(&scan.interface, pair[1]),
+                    label: Some("same MAC".to_string()),
+                    conflict: true,
+                });
+            }
+        }
+        segment.groups.sort_by(|a, b| a.0.cmp(&b.0));
+        map.segments.push(segment);
+    }
+    map
+}
+
+fn dot_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn dot_label(lines: &[String]) -> String {
+    let lines: Vec<String> = lines.iter().map(|line| line.replace('\\', "\\\\").replace('"', "\\\"")).collect();
+    format!("\"{}\"", lines.join("\\n"))
+}
+
+fn print_dot(map: &NetworkMap) {
+    let node = |node: &MapNode, indent: &str| {
+        let mut attrs = vec![format!("label={}", dot_label(&node.lines))];
+        if node.gateway {
+            attrs.push("shape=doubleoctagon, style=\"filled,bold\", fillcolor=\"#ddf4ff\"".to_string());
+        }
+        if node.conflict {
+            attrs.push("color=red, fontcolor=red".to_string());
+        }
+        println!("{}{} [{}];", indent, dot_string(&node.id), attrs.join(", "));
+    };
+    println!("graph arp_scan {{");
+    println!("  rankdir=LR;");
+    println!("  node [shape=box, style=rounded, fontname=\"Helvetica\", fontsize=10];");
+    println!("  edge [color=\"#8c959f\"];");
+    for segment in &map.segments {
+        println!("  {} [label={}, shape=box3d, style=bold];", dot_string(&segment.id), dot_label(&segment.lines));
+        for (index, (group, nodes)) in segment.groups.iter().enumerate() {
+            println!("  subgraph {} {{", dot_string(&format!("cluster_{}_{}", segment.id, index)));
+            println!("    label={};", dot_string(group));
+            println!("    style=dashed; color=\"#d0d7de\";");
+            for host in nodes {
+                node(host, "    ");
+            }
+            println!("  }}");
+        }
+    }
+    for stray in &map.strays {
+        node(stray, "  ");
+    }
+    for edge in &map.edges {
+        let mut attrs = Vec::new();
+        if let Some(label) = &edge.label {
+            attrs.push(format!("label={}", dot_string(label)));
+        }
+        if edge.conflict {
+            attrs.push("color=red, fontcolor=red, style=dashed, penwidth=2, constraint=false".to_string());
+        }
+        let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+        println!("  {} -- {}{};", dot_string(&edge.from), dot_string(&edge.to), attrs);
+    }
+    println!("}}");
+}
+
+// Mermaid labels are quoted; quotes inside become entities.
+fn mermaid_label(lines: &[String]) -> String {
+    let lines: Vec<String> = lines.iter().map(|line| line.replace('"', "#quot;")).collect();
+    format!("\"{}\"", lines.join("<br/>"))
+}
+
+fn print_mermaid(map: &NetworkMap) {
+    let node = |node: &MapNode, indent: &str| {
+        if node.gateway {
+            println!("{}{}{{{{{}}}}}", indent, node.id, mermaid_label(&node.lines));
+        } else {
+            println!("{}{}[{}]", indent, node.id, mermaid_label(&node.lines));
+        }
+    };
+    println!("graph LR");
+    for segment in &map.segments {
+        println!("  {}[({})]", segment.id, mermaid_label(&segment.lines));
+        for (index, (group, nodes)) in segment.groups.iter().enumerate() {
+            println!("  subgraph {}_{}[{}]", segment.id, index, mermaid_label(std::slice::from_ref(group)));
+            for host in nodes {
+                node(host, "    ");
+            }
+            println!("  end");
+        }
+    }
+    for stray in &map.strays {
+        node(stray, "  ");
+    }
+    let mut conflict_edges = Vec::new();
+    for (index, edge) in map.edges.iter().enumerate() {
+        let link = match (&edge.label, edge.conflict) {
+            (Some(label), true) => format!("-. {} .-", label.replace('"', "#quot;")),
+            (None, true) => "-.-".to_string(),
+            (Some(label), false) => format!("---|{}|", label.replace('"', "#quot;")),
+            (None, false) => "---".to_string(),
+        };
+        println!("  {} {} {}", edge.from, link, edge.to);
+        if edge.conflict {
+            conflict_edges.push(index.to_string());
+        }
+    }
+    println!("  classDef gateway fill:#ddf4ff,stroke:#0969da,stroke-width:2px");
+    println!("  classDef conflict stroke:#cf222e,color:#cf222e,stroke-width:2px");
+    let ids = |wanted: fn(&MapNode) -> bool| -> Vec<String> {
+        map.segments.iter()
+            .flat_map(|segment| segment.groups.iter().flat_map(|(_, nodes)| nodes))
+            .chain(&map.strays)
+            .filter(|node| wanted(node))
+            .map(|node| node.id.clone())
+            .collect()
+    };
+    let gateways = ids(|node| node.gateway);
+    if !gateways.is_empty() {
+        println!("  class {} gateway", gateways.join(","));
+    }
+    let conflicts = ids(|node| node.conflict);
+    if !conflicts.is_empty() {
+        println!("  class {} conflict", conflicts.join(","));
+    }
+    if !conflict_edges.is_empty() {
+        println!("  linkStyle {} stroke:#cf222e,stroke-width:2px", conflict_edges.join(","));
+    }
+}
+
+// Every reply across the scanned interfaces, ordered by IP then interface.
+fn sorted(scans: &[ScanResult]) -> Vec<(&ScanResult, &HostReply)> {
+    let mut replies: Vec<(&ScanResult, &HostReply)> = scans.iter()
+        .flat_map(|scan| scan.replies.values().map(move |reply| (scan, reply)))
+        .collect();
+    replies.sort_by(|a, b| (a.1.ip, &a.1.interface).cmp(&(b.1.ip, &b.1.interface)));
+    replies
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn json_rtt(reply: &HostReply) -> String {
+    reply.rtt.map(fmt_ms).unwrap_or_else(|| "null".to_string())
+}
+
+fn latency_stats(scans: &[ScanResult]) -> Option<LatencyStats> {
+    let samples: Vec<Duration> = scans.iter()
+        .flat_map(|scan| scan.replies.values())
+        .filter_map(|reply| reply.rtt)
+        .collect();
+    LatencyStats::from_samples(&samples)
+}
+
+pub fn print_latency_summary(scans: &[ScanResult]) {
+    let Some(stats) = latency_stats(scans) else {
+        return;
+    };
+    println!(
+        "\nLatency over {} hosts: p50 {} ms, p90 {} ms, p99 {} ms (min {}, max {})",
+        stats.count,
+        fmt_ms(stats.p50),
+        fmt_ms(stats.p90),
+        fmt_ms(stats.p99),
+        fmt_ms(stats.min),
+        fmt_ms(stats.max)
+    );
+    // Only print the populated span of the histogram to keep it compact.
+    let first = stats.histogram.iter().position(|n| *n > 0).unwrap_or(0);
+    let last = stats.histogram.iter().rposition(|n| *n > 0).unwrap_or(0);
+    let widest = *stats.histogram.iter().max().unwrap_or(&1);
+    for (i, count) in stats.histogram.iter().enumerate().take(last + 1).skip(first) {
+        let bar = "#".repeat((count * 40).div_ceil(widest));
+        println!("  {:>12} {:>5} {}", LatencyStats::bucket_label(i), count, bar);
+    }
+}
+
+fn scan_summary(scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) -> Summary {
+    Summary::from_scans(scans, |mac| display_vendor(vendors, mac, opts))
+}
+
+pub fn print_summary(scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) {
+    let summary = scan_summary(scans, vendors, opts);
+    println!("\nSummary:");
+    match summary.response_rate() {
+        Some(rate) => println!("  {} hosts answered out of {} addresses probed ({:.1}%).", summary.hosts, summary.probed, rate),
+        None => println!("  {} hosts answered.", summary.hosts),
+    }
+    println!(
+        "  {} have a note; {} use randomized (locally administered) MACs.",
+        summary.noted,
+        summary.randomized
+    );
+    if summary.rounds > 1 {
+        println!(
+            "  {} answered all {} rounds, {} only some of them.",
+            summary.steady,
+            summary.rounds,
+            summary.hosts - summary.steady
+        );
+        let attempts: Vec<String> = summary.by_attempt.iter()
+            .map(|(attempt, count)| format!("{} on attempt {}", count, attempt))
+            .collect();
+        if !attempts.is_empty() {
+            println!("  First answers: {}.", attempts.join(", "));
+        }
+    }
+    if summary.hosts == 0 {
+        return;
+    }
+    println!("\nHosts per vendor:");
+    for (vendor, count) in &summary.by_vendor {
+        println!("  {:<40} {:>5}", vendor, count);
+    }
+    println!("\nHosts per /24:");
+    for (subnet, count) in &summary.by_subnet {
+        println!("  {:<40} {:>5}", subnet.to_string(), count);
+    }
+}
+
+fn json_summary(summary: &Summary) -> String {
+    let counts = |items: Vec<(String, usize)>| {
+        let items: Vec<String> = items.iter().map(|(key, count)| format!("{}: {}", json_string(key), count)).collect();
+        format!("{{{}}}", items.join(", "))
+    };
+    format!(
+        "{{\"hosts\": {}, \"probed\": {}, \"response_rate_pct\": {}, \"noted\": {}, \"randomized\": {}, \"rounds\": {}, \"steady\": {}, \"by_attempt\": {}, \"by_vendor\": {}, \"by_subnet\": {}}}",
+        summary.hosts,
+        summary.probed,
+        summary.response_rate().map_or("null".to_string(), |rate| format!("{:.1}", rate)),
+        summary.noted,
+        summary.randomized,
+        summary.rounds,
+        summary.steady,
+        counts(summary.by_attempt.iter().map(|(attempt, count)| (attempt.to_string(), *count)).collect()),
+        counts(summary.by_vendor.clone()),
+        counts(summary.by_subnet.iter().map(|(subnet, count)| (subnet.to_string(), *count)).collect())
+    )
+}
+
+fn json_stats(scans: &[ScanResult]) -> String {
+    let Some(stats) = latency_stats(scans) else {
+        return "{\"latency_ms\": null}".to_string();
+    };
+    let buckets: Vec<String> = stats.histogram.iter().enumerate()
+        .map(|(i, count)| format!("{{\"bucket\": {}, \"count\": {}}}", json_string(&LatencyStats::bucket_label(i)), count))
+        .collect();
+    format!(
+        "{{\"latency_ms\": {{\"count\": {}, \"min\": {}, \"max\": {}, \"mean\": {}, \"p50\": {}, \"p90\": {}, \"p99\": {}, \"histogram\": [{}]}}}}",
+        stats.count,
+        fmt_ms(stats.min),
+        fmt_ms(stats.max),
+        fmt_ms(stats.mean),
+        fmt_ms(stats.p50),
+        fmt_ms(stats.p90),
+        fmt_ms(stats.p99),
+        buckets.join(", ")
+    )
+}
+
+fn json_meta(meta: &ScanMeta, opts: &Options) -> String {
+    let targets: Vec<String> = meta.targets.iter().map(|t| json_string(t)).collect();
+    let settings: Vec<String> = meta.settings.iter()
+        .map(|(key, value)| format!("{}: {}", json_string(key), json_string(value)))
+        .collect();
+    format!(
+        "{{\"scan_id\": {}, \"started\": {}, \"finished\": {}, \"tool_version\": {}, \"interface\": {}, \"source_ip\": {}, \"source_mac\": {}, \"targets\": [{}], \"settings\": {{{}}}}}",
+        json_string(&meta.scan_id),
+        json_string(&format_timestamp(meta.started)),
+        json_string(&format_timestamp(meta.finished)),
+        json_string(TOOL_VERSION),
+        json_string(&meta.interface),
+        json_string(&meta.source_ip.to_string()),
+        json_string(&display_mac(&meta.source_mac, opts)),
+        targets.join(", "),
+        settings.join(", ")
+    )
+}
+
+fn json_switch_port(port: &SwitchPort) -> String {
+    format!(
+        "{{\"switch\": {}, \"port\": {}, \"vlan\": {}}}",
+        port.switch.as_deref().map_or("null".to_string(), json_string),
+        json_string(&port.port),
+        port.vlan.map_or("null".to_string(), |vlan| vlan.to_string())
+    )
+}
+
+fn json_host(scans: &[ScanResult], scan: &ScanResult, reply: &HostReply, extras: &Enrichment, vendors: &dyn VendorDb, opts: &Options) -> String {
+    let extra: Vec<String> = extra_fields(extras, reply).iter()
+        .map(|(key, value)| format!("{}: {}", json_string(key), value))
+        .collect();
+    let elsewhere: Vec<String> = also_seen_on(scans, reply).iter().map(|name| json_string(name)).collect();
+    let duplicate_macs: Vec<String> = reply.duplicate_macs.iter().map(|mac| json_string(&display_mac(mac, opts))).collect();
+    let roamed_from: Vec<String> = reply.roamed_from.iter()
+        .map(|roam| format!(
+            "{{\"interface\": {}, \"ip\": {}, \"seen\": {}}}",
+            json_string(&roam.interface),
+            json_string(&roam.ip.to_string()),
+            json_string(&format_timestamp(roam.at))
+        ))
+        .collect();
+    let fields = [
+        ("ip", json_string(&reply.ip.to_string())),
+        ("mac", json_string(&display_mac(&reply.mac, opts))),
+        ("vendor", json_string(&display_vendor(vendors, &reply.mac, opts))),
+        ("country", display_country(vendors, &reply.mac, opts).map_or("null".to_string(), |c| json_string(&c))),
+        ("rtt_ms", json_rtt(reply)),
+        ("eth_source", json_string(&display_mac(&reply.eth_source, opts))),
+        ("eth_source_mismatch", reply.eth_source_mismatch().to_string()),
+        ("frame_len", reply.frame_len.to_string()),
+        ("padding_nonzero", reply.padding_nonzero().to_string()),
+        ("responses", responses(scan, reply).to_string()),
+        ("interface", json_string(&reply.interface)),
+        ("hostname", reply.hostname.as_deref().map_or("null".to_string(), json_string)),
+        ("note", reply.note.as_deref().map_or("null".to_string(), json_string)),
+        ("switch_port", reply.switch_port.as_ref().map_or("null".to_string(), json_switch_port)),
+        ("duplicates", reply.duplicates.to_string()),
+        ("duplicate_macs", format!("[{}]", duplicate_macs.join(", "))),
+        ("attempt", reply.attempt.map_or("null".to_string(), |attempt| attempt.to_string())),
+        ("roamed_from", format!("[{}]", roamed_from.join(", "))),
+        ("also_seen_on", format!("[{}]", elsewhere.join(", "))),
+        ("extra", format!("{{{}}}", extra.join(", "))),
+    ];
+    let fields: Vec<String> = fields.iter()
+        .map(|(key, value)| format!("{}: {}", json_string(key), value))
+        .collect();
+    format!("    {{{}}}", fields.join(", "))
+}
+
+// The scan's hosts as devices, labelled and with vendors filled in.
+fn scan_devices(scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) -> HostTable {
+    let mut devices = HostTable::from_scans(scans);
+    for (ips, label) in &opts.labels {
+        devices.label(ips, label);
+    }
+    devices.fill_vendors(|mac| Some(display_vendor(vendors, mac, opts)));
+    devices
+}
+
+fn json_device(device: &Host, opts: &Options) -> String {
+    let strings = |items: Vec<String>| {
+        let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+        format!("[{}]", items.join(", "))
+    };
+    let names: Vec<String> = device.names.iter()
+        .map(|(source, name)| format!("{}: {}", json_string(&source.to_string()), json_string(name)))
+        .collect();
+    let fields = [
+        ("mac", json_string(&display_mac(&device.mac, opts))),
+        ("vendor", device.vendor.as_deref().map_or("null".to_string(), json_string)),
+        ("name", device.name().map_or("null".to_string(), json_string)),
+        ("names", format!("{{{}}}", names.join(", "))),
+        ("ipv4", strings(device.ipv4.iter().map(Ipv4Addr::to_string).collect())),
+        ("ipv6", strings(device.ipv6.iter().map(|ip| ip.to_string()).collect())),
+        ("labels", strings(device.labels.iter().cloned().collect())),
+        ("interfaces", strings(device.interfaces.iter().cloned().collect())),
+        ("first_seen", json_string(&format_timestamp(device.first_seen))),
+        ("last_seen", json_string(&format_timestamp(device.last_seen))),
+    ];
+    let fields: Vec<String> = fields.iter()
+        .map(|(key, value)| format!("{}: {}", json_string(key), value))
+        .collect();
+    format!("    {{{}}}", fields.join(", "))
+}
+
+pub fn print_json(meta: &ScanMeta, scans: &[ScanResult], blocks: &[TargetBlock], extras: &Enrichment, vendors: &dyn VendorDb, opts: &Options) {
+    let hosts: Vec<String> = sorted(scans).iter()
+        .map(|(scan, reply)| json_host(scans, scan, reply, extras, vendors, opts))
+        .collect();
+    let devices: Vec<String> = scan_devices(scans, vendors, opts).iter()
+        .map(|device| json_device(device, opts))
+        .collect();
+
+    println!("{{");
+    println!("  \"schema_version\": {},", SCHEMA_VERSION);
+    println!("  \"scan\": {},", json_meta(meta, opts));
+    println!("  \"stats\": {},", json_stats(scans));
+    if opts.summary {
+        println!("  \"summary\": {},", json_summary(&scan_summary(scans, vendors, opts)));
+    }
+    if !blocks.is_empty() {
+        let counts: Vec<String> = summary::by_block(scans, blocks).iter()
+            .map(|block| format!(
+                "    {{\"target\": {}, \"probed\": {}, \"hosts\": {}}}",
+                json_string(&block.label),
+                block.probed,
+                block.hosts
+            ))
+            .collect();
+        println!("  \"blocks\": [\n{}\n  ],", counts.join(",\n"));
+    }
+    if hosts.is_empty() {
+        println!("  \"hosts\": [],");
+        println!("  \"devices\": []");
+    } else {
+        println!("  \"hosts\": [\n{}\n  ],", hosts.join(",\n"));
+        println!("  \"devices\": [\n{}\n  ]", devices.join(",\n"));
+    }
+    println!("}}");
+}
+
+// Pipes would end the cell and newlines the row.
+fn markdown_cell(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+pub fn print_markdown(meta: &ScanMeta, scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) {
+    let summary = scan_summary(scans, vendors, opts);
+    let replies = sorted(scans);
+    let show_names = replies.iter().any(|(_, reply)| reply.hostname.is_some());
+    let show_ports = replies.iter().any(|(_, reply)| reply.switch_port.is_some());
+    let show_interfaces = scans.len() > 1;
+
+    println!("## ARP scan of {}", markdown_cell(&meta.targets.join(", ")));
+    println!();
+    println!(
+        "Scanned from {} ({}) on {}, {} to {}.",
+        meta.source_ip,
+        markdown_cell(&display_mac(&meta.source_mac, opts)),
+        markdown_cell(&meta.interface),
+        format_timestamp(meta.started),
+        format_timestamp(meta.finished)
+    );
+    println!();
+
+    let mut header = vec!["IP Address", "MAC Address", "Manufacturer"];
+    if show_names {
+        header.push("Name");
+    }
+    if show_ports {
+        header.push("Switch port");
+    }
+    if show_interfaces {
+        header.push("Interface");
+    }
+    header.extend(["RTT (ms)", "Warnings"]);
+    println!("| {} |", header.join(" | "));
+    let rule: Vec<&str> = header.iter().map(|column| if *column == "RTT (ms)" { "---:" } else { "---" }).collect();
+    println!("| {} |", rule.join(" | "));
+    for (scan, reply) in &replies {
+        let mut row = vec![
+            display_ip(reply.ip, opts),
+            markdown_cell(&display_mac(&reply.mac, opts)),
+            markdown_cell(&display_vendor(vendors, &reply.mac, opts)),
+        ];
+        if show_names {
+            row.push(markdown_cell(reply.hostname.as_deref().unwrap_or("")));
+        }
+        if show_ports {
+            row.push(markdown_cell(&reply.switch_port.as_ref().map(SwitchPort::to_string).unwrap_or_default()));
+        }
+        if show_interfaces {
+            row.push(markdown_cell(&reply.interface));
+        }
+        row.push(display_rtt(reply));
+        row.push(markdown_cell(&warnings(scans, scan, reply, opts).join("; ")));
+        println!("| {} |", row.join(" | "));
+    }
+
+    println!();
+    println!("### Summary");
+    println!();
+    match summary.response_rate() {
+        Some(rate) => println!("- **{}** hosts answered out of {} addresses probed ({:.1}%)", summary.hosts, summary.probed, rate),
+        None => println!("- **{}** hosts answered", summary.hosts),
+    }
+    println!("- {} have a note", summary.noted);
+    println!("- {} use randomized (locally administered) MACs", summary.randomized);
+    if summary.rounds > 1 {
+        println!("- {} answered all {} rounds, {} only some", summary.steady, summary.rounds, summary.hosts - summary.steady);
+    }
+    if let Some(stats) = latency_stats(scans) {
+        println!("- Reply latency p50 {} ms, p90 {} ms, p99 {} ms", fmt_ms(stats.p50), fmt_ms(stats.p90), fmt_ms(stats.p99));
+    }
+    if !summary.by_vendor.is_empty() {
+        println!();
+        println!("| Manufacturer | Hosts |");
+        println!("| --- | ---: |");
+        for (vendor, count) in &summary.by_vendor {
+            println!("| {} | {} |", markdown_cell(vendor), count);
+        }
+    }
+}
+
+const HTML_TEMPLATE: &str = include_str!("files/report.html");
+
+fn html_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// One labelled bar chart; bars are sized relative to the largest count.
+fn html_chart(title: &str, bars: &[(String, usize)]) -> String {
+    let widest = bars.iter().map(|(_, count)| *count).max().unwrap_or(1).max(1);
+    let mut chart = format!("<div class=\"chart\">\n<h3>{}</h3>\n", html_escape(title));
+    for (label, count) in bars {
+        chart.push_str(&format!(
+            "<div class=\"bar-row\"><span class=\"bar-label\" title=\"{0}\">{0}</span><div class=\"bar\" style=\"width: {1}%\"></div><span class=\"bar-count\">{2}</span></div>\n",
+            html_escape(label),
+            count * 100 / widest,
+            count
+        ));
+    }
+    chart.push_str("</div>");
+    chart
+}
+
+// A standalone report: metadata, overview charts and a sortable host
+// table, with its styles and script inline so the file can be attached to
+// a ticket and opened anywhere.
+pub fn write_html(path: &Path, meta: &ScanMeta, scans: &[ScanResult], vendors: &dyn VendorDb, opts: &Options) -> Result<(), String> {
+    let summary = scan_summary(scans, vendors, opts);
+    let title = format!("ARP scan of {}", meta.targets.join(", "));
+    let subtitle = match summary.response_rate() {
+        Some(rate) => format!("{} hosts answered out of {} addresses probed ({:.1}%)", summary.hosts, summary.probed, rate),
+        None => format!("{} hosts answered", summary.hosts),
+    };
+
+    let mut fields = vec![
+        ("Scan ID", meta.scan_id.clone()),
+        ("Started", format_timestamp(meta.started)),
+        ("Finished", format_timestamp(meta.finished)),
+        ("Interface", meta.interface.clone()),
+        ("Source", format!("{} ({})", meta.source_ip, display_mac(&meta.source_mac, opts))),
+        ("Targets", meta.targets.join(" ")),
+    ];
+    for (key, value) in &meta.settings {
+        fields.push(("Setting", format!("{}={}", key, value)));
+    }
+    let meta_html: Vec<String> = fields.iter()
+        .map(|(key, value)| format!("<dt>{}</dt><dd>{}</dd>", key, html_escape(value)))
+        .collect();
+
+    let mut charts = vec![
+        html_chart("Hosts per vendor", &summary.by_vendor),
+        html_chart("Hosts per /24", &summary.by_subnet.iter().map(|(subnet, count)| (subnet.to_string(), *count)).collect::<Vec<_>>()),
+    ];
+    if let Some(stats) = latency_stats(scans) {
+        let first = stats.histogram.iter().position(|n| *n > 0).unwrap_or(0);
+        let last = stats.histogram.iter().rposition(|n| *n > 0).unwrap_or(0);
+        let buckets: Vec<(String, usize)> = (first..=last).map(|i| (LatencyStats::bucket_label(i), stats.histogram[i])).collect();
+        charts.push(html_chart(&format!("Reply latency (p50 {} ms, p90 {} ms)", fmt_ms(stats.p50), fmt_ms(stats.p90)), &buckets));
+    }
+    charts.push(html_chart("MAC addresses", &[
+        ("Global (vendor assigned)".to_string(), summary.hosts - summary.randomized),
+        ("Randomized or virtual".to_string(), summary.randomized),
+    ]));
+
+    let rows: Vec<String> = sorted(scans).iter()
+        .map(|(scan, reply)| {
+            let warnings = warnings(scans, scan, reply, opts);
+            format!(
+                "<tr{}><td class=\"mono\" data-sort=\"{}\">{}</td><td class=\"mono\">{}</td><td>{}</td><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                if warnings.is_empty() { "" } else { " class=\"warn\"" },
+                u32::from(reply.ip),
+                reply.ip,
+                html_escape(&display_mac(&reply.mac, opts)),
+                html_escape(&display_vendor(vendors, &reply.mac, opts)),
+                html_escape(reply.hostname.as_deref().unwrap_or("")),
+                display_rtt(reply),
+                responses(scan, reply),
+                html_escape(&reply.switch_port.as_ref().map(SwitchPort::to_string).unwrap_or_default()),
+                html_escape(&reply.interface),
+                html_escape(&warnings.join("; "))
+            )
+        })
+        .collect();
+
+    let html = HTML_TEMPLATE
+        .replace("{{TITLE}}", &html_escape(&title))
+        .replace("{{SUBTITLE}}", &html_escape(&subtitle))
+        .replace("{{VERSION}}", &html_escape(TOOL_VERSION))
+        .replace("{{META}}", &meta_html.join("\n"))
+        .replace("{{CHARTS}}", &charts.join("\n"))
+        .replace("{{ROWS}}", &rows.join("\n"));
+    fs::write(path, html).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn print_csv(meta: &ScanMeta, scans: &[ScanResult], extras: &Enrichment, vendors: &dyn VendorDb, opts: &Options) {
+    // Metadata goes in leading comment lines; the table itself stays plain CSV.
+    println!("# schema_version={}", SCHEMA_VERSION);
+    println!("# scan_id={}", meta.scan_id);
+    println!("# started={}", format_timestamp(meta.started));
+    println!("# finished={}", format_timestamp(meta.finished));
+    println!("# tool_version={}", TOOL_VERSION);
+    println!("# interface={}", meta.interface);
+    println!("# source_ip={}", meta.source_ip);
+    println!("# source_mac={}", display_mac(&meta.source_mac, opts));
+    println!("# targets={}", meta.targets.join(" "));
+    for (key, value) in &meta.settings {
+        println!("# setting.{}={}", key, value);
+    }
+    if let Some(stats) = latency_stats(scans) {
+        println!("# latency_p50_ms={}", fmt_ms(stats.p50));
+        println!("# latency_p90_ms={}", fmt_ms(stats.p90));
+        println!("# latency_p99_ms={}", fmt_ms(stats.p99));
+    }
+    // Enrichment fields become extra.<key> columns, in first-seen order.
+    let mut extra_keys: Vec<&str> = Vec::new();
+    for (_, reply) in sorted(scans) {
+        for (key, _) in extra_fields(extras, reply) {
+            if !extra_keys.contains(&key.as_str()) {
+                extra_keys.push(key);
+            }
+        }
+    }
+    let mut header = "ip,mac,vendor,rtt_ms,eth_source,eth_source_mismatch,frame_len,padding_nonzero,responses,interface,also_seen_on,hostname,note,country,switch,switch_port,switch_vlan,duplicates,duplicate_macs,attempt,roamed_from".to_string();
+    for key in &extra_keys {
+        header.push_str(&format!(",{}", csv_field(&format!("extra.{}", key))));
+    }
+    println!("{}", header);
+    for (scan, reply) in sorted(scans) {
+        let fields = extra_fields(extras, reply);
+        let extra: String = extra_keys.iter()
+            .map(|key| {
+                let value = fields.iter().find(|(k, _)| k == key).map(|(_, v)| enrich::display_value(v));
+                format!(",{}", csv_field(&value.unwrap_or_default()))
+            })
+            .collect();
+        println!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}{}",
+            reply.ip,
+            csv_field(&display_mac(&reply.mac, opts)),
+            csv_field(&display_vendor(vendors, &reply.mac, opts)),
+            reply.rtt.map(fmt_ms).unwrap_or_default(),
+            csv_field(&display_mac(&reply.eth_source, opts)),
+            reply.eth_source_mismatch(),
+            reply.frame_len,
+            reply.padding_nonzero(),
+            responses(scan, reply),
+            csv_field(&reply.interface),
+            csv_field(&also_seen_on(scans, reply).join(" ")),
+            csv_field(reply.hostname.as_deref().unwrap_or("")),
+            csv_field(reply.note.as_deref().unwrap_or("")),
+            display_country(vendors, &reply.mac, opts).unwrap_or_default(),
+            csv_field(reply.switch_port.as_ref().and_then(|port| port.switch.as_deref()).unwrap_or("")),
+            csv_field(reply.switch_port.as_ref().map_or("", |port| port.port.as_str())),
+            reply.switch_port.as_ref().and_then(|port| port.vlan).map(|vlan| vlan.to_string()).unwrap_or_default(),
+            reply.duplicates,
+            csv_field(&reply.duplicate_macs.iter().map(|mac| display_mac(mac, opts)).collect::<Vec<_>>().join(" ")),
+            reply.attempt.map(|attempt| attempt.to_string()).unwrap_or_default(),
+            csv_field(&reply.roamed_from.iter().map(|roam| format!("{}@{}", roam.ip, roam.interface)).collect::<Vec<_>>().join(" ")),
+            extra
+        );
+    }
+}
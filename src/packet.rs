@@ -0,0 +1,475 @@
+// Ethernet/ARP frame construction. This module deliberately sticks to `core`:
+// it never allocates and does no I/O, so it can be lifted into no_std
+// projects (e.g. building probes for smoltcp on a microcontroller). Addresses
+// are plain byte arrays for the same reason.
+
+use core::fmt;
+use core::net::Ipv4Addr;
+
+pub const ETHERNET_HEADER_LEN: usize = 14;
+pub const ARP_PACKET_LEN: usize = 28;
+// Ethernet header plus ARP payload, before any padding
+pub const ARP_FRAME_LEN: usize = ETHERNET_HEADER_LEN + ARP_PACKET_LEN;
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+// 802.1Q customer tag, and the 802.1ad service tag used as the outer tag
+// in QinQ. 0x9100 is the pre-standard QinQ tag some switches still use.
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+pub const ETHERTYPE_QINQ: u16 = 0x88A8;
+pub const ETHERTYPE_QINQ_LEGACY: u16 = 0x9100;
+pub const VLAN_TAG_LEN: usize = 4;
+pub const HARDWARE_ETHERNET: u16 = 1;
+
+pub const ARP_REQUEST: u16 = 1;
+pub const ARP_REPLY: u16 = 2;
+
+// Largest frame we will pad to; covers the usual 9000-byte jumbo MTU
+pub const MAX_FRAME_LEN: usize = 9216;
+
+pub const BROADCAST_MAC: [u8; 6] = [0xff; 6];
+pub const ZERO_MAC: [u8; 6] = [0; 6];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    // The buffer can't hold the frame; carries the length that was needed.
+    BufferTooSmall(usize),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::BufferTooSmall(needed) => write!(f, "buffer too small for ARP frame ({} bytes needed)", needed),
+        }
+    }
+}
+
+// Everything needed to build a single Ethernet+ARP frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArpFrame {
+    pub eth_destination: [u8; 6],
+    pub eth_source: [u8; 6],
+    pub operation: u16,
+    pub sender_mac: [u8; 6],
+    pub sender_ip: Ipv4Addr,
+    pub target_mac: [u8; 6],
+    pub target_ip: Ipv4Addr,
+}
+
+impl ArpFrame {
+    // A standard who-has request broadcast to the whole segment.
+    pub fn request(source_mac: [u8; 6], source_ip: Ipv4Addr, target_ip: Ipv4Addr) -> Self {
+        ArpFrame {
+            eth_destination: BROADCAST_MAC,
+            eth_source: source_mac,
+            operation: ARP_REQUEST,
+            sender_mac: source_mac,
+            sender_ip: source_ip,
+            target_mac: ZERO_MAC,
+            target_ip,
+        }
+    }
+
+    // A request sent straight to a host we believe owns `target_ip`, rather
+    // than broadcast. Only that host sees it, so a reply confirms the
+    // binding without disturbing the rest of the segment.
+    pub fn unicast_request(source_mac: [u8; 6], source_ip: Ipv4Addr, target_ip: Ipv4Addr, target_mac: [u8; 6]) -> Self {
+        ArpFrame {
+            eth_destination: target_mac,
+            target_mac,
+            ..Self::request(source_mac, source_ip, target_ip)
+        }
+    }
+
+    // An ARP announcement (RFC 5227): a request for our own address, which
+    // every host on the segment takes as the current binding.
+    pub fn announcement(source_mac: [u8; 6], ip: Ipv4Addr) -> Self {
+        Self::request(source_mac, ip, ip)
+    }
+
+    // Write the frame into `buf` and return the number of bytes used.
+    pub fn write(&self, buf: &mut [u8]) -> Result<usize, BuildError> {
+        if buf.len() < ARP_FRAME_LEN {
+            return Err(BuildError::BufferTooSmall(ARP_FRAME_LEN));
+        }
+
+        buf[0..6].copy_from_slice(&self.eth_destination);
+        buf[6..12].copy_from_slice(&self.eth_source);
+        buf[12..14].copy_from_slice(&ETHERTYPE_ARP.to_be_bytes());
+
+        let arp = &mut buf[ETHERNET_HEADER_LEN..ARP_FRAME_LEN];
+        arp[0..2].copy_from_slice(&HARDWARE_ETHERNET.to_be_bytes());
+        arp[2..4].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        arp[4] = 6;
+        arp[5] = 4;
+        arp[6..8].copy_from_slice(&self.operation.to_be_bytes());
+        arp[8..14].copy_from_slice(&self.sender_mac);
+        arp[14..18].copy_from_slice(&self.sender_ip.octets());
+        arp[18..24].copy_from_slice(&self.target_mac);
+        arp[24..28].copy_from_slice(&self.target_ip.octets());
+
+        Ok(ARP_FRAME_LEN)
+    }
+
+    // Like `write`, but zero-fill the frame out to `frame_len` bytes. Useful
+    // for exercising switches and drivers that mishandle small or odd-sized
+    // frames; lengths below the bare ARP frame are treated as no padding.
+    pub fn write_padded(&self, buf: &mut [u8], frame_len: usize) -> Result<usize, BuildError> {
+        let len = frame_len.max(ARP_FRAME_LEN);
+        if buf.len() < len {
+            return Err(BuildError::BufferTooSmall(len));
+        }
+        self.write(buf)?;
+        buf[ARP_FRAME_LEN..len].fill(0);
+        Ok(len)
+    }
+}
+
+// VLAN tags between the MAC addresses and the EtherType: none, a single
+// 802.1Q tag, or an 802.1ad outer tag around an 802.1Q inner one (QinQ).
+// IDs are the 12-bit VLAN ID; priority and DEI bits are left at zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Vlan {
+    #[default]
+    Untagged,
+    Single(u16),
+    QinQ { outer: u16, inner: u16 },
+}
+
+impl Vlan {
+    // Bytes the tags add to a frame.
+    pub const fn len(&self) -> usize {
+        match self {
+            Vlan::Untagged => 0,
+            Vlan::Single(_) => VLAN_TAG_LEN,
+            Vlan::QinQ { .. } => 2 * VLAN_TAG_LEN,
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        matches!(self, Vlan::Untagged)
+    }
+
+    // Whether a frame that arrived carrying `seen` can belong to this VLAN.
+    // Drivers often strip the outer tag before a capture sees the frame,
+    // so fewer tags than expected is fine as long as the inner ones agree.
+    pub fn accepts(&self, seen: Vlan) -> bool {
+        match (*self, seen) {
+            (_, Vlan::Untagged) => true,
+            (Vlan::QinQ { inner, .. }, Vlan::Single(id)) => inner == id,
+            (expected, seen) => expected == seen,
+        }
+    }
+
+    // Insert the tags into the untagged frame in `buf[..len]`, moving
+    // everything after the MAC addresses along. Returns the new length.
+    pub fn tag(&self, buf: &mut [u8], len: usize) -> Result<usize, BuildError> {
+        let tagged = len + self.len();
+        if buf.len() < tagged {
+            return Err(BuildError::BufferTooSmall(tagged));
+        }
+        let mut tags = [0u8; 2 * VLAN_TAG_LEN];
+        let mut write_tag = |at: usize, tpid: u16, id: u16| {
+            tags[at..at + 2].copy_from_slice(&tpid.to_be_bytes());
+            tags[at + 2..at + 4].copy_from_slice(&(id & 0x0fff).to_be_bytes());
+        };
+        match *self {
+            Vlan::Untagged => return Ok(len),
+            Vlan::Single(id) => write_tag(0, ETHERTYPE_VLAN, id),
+            Vlan::QinQ { outer, inner } => {
+                write_tag(0, ETHERTYPE_QINQ, outer);
+                write_tag(VLAN_TAG_LEN, ETHERTYPE_VLAN, inner);
+            }
+        }
+        buf.copy_within(12..len, 12 + self.len());
+        buf[12..12 + self.len()].copy_from_slice(&tags[..self.len()]);
+        Ok(tagged)
+    }
+}
+
+impl fmt::Display for Vlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Vlan::Untagged => f.write_str("untagged"),
+            Vlan::Single(id) => write!(f, "{}", id),
+            Vlan::QinQ { outer, inner } => write!(f, "{}:{}", outer, inner),
+        }
+    }
+}
+
+// The fixed part of an ARP packet for Ethernet/IPv4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArpHeader {
+    pub hardware_type: u16,
+    pub protocol_type: u16,
+    pub hw_addr_len: u8,
+    pub proto_addr_len: u8,
+    pub operation: u16,
+    pub sender_mac: [u8; 6],
+    pub sender_ip: Ipv4Addr,
+    pub target_mac: [u8; 6],
+    pub target_ip: Ipv4Addr,
+}
+
+impl ArpHeader {
+    // Decode the fields as they are, without checking they make sense.
+    // The address fields are read at their Ethernet/IPv4 offsets whatever
+    // the length fields say.
+    pub fn parse(arp: &[u8]) -> Option<Self> {
+        Some(ArpHeader {
+            hardware_type: u16_at(arp, 0)?,
+            protocol_type: u16_at(arp, 2)?,
+            hw_addr_len: *arp.get(4)?,
+            proto_addr_len: *arp.get(5)?,
+            operation: u16_at(arp, 6)?,
+            sender_mac: mac_at(arp, 8)?,
+            sender_ip: ipv4_at(arp, 14)?,
+            target_mac: mac_at(arp, 18)?,
+            target_ip: ipv4_at(arp, 24)?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; ARP_PACKET_LEN] {
+        let mut arp = [0u8; ARP_PACKET_LEN];
+        arp[0..2].copy_from_slice(&self.hardware_type.to_be_bytes());
+        arp[2..4].copy_from_slice(&self.protocol_type.to_be_bytes());
+        arp[4] = self.hw_addr_len;
+        arp[5] = self.proto_addr_len;
+        arp[6..8].copy_from_slice(&self.operation.to_be_bytes());
+        arp[8..14].copy_from_slice(&self.sender_mac);
+        arp[14..18].copy_from_slice(&self.sender_ip.octets());
+        arp[18..24].copy_from_slice(&self.target_mac);
+        arp[24..28].copy_from_slice(&self.target_ip.octets());
+        arp
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParsedArpFrame {
+    pub eth_destination: [u8; 6],
+    pub eth_source: [u8; 6],
+    // Tags the frame carried. Many drivers strip the outer tag before a
+    // capture sees it, so an untagged frame may still have been tagged on
+    // the wire.
+    pub vlan: Vlan,
+    pub arp: ArpHeader,
+}
+
+impl ParsedArpFrame {
+    // Where the ARP payload ends and any padding begins.
+    pub fn payload_end(&self) -> usize {
+        ARP_FRAME_LEN + self.vlan.len()
+    }
+}
+
+// Received frames come from whoever is on the segment, so every read is
+// bounds-checked and a short buffer is None, never a panic.
+fn mac_at(buf: &[u8], offset: usize) -> Option<[u8; 6]> {
+    buf.get(offset..offset.checked_add(6)?)?.try_into().ok()
+}
+
+fn ipv4_at(buf: &[u8], offset: usize) -> Option<Ipv4Addr> {
+    let octets: [u8; 4] = buf.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+    Some(Ipv4Addr::from(octets))
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*buf.get(offset)?, *buf.get(offset.checked_add(1)?)?]))
+}
+
+// Decode an Ethernet frame carrying Ethernet/IPv4 ARP, untagged or behind
+// one or two VLAN tags. Returns None for anything else: truncated frames,
+// deeper tag stacks, and ARP for other hardware or protocol address sizes.
+// Trailing bytes are padding and are left for the caller to inspect.
+pub fn parse_arp_frame(frame: &[u8]) -> Option<ParsedArpFrame> {
+    let is_tag = |ethertype| matches!(ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ | ETHERTYPE_QINQ_LEGACY);
+    let tag_id = |offset: usize| u16_at(frame, offset + 2).map(|tci| tci & 0x0fff);
+    let first = u16_at(frame, 12)?;
+    let vlan = if !is_tag(first) {
+        Vlan::Untagged
+    } else if !is_tag(u16_at(frame, 16)?) {
+        Vlan::Single(tag_id(12)?)
+    } else {
+        Vlan::QinQ { outer: tag_id(12)?, inner: tag_id(16)? }
+    };
+    let header_len = ETHERNET_HEADER_LEN + vlan.len();
+    if frame.len() < header_len + ARP_PACKET_LEN || u16_at(frame, header_len - 2)? != ETHERTYPE_ARP {
+        return None;
+    }
+
+    let header = ArpHeader::parse(frame.get(header_len..)?)?;
+    if header.hw_addr_len != 6 || header.proto_addr_len != 4 || header.protocol_type != ETHERTYPE_IPV4 {
+        return None;
+    }
+
+    Some(ParsedArpFrame {
+        eth_destination: mac_at(frame, 0)?,
+        eth_source: mac_at(frame, 6)?,
+        vlan,
+        arp: header,
+    })
+}
+
+pub const IPV4_HEADER_LEN: usize = 20;
+pub const ICMP_ECHO_LEN: usize = 8;
+pub const ICMP_ECHO_FRAME_LEN: usize = ETHERNET_HEADER_LEN + IPV4_HEADER_LEN + ICMP_ECHO_LEN;
+
+// RFC 1071 Internet checksum.
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    for chunk in data.chunks(2) {
+        let word = match chunk {
+            [hi, lo] => u16::from_be_bytes([*hi, *lo]),
+            [hi] => u16::from_be_bytes([*hi, 0]),
+            _ => 0,
+        };
+        sum += word as u32;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// An ICMP echo request sent to an Ethernet broadcast. Hosts that answer a
+// broadcast ping have to ARP for us first, which reveals them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IcmpEchoFrame {
+    pub eth_source: [u8; 6],
+    pub source_ip: Ipv4Addr,
+    pub destination_ip: Ipv4Addr,
+    pub identifier: u16,
+    pub sequence: u16,
+}
+
+impl IcmpEchoFrame {
+    pub fn write(&self, buf: &mut [u8]) -> Result<usize, BuildError> {
+        if buf.len() < ICMP_ECHO_FRAME_LEN {
+            return Err(BuildError::BufferTooSmall(ICMP_ECHO_FRAME_LEN));
+        }
+
+        buf[0..6].copy_from_slice(&BROADCAST_MAC);
+        buf[6..12].copy_from_slice(&self.eth_source);
+        buf[12..14].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+        let ip = &mut buf[ETHERNET_HEADER_LEN..ETHERNET_HEADER_LEN + IPV4_HEADER_LEN];
+        ip[0] = 0x45; // version 4, 20-byte header
+        ip[1] = 0;
+        ip[2..4].copy_from_slice(&((IPV4_HEADER_LEN + ICMP_ECHO_LEN) as u16).to_be_bytes());
+        ip[4..6].copy_from_slice(&self.identifier.to_be_bytes());
+        ip[6..8].copy_from_slice(&0u16.to_be_bytes());
+        ip[8] = 64; // TTL
+        ip[9] = 1; // ICMP
+        ip[10..12].copy_from_slice(&0u16.to_be_bytes());
+        ip[12..16].copy_from_slice(&self.source_ip.octets());
+        ip[16..20].copy_from_slice(&self.destination_ip.octets());
+        let checksum = internet_checksum(ip);
+        ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+        let icmp = &mut buf[ETHERNET_HEADER_LEN + IPV4_HEADER_LEN..ICMP_ECHO_FRAME_LEN];
+        icmp[0] = 8; // echo request
+        icmp[1] = 0;
+        icmp[2..4].copy_from_slice(&0u16.to_be_bytes());
+        icmp[4..6].copy_from_slice(&self.identifier.to_be_bytes());
+        icmp[6..8].copy_from_slice(&self.sequence.to_be_bytes());
+        let checksum = internet_checksum(icmp);
+        icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+        Ok(ICMP_ECHO_FRAME_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    fn reply_frame(vlan: Vlan) -> Vec<u8> {
+        let reply = ArpFrame {
+            eth_destination: BROADCAST_MAC,
+            eth_source: SENDER,
+            operation: ARP_REPLY,
+            sender_mac: SENDER,
+            sender_ip: Ipv4Addr::new(10, 0, 0, 7),
+            target_mac: ZERO_MAC,
+            target_ip: Ipv4Addr::new(10, 0, 0, 1),
+        };
+        let mut buf = [0u8; 64];
+        let len = reply.write(&mut buf).unwrap();
+        let len = vlan.tag(&mut buf, len).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn parses_tagged_and_untagged_replies() {
+        for vlan in [Vlan::Untagged, Vlan::Single(100), Vlan::QinQ { outer: 200, inner: 100 }] {
+            let parsed = parse_arp_frame(&reply_frame(vlan)).unwrap();
+            assert_eq!(parsed.vlan, vlan);
+            assert_eq!(parsed.arp.sender_mac, SENDER);
+            assert_eq!(parsed.arp.sender_ip, Ipv4Addr::new(10, 0, 0, 7));
+            assert_eq!(parsed.payload_end(), ARP_FRAME_LEN + vlan.len());
+        }
+    }
+
+    #[test]
+    fn rejects_every_truncation() {
+        for vlan in [Vlan::Untagged, Vlan::Single(100), Vlan::QinQ { outer: 200, inner: 100 }] {
+            let frame = reply_frame(vlan);
+            for len in 0..frame.len() {
+                assert_eq!(parse_arp_frame(&frame[..len]), None, "{} of {} bytes", len, frame.len());
+            }
+        }
+        assert_eq!(ArpHeader::parse(&[0u8; ARP_PACKET_LEN - 1]), None);
+    }
+
+    #[test]
+    fn rejects_other_address_sizes_and_protocols() {
+        let mut frame = reply_frame(Vlan::Untagged);
+        frame.resize(80, 0);
+        // Hardware address length, protocol address length, protocol type
+        for (offset, value) in [(18, 8u8), (19, 16), (19, 0), (16, 0x86)] {
+            let mut odd = frame.clone();
+            odd[offset] = value;
+            assert_eq!(parse_arp_frame(&odd), None, "byte {} = {}", offset, value);
+        }
+        // Not ARP behind the tag, and one tag too many
+        let mut ipv4 = reply_frame(Vlan::Single(5));
+        ipv4[16..18].copy_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        assert_eq!(parse_arp_frame(&ipv4), None);
+        let mut stacked = reply_frame(Vlan::QinQ { outer: 1, inner: 2 });
+        stacked.splice(20..20, [0x81, 0x00, 0x00, 0x03]);
+        assert_eq!(parse_arp_frame(&stacked), None);
+    }
+
+    #[test]
+    fn survives_garbage() {
+        // xorshift, so the frames are the same on every run
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let template = reply_frame(Vlan::Single(7));
+        for _ in 0..20_000 {
+            let len = (next() % 96) as usize;
+            let mut frame: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            // Half of them start out as a plausible ARP frame, so the parser
+            // gets past the EtherType check with damage further in
+            if next() % 2 == 0 {
+                let keep = len.min(template.len());
+                frame[..keep].copy_from_slice(&template[..keep]);
+                if len > 0 {
+                    let at = (next() % len as u64) as usize;
+                    frame[at] = next() as u8;
+                }
+            }
+            if let Some(parsed) = parse_arp_frame(&frame) {
+                assert!(parsed.payload_end() <= frame.len());
+                assert_eq!(parsed.arp.hw_addr_len, 6);
+            }
+        }
+    }
+}
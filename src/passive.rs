@@ -0,0 +1,413 @@
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "engine")]
+use std::net::IpAddr;
+use std::net::{Ipv4Addr, Ipv6Addr};
+#[cfg(feature = "engine")]
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+use pnet_base::MacAddr;
+
+#[cfg(feature = "engine")]
+use crate::packet::{parse_arp_frame, ARP_REPLY, ARP_REQUEST};
+use crate::dhcp::DhcpClient;
+#[cfg(feature = "engine")]
+use crate::dhcp;
+#[cfg(feature = "engine")]
+use crate::ndp::{self, NdpSighting};
+use crate::neighbor::Neighbor;
+#[cfg(feature = "engine")]
+use crate::neighbor::{self, NeighborProtocol};
+use crate::scan::{ChannelTuning, ReplyKind};
+use crate::target::CidrV4;
+#[cfg(feature = "engine")]
+use crate::scan::{is_arp_frame, open_channel, resolve_interface, spawn_receiver};
+
+// ARP requests per second above which a sender, or the segment as a whole,
+// is reported as a possible storm or someone else scanning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StormThresholds {
+    pub per_source: u32,
+    pub total: u32,
+}
+
+impl Default for StormThresholds {
+    fn default() -> Self {
+        StormThresholds {
+            per_source: 50,
+            total: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StormAlert {
+    // The offending sender, or None when only the total rate is too high
+    pub source: Option<MacAddr>,
+    pub requests: usize,
+    pub window: Duration,
+    pub at: SystemTime,
+}
+
+// Counts ARP requests over a sliding one-second window. Each source (and
+// the total) alerts at most once per cooldown so a sustained flood yields a
+// handful of alerts rather than one per packet.
+#[derive(Debug)]
+pub struct StormDetector {
+    thresholds: StormThresholds,
+    window: Duration,
+    cooldown: Duration,
+    recent: VecDeque<(Instant, MacAddr)>,
+    per_source: HashMap<MacAddr, usize>,
+    last_alert: HashMap<Option<MacAddr>, Instant>,
+}
+
+impl StormDetector {
+    pub fn new(thresholds: StormThresholds) -> Self {
+        StormDetector {
+            thresholds,
+            window: Duration::from_secs(1),
+            cooldown: Duration::from_secs(10),
+            recent: VecDeque::new(),
+            per_source: HashMap::new(),
+            last_alert: HashMap::new(),
+        }
+    }
+
+    // Record one ARP request and return an alert if it pushed a rate over
+    // its threshold.
+    pub fn observe(&mut self, at: Instant, source: MacAddr) -> Option<StormAlert> {
+        while let Some((seen, mac)) = self.recent.front().copied() {
+            if at.saturating_duration_since(seen) < self.window {
+                break;
+            }
+            self.recent.pop_front();
+            if let Some(count) = self.per_source.get_mut(&mac) {
+                *count -= 1;
+                if *count == 0 {
+                    self.per_source.remove(&mac);
+                }
+            }
+        }
+        self.recent.push_back((at, source));
+        let from_source = self.per_source.entry(source).or_insert(0);
+        *from_source += 1;
+        let from_source = *from_source;
+
+        if from_source > self.thresholds.per_source as usize {
+            self.alert(at, Some(source), from_source)
+        } else if self.recent.len() > self.thresholds.total as usize {
+            self.alert(at, None, self.recent.len())
+        } else {
+            None
+        }
+    }
+
+    fn alert(&mut self, at: Instant, source: Option<MacAddr>, requests: usize) -> Option<StormAlert> {
+        if let Some(last) = self.last_alert.get(&source) {
+            if at.saturating_duration_since(*last) < self.cooldown {
+                return None;
+            }
+        }
+        self.last_alert.insert(source, at);
+        Some(StormAlert {
+            source,
+            requests,
+            window: self.window,
+            at: SystemTime::now(),
+        })
+    }
+}
+
+// The subnets a segment is expected to carry. A host using an address
+// outside all of them is misconfigured (an old static address, say) or
+// bridged in from elsewhere. Link-local addresses (RFC 3927) belong
+// everywhere.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalSubnets {
+    subnets: Vec<CidrV4>,
+}
+
+impl LocalSubnets {
+    pub fn new(subnets: Vec<CidrV4>) -> Self {
+        LocalSubnets { subnets }
+    }
+
+    pub fn subnets(&self) -> &[CidrV4] {
+        &self.subnets
+    }
+
+    // Without any subnets to go on, nothing counts as off-subnet.
+    pub fn is_off_subnet(&self, ip: Ipv4Addr) -> bool {
+        !self.subnets.is_empty() && !ip.is_link_local() && !self.subnets.iter().any(|subnet| subnet.contains(ip))
+    }
+}
+
+#[cfg(feature = "engine")]
+const STOP_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Default)]
+pub struct PassiveConfig {
+    pub interface: Option<String>,
+    // Stop after this long; listen until interrupted when None
+    pub duration: Option<Duration>,
+    pub storm: StormThresholds,
+    pub channel: ChannelTuning,
+    // Subnets that belong on the segment besides the interface's own
+    pub subnets: Vec<CidrV4>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassiveHost {
+    pub ip: Ipv4Addr,
+    pub mac: MacAddr,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+    pub packets: u64,
+    // The address is outside every local subnet
+    pub off_subnet: bool,
+}
+
+// An IPv6 address learned from Neighbor Discovery.
+#[derive(Debug, Clone)]
+pub struct PassiveHost6 {
+    pub ip: Ipv6Addr,
+    pub mac: MacAddr,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+    pub packets: u64,
+    // It has sent a router advertisement or flagged itself as a router
+    pub router: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum PassiveEvent {
+    NewHost(PassiveHost, ReplyKind),
+    // An IPv6 address seen for the first time in NDP traffic
+    NewHost6(PassiveHost6),
+    // Every sighting, including the first; lets callers track bindings
+    Seen(Ipv4Addr, MacAddr, SystemTime),
+    // Every request, by whom and for which address. `from` is the sender
+    // address, 0.0.0.0 for DAD probes.
+    Asked { ip: Ipv4Addr, by: MacAddr, from: Ipv4Addr, at: SystemTime },
+    Storm(StormAlert),
+    // A switch announced itself over LLDP or CDP, for the first time or
+    // with something changed since
+    Neighbor(Neighbor, SystemTime),
+    // A DHCP client broadcast, for the first time from this MAC or with a
+    // different hostname or fingerprint since
+    Dhcp(DhcpClient, SystemTime),
+}
+
+#[derive(Debug, Clone)]
+pub struct PassiveResult {
+    pub interface: String,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    pub hosts: HashMap<Ipv4Addr, PassiveHost>,
+    pub hosts6: HashMap<Ipv6Addr, PassiveHost6>,
+    // On-link prefixes from router advertisements
+    pub prefixes: Vec<(Ipv6Addr, u8)>,
+    pub alerts: Vec<StormAlert>,
+    pub local: LocalSubnets,
+    // The latest announcement from each neighbor
+    pub neighbors: Vec<Neighbor>,
+    // The latest DHCP message from each client MAC
+    pub dhcp: HashMap<MacAddr, DhcpClient>,
+    // Reads from the capture channel that failed
+    pub receive_errors: usize,
+}
+
+// ARP plus the NDP, switch announcements and DHCP broadcasts passive
+// listening also decodes.
+#[cfg(feature = "engine")]
+fn passive_frame(frame: &[u8]) -> bool {
+    is_arp_frame(frame) || ndp::is_ndp_frame(frame) || neighbor::is_neighbor_frame(frame) || dhcp::is_dhcp_frame(frame)
+}
+
+// Record an NDP sighting, returning the host if it is new.
+#[cfg(feature = "engine")]
+fn observe_ndp(hosts6: &mut HashMap<Ipv6Addr, PassiveHost6>, sighting: &NdpSighting, at: SystemTime) -> Option<PassiveHost6> {
+    match hosts6.get_mut(&sighting.ip) {
+        Some(host) => {
+            host.mac = sighting.mac;
+            host.last_seen = at;
+            host.packets += 1;
+            host.router |= sighting.router;
+            None
+        }
+        None => {
+            let host = PassiveHost6 {
+                ip: sighting.ip,
+                mac: sighting.mac,
+                first_seen: at,
+                last_seen: at,
+                packets: 1,
+                router: sighting.router,
+            };
+            hosts6.insert(sighting.ip, host.clone());
+            Some(host)
+        }
+    }
+}
+
+// Listen to ARP traffic without sending anything, reporting hosts as they
+// first appear and any request floods along the way.
+#[cfg(feature = "engine")]
+pub fn listen<F: FnMut(&PassiveEvent)>(config: &PassiveConfig, on_event: F) -> Result<PassiveResult, String> {
+    listen_until(config, on_event, || false)
+}
+
+// Like listen, but also stops as soon as `stop` returns true. It is polled
+// a few times a second.
+#[cfg(feature = "engine")]
+pub fn listen_until<F, S>(config: &PassiveConfig, mut on_event: F, mut should_stop: S) -> Result<PassiveResult, String>
+where
+    F: FnMut(&PassiveEvent),
+    S: FnMut() -> bool,
+{
+    let interface = resolve_interface(config.interface.as_deref())?;
+    let (_tx, rx) = open_channel(&interface, &config.channel)?;
+    let own_mac = interface.mac.unwrap_or(MacAddr::zero());
+    let mut subnets = config.subnets.clone();
+    for network in &interface.ips {
+        if let IpAddr::V4(ip) = network.ip() {
+            subnets.push(CidrV4::new(ip, network.prefix())?);
+        }
+    }
+    let local = LocalSubnets::new(subnets);
+
+    let (frames, receiver) = spawn_receiver(rx, passive_frame);
+    let started = SystemTime::now();
+    let deadline = config.duration.map(|duration| Instant::now() + duration);
+    let mut detector = StormDetector::new(config.storm);
+    let mut hosts: HashMap<Ipv4Addr, PassiveHost> = HashMap::new();
+    let mut hosts6: HashMap<Ipv6Addr, PassiveHost6> = HashMap::new();
+    let mut prefixes: Vec<(Ipv6Addr, u8)> = Vec::new();
+    let mut alerts = Vec::new();
+    let mut neighbors: HashMap<(NeighborProtocol, MacAddr), Neighbor> = HashMap::new();
+    let mut clients: HashMap<MacAddr, DhcpClient> = HashMap::new();
+
+    loop {
+        if should_stop() {
+            break;
+        }
+        let wait = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                remaining.min(STOP_POLL)
+            }
+            None => STOP_POLL,
+        };
+        let received = match frames.recv_timeout(wait) {
+            Ok(received) => received,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        if let Some(heard) = neighbor::parse_neighbor_frame(&received.data) {
+            let key = (heard.protocol, heard.source);
+            // Announcements repeat every 30-60 seconds; only news is reported
+            if neighbors.get(&key) != Some(&heard) {
+                on_event(&PassiveEvent::Neighbor(heard.clone(), received.wall_clock));
+                neighbors.insert(key, heard);
+            }
+            continue;
+        }
+        if let Some(sighting) = ndp::parse_ndp_frame(&received.data) {
+            if sighting.mac == own_mac {
+                continue;
+            }
+            for prefix in &sighting.prefixes {
+                if !prefixes.contains(prefix) {
+                    prefixes.push(*prefix);
+                }
+            }
+            if let Some(host) = observe_ndp(&mut hosts6, &sighting, received.wall_clock) {
+                on_event(&PassiveEvent::NewHost6(host));
+            }
+            continue;
+        }
+        if let Some(mut client) = dhcp::parse_dhcp_frame(&received.data) {
+            let known = clients.get(&client.mac);
+            if !known.is_some_and(|known| known.same_identity(&client)) {
+                on_event(&PassiveEvent::Dhcp(client.clone(), received.wall_clock));
+            }
+            if client.relay_agent.is_none() {
+                client.relay_agent = known.and_then(|known| known.relay_agent.clone());
+            }
+            clients.insert(client.mac, client);
+            continue;
+        }
+        let Some(frame) = parse_arp_frame(&received.data) else {
+            continue;
+        };
+        let kind = match frame.arp.operation {
+            ARP_REPLY => ReplyKind::Reply,
+            ARP_REQUEST => ReplyKind::Request,
+            _ => continue,
+        };
+        let eth_source = MacAddr::from(frame.eth_source);
+        if eth_source == own_mac {
+            continue;
+        }
+        if kind == ReplyKind::Request {
+            if let Some(alert) = detector.observe(received.at, eth_source) {
+                let event = PassiveEvent::Storm(alert.clone());
+                alerts.push(alert);
+                on_event(&event);
+            }
+            on_event(&PassiveEvent::Asked {
+                ip: frame.arp.target_ip,
+                by: MacAddr::from(frame.arp.sender_mac),
+                from: frame.arp.sender_ip,
+                at: received.wall_clock,
+            });
+        }
+
+        // DAD probes from 0.0.0.0 say nothing about who owns an address
+        let ip = frame.arp.sender_ip;
+        if ip.is_unspecified() {
+            continue;
+        }
+        let mac = MacAddr::from(frame.arp.sender_mac);
+        on_event(&PassiveEvent::Seen(ip, mac, received.wall_clock));
+        match hosts.get_mut(&ip) {
+            Some(host) => {
+                host.mac = mac;
+                host.last_seen = received.wall_clock;
+                host.packets += 1;
+            }
+            None => {
+                let host = PassiveHost {
+                    ip,
+                    mac,
+                    first_seen: received.wall_clock,
+                    last_seen: received.wall_clock,
+                    packets: 1,
+                    off_subnet: local.is_off_subnet(ip),
+                };
+                on_event(&PassiveEvent::NewHost(host.clone(), kind));
+                hosts.insert(ip, host);
+            }
+        }
+    }
+
+    let receive_errors = receiver.finish();
+
+    Ok(PassiveResult {
+        interface: interface.name,
+        started,
+        finished: SystemTime::now(),
+        hosts,
+        hosts6,
+        prefixes,
+        alerts,
+        local,
+        neighbors: neighbors.into_values().collect(),
+        dhcp: clients,
+        receive_errors,
+    })
+}
@@ -0,0 +1,51 @@
+use std::env;
+use std::path::PathBuf;
+
+// Per-user directory for arp_scan's files: arp_scan under $XDG_DATA_HOME
+// (or ~/.local/share) on Unix and under %APPDATA% on Windows. Under sudo,
+// doas or pkexec HOME is root's, so ~/.local/share is taken from the
+// invoking user's home instead, so a scan run with sudo sees the notes and
+// oui.txt that user saved without it.
+pub fn data_dir() -> Option<PathBuf> {
+    let non_empty = |name: &str| env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = if cfg!(target_os = "windows") {
+        non_empty("APPDATA")
+    } else {
+        non_empty("XDG_DATA_HOME")
+            .or_else(|| invoking_home().or_else(|| non_empty("HOME")).map(|home| home.join(".local").join("share")))
+    };
+    base.map(|dir| dir.join("arp_scan"))
+}
+
+// Home directory of the user root was borrowed from, if running as root
+// through sudo, doas or pkexec.
+#[cfg(unix)]
+fn invoking_home() -> Option<PathBuf> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    if unsafe { libc::geteuid() } != 0 {
+        return None;
+    }
+    let env_uid = |name: &str| env::var(name).ok()?.trim().parse::<u32>().ok().filter(|uid| *uid != 0);
+    let entry = if let Some(uid) = env_uid("SUDO_UID").or_else(|| env_uid("PKEXEC_UID")) {
+        unsafe { libc::getpwuid(uid) }
+    } else {
+        let name = CString::new(env::var("DOAS_USER").ok()?).ok()?;
+        unsafe { libc::getpwnam(name.as_ptr()) }
+    };
+    if entry.is_null() {
+        return None;
+    }
+    let entry = unsafe { &*entry };
+    if entry.pw_uid == 0 || entry.pw_dir.is_null() {
+        return None;
+    }
+    let dir = unsafe { CStr::from_ptr(entry.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+#[cfg(not(unix))]
+fn invoking_home() -> Option<PathBuf> {
+    None
+}
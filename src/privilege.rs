@@ -0,0 +1,140 @@
+// Giving up root once the raw socket is open. Only opening the channel
+// needs privileges; the open descriptor keeps working afterwards, so the
+// parsing, enrichment and output that follow can run as an ordinary user.
+//
+// As root the process becomes the user who ran sudo, doas or pkexec, or
+// nobody if there is none. Without root (Linux file capabilities, e.g.
+// setcap cap_net_raw+ep) the capabilities are cleared instead. On OpenBSD
+// the process is then also pledged to file access and running --enrich-exec
+// commands. It is not unveiled: the files still to be opened (--save and
+// --output targets, state files, the vendor database search path and
+// whatever --enrich-exec runs from PATH) are only known to the caller, and
+// missing one would only show up as a failure after the scan.
+
+// Who the process now runs as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dropped {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+#[cfg(unix)]
+fn env_id(name: &str) -> Option<u32> {
+    std::env::var(name).ok()?.trim().parse().ok()
+}
+
+// The uid and primary gid of a user, looked up by name or by uid.
+#[cfg(unix)]
+fn passwd_entry(name: Option<&str>, uid: Option<u32>) -> Option<(u32, u32)> {
+    let name = name.map(std::ffi::CString::new).transpose().ok()?;
+    let entry = unsafe {
+        match (&name, uid) {
+            (Some(name), _) => libc::getpwnam(name.as_ptr()),
+            (None, Some(uid)) => libc::getpwuid(uid),
+            (None, None) => return None,
+        }
+    };
+    if entry.is_null() {
+        return None;
+    }
+    let entry = unsafe { &*entry };
+    Some((entry.pw_uid, entry.pw_gid))
+}
+
+// The user root was borrowed from, or nobody.
+#[cfg(unix)]
+fn invoking_user() -> Result<(u32, u32), String> {
+    if let Some(uid) = env_id("SUDO_UID").filter(|uid| *uid != 0) {
+        let gid = env_id("SUDO_GID").or_else(|| passwd_entry(None, Some(uid)).map(|(_, gid)| gid));
+        return gid.map(|gid| (uid, gid)).ok_or_else(|| format!("No group found for uid {}", uid));
+    }
+    if let Some(uid) = env_id("PKEXEC_UID").filter(|uid| *uid != 0) {
+        return passwd_entry(None, Some(uid)).ok_or_else(|| format!("No user found for uid {}", uid));
+    }
+    if let Ok(name) = std::env::var("DOAS_USER") {
+        if let Some(user) = passwd_entry(Some(&name), None).filter(|(uid, _)| *uid != 0) {
+            return Ok(user);
+        }
+    }
+    passwd_entry(Some("nobody"), None).ok_or_else(|| "No 'nobody' user to drop privileges to".to_string())
+}
+
+// Clear every capability set. Setting a non-root uid does this by itself,
+// but a binary given capabilities with setcap never was root.
+#[cfg(target_os = "linux")]
+fn clear_capabilities() -> Result<(), String> {
+    #[repr(C)]
+    struct Header {
+        version: u32,
+        pid: i32,
+    }
+    #[repr(C)]
+    #[derive(Default, Clone, Copy)]
+    struct Data {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+    // _LINUX_CAPABILITY_VERSION_3: two 32-bit halves of each set
+    let mut header = Header { version: 0x2008_0522, pid: 0 };
+    let data = [Data::default(); 2];
+    if unsafe { libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) } != 0 {
+        return Err(format!("Failed to clear capabilities: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn clear_capabilities() -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(target_os = "openbsd")]
+fn pledge() -> Result<(), String> {
+    let promises = c"stdio rpath wpath cpath proc exec";
+    if unsafe { libc::pledge(promises.as_ptr(), std::ptr::null()) } != 0 {
+        return Err(format!("pledge failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "openbsd")))]
+fn pledge() -> Result<(), String> {
+    Ok(())
+}
+
+// Drop to an unprivileged user for the rest of the process, every thread
+// included. Anything that needs root afterwards, such as opening a second
+// channel, will fail.
+#[cfg(unix)]
+pub fn drop_privileges() -> Result<Dropped, String> {
+    let failed = |what: &str| format!("Failed to drop privileges ({}): {}", what, std::io::Error::last_os_error());
+    if unsafe { libc::geteuid() } != 0 {
+        clear_capabilities()?;
+        pledge()?;
+        return Ok(Dropped { uid: unsafe { libc::getuid() }, gid: unsafe { libc::getgid() } });
+    }
+
+    let (uid, gid) = invoking_user()?;
+    // Groups first: changing them needs the root about to be given up
+    if unsafe { libc::setgroups(1, &gid) } != 0 {
+        return Err(failed("setgroups"));
+    }
+    if unsafe { libc::setgid(gid) } != 0 {
+        return Err(failed("setgid"));
+    }
+    if unsafe { libc::setuid(uid) } != 0 {
+        return Err(failed("setuid"));
+    }
+    // As root, setuid changes the saved uid too, so there is no way back
+    if unsafe { libc::setuid(0) } == 0 {
+        return Err("Failed to drop privileges: root could be regained".to_string());
+    }
+    pledge()?;
+    Ok(Dropped { uid, gid })
+}
+
+#[cfg(not(unix))]
+pub fn drop_privileges() -> Result<Dropped, String> {
+    Err("Dropping privileges is only supported on Unix".to_string())
+}
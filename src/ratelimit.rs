@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::scan::ScanResult;
+
+// Switches doing Dynamic ARP Inspection police ARP per port (Cisco's
+// default is 15 packets per second) and either drop the excess or shut the
+// port down. Both look like a network with fewer hosts than it really has,
+// so look for patterns an ordinary segment doesn't produce.
+
+// Below this many probes there is too little to go on
+const MIN_PROBES: usize = 64;
+// Sending slower than this is already under most policers
+const SLOW_PPS: f64 = 30.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitHint {
+    pub reason: String,
+    pub suggested_pps: u32,
+}
+
+// Probes per second over the first `count` probes.
+fn send_rate(probes: &[(Ipv4Addr, Duration)], count: usize) -> f64 {
+    let Some(&(_, last)) = probes.get(count.saturating_sub(1)) else {
+        return 0.0;
+    };
+    let elapsed = last.saturating_sub(probes[0].1).as_secs_f64();
+    if elapsed <= 0.0 {
+        f64::INFINITY
+    } else {
+        (count - 1) as f64 / elapsed
+    }
+}
+
+// Each target's first probe, in the order sent.
+fn first_probes(scan: &ScanResult) -> Vec<(Ipv4Addr, Duration)> {
+    let mut seen = HashSet::new();
+    scan.probes.iter().copied().filter(|(ip, _)| seen.insert(*ip)).collect()
+}
+
+// Half the rate that caused trouble, but never more than SLOW_PPS.
+fn suggest(rate: f64) -> u32 {
+    ((rate / 2.0).min(SLOW_PPS) as u32).max(1)
+}
+
+// Look at which probes went unanswered or failed and when, and explain the
+// gap if it looks like the switch rather than the hosts.
+pub fn detect(scan: &ScanResult) -> Option<RateLimitHint> {
+    port_shut_down(scan)
+        .or_else(|| replies_cut_off(scan))
+        .or_else(|| intermittent(scan))
+}
+
+// Sends worked for a while, then failed for good: the port may have been
+// err-disabled.
+fn port_shut_down(scan: &ScanResult) -> Option<RateLimitHint> {
+    let failed: HashSet<Ipv4Addr> = scan.send_failures.iter().map(|failure| failure.target).collect();
+    if scan.probes.len() < MIN_PROBES || failed.len() < 8 {
+        return None;
+    }
+    let first_failure = scan.probes.iter().position(|(ip, _)| failed.contains(ip))?;
+    let after = &scan.probes[first_failure..];
+    let still_failing = after.iter().filter(|(ip, _)| failed.contains(ip)).count();
+    if first_failure == 0 || still_failing * 10 < after.len() * 9 {
+        return None;
+    }
+    let rate = send_rate(&scan.probes, first_failure);
+    if rate <= SLOW_PPS {
+        return None;
+    }
+    Some(RateLimitHint {
+        reason: format!(
+            "sends started failing after {} probes at about {:.0} per second and never recovered; \
+             the switch may have shut the port down for exceeding its ARP rate limit",
+            first_failure, rate
+        ),
+        suggested_pps: suggest(rate),
+    })
+}
+
+// Hosts answered densely at first, then nothing answered at all even
+// though probing carried on for much longer. Only meaningful when the
+// targets were shuffled: in address order, a small network clustered at
+// the bottom of its range looks exactly like this.
+fn replies_cut_off(scan: &ScanResult) -> Option<RateLimitHint> {
+    let order = first_probes(scan);
+    if order.len() < MIN_PROBES || order.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+        return None;
+    }
+    let answered = order.iter().filter(|(ip, _)| scan.replies.contains_key(ip)).count();
+    let last = order.iter().rposition(|(ip, _)| scan.replies.contains_key(ip))?;
+    let before = last + 1;
+    let after = order.len() - before;
+    if answered < 4 || after < 32 || after < before * 2 || answered * 4 < before {
+        return None;
+    }
+    let rate = send_rate(&order, before);
+    if rate <= SLOW_PPS {
+        return None;
+    }
+    Some(RateLimitHint {
+        reason: format!(
+            "replies stopped {:.1}s into the scan, after {} probes at about {:.0} per second, \
+             and none of the {} probes after that were answered",
+            order[last].1.as_secs_f64(),
+            before,
+            rate,
+            after
+        ),
+        suggested_pps: suggest(rate),
+    })
+}
+
+// Over several rounds, most hosts skipped some of them. The rate is taken
+// from the first round, since the wait between rounds isn't sending.
+fn intermittent(scan: &ScanResult) -> Option<RateLimitHint> {
+    if scan.rounds < 2 || scan.responses.len() < 4 {
+        return None;
+    }
+    let partial = scan.responses.values().filter(|count| **count < scan.rounds).count();
+    if partial * 2 < scan.responses.len() {
+        return None;
+    }
+    let order = first_probes(scan);
+    let rate = send_rate(&order, order.len());
+    if rate <= SLOW_PPS {
+        return None;
+    }
+    Some(RateLimitHint {
+        reason: format!(
+            "{} of {} hosts missed some of the {} rounds while probing at about {:.0} per second",
+            partial,
+            scan.responses.len(),
+            scan.rounds,
+            rate
+        ),
+        suggested_pps: suggest(rate),
+    })
+}
@@ -0,0 +1,138 @@
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const MDNS_TIMEOUT: Duration = Duration::from_secs(1);
+const TYPE_A: u16 = 1;
+const CLASS_IN: u16 = 1;
+
+// IPv4 addresses for a host name. The system resolver is tried first; names
+// under .local fall back to asking the segment directly over mDNS, for
+// systems without an mDNS-aware resolver.
+pub fn resolve_host(name: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let system = (name, 0).to_socket_addrs().map(|addrs| {
+        let mut ips: Vec<Ipv4Addr> = addrs
+            .filter_map(|addr| match addr {
+                SocketAddr::V4(v4) => Some(*v4.ip()),
+                SocketAddr::V6(_) => None,
+            })
+            .collect();
+        ips.dedup();
+        ips
+    });
+    let error = match system {
+        Ok(ips) if !ips.is_empty() => return Ok(ips),
+        Ok(_) => "no IPv4 address".to_string(),
+        Err(e) => e.to_string(),
+    };
+
+    if name.trim_end_matches('.').to_ascii_lowercase().ends_with(".local") {
+        if let Ok(ips) = mdns_query(name) {
+            if !ips.is_empty() {
+                return Ok(ips);
+            }
+        }
+    }
+    Err(format!("Could not resolve '{}': {}", name, error))
+}
+
+// Encode a DNS name as length-prefixed labels.
+fn encode_name(name: &str) -> Result<Vec<u8>, String> {
+    let mut encoded = Vec::new();
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("Invalid host name '{}'", name));
+        }
+        encoded.push(label.len() as u8);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    Ok(encoded)
+}
+
+// Read a possibly compressed name starting at `pos`. Returns the name and
+// the position just after it in the message.
+fn read_name(message: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds pointer chasing so a malicious loop can't hang us
+    for _ in 0..64 {
+        let len = *message.get(pos)? as usize;
+        if len == 0 {
+            return Some((labels.join("."), end.unwrap_or(pos + 1)));
+        }
+        if len & 0xC0 == 0xC0 {
+            let pointer = ((len & 0x3F) << 8) | *message.get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pos = pointer;
+            continue;
+        }
+        let label = message.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+    None
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*message.get(pos)?, *message.get(pos + 1)?]))
+}
+
+// A records for `name` in a DNS response.
+fn parse_a_records(message: &[u8], name: &str) -> Option<Vec<Ipv4Addr>> {
+    if message.get(2)? & 0x80 == 0 {
+        return None;
+    }
+    let questions = read_u16(message, 4)?;
+    let answers = read_u16(message, 6)? as usize + read_u16(message, 8)? as usize + read_u16(message, 10)? as usize;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(message, pos)?.1 + 4;
+    }
+    let wanted = name.trim_end_matches('.');
+    let mut ips = Vec::new();
+    for _ in 0..answers {
+        let (owner, after) = read_name(message, pos)?;
+        let kind = read_u16(message, after)?;
+        // The top bit of the class is mDNS's cache-flush flag
+        let class = read_u16(message, after + 2)? & 0x7FFF;
+        let len = read_u16(message, after + 8)? as usize;
+        let data = message.get(after + 10..after + 10 + len)?;
+        if kind == TYPE_A && class == CLASS_IN && len == 4 && owner.eq_ignore_ascii_case(wanted) {
+            ips.push(Ipv4Addr::new(data[0], data[1], data[2], data[3]));
+        }
+        pos = after + 10 + len;
+    }
+    Some(ips)
+}
+
+// One-shot mDNS query (RFC 6762 section 5.1): sent from an ephemeral port,
+// so responders answer us directly by unicast.
+fn mdns_query(name: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let mut query = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    query.extend(encode_name(name)?);
+    query.extend_from_slice(&TYPE_A.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|e| format!("mDNS: {}", e))?;
+    socket.send_to(&query, (MDNS_GROUP, MDNS_PORT)).map_err(|e| format!("mDNS: {}", e))?;
+
+    let deadline = Instant::now() + MDNS_TIMEOUT;
+    let mut buf = [0u8; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(Vec::new());
+        }
+        socket.set_read_timeout(Some(remaining)).map_err(|e| format!("mDNS: {}", e))?;
+        let Ok((len, _)) = socket.recv_from(&mut buf) else {
+            return Ok(Vec::new());
+        };
+        if let Some(ips) = parse_a_records(&buf[..len], name) {
+            if !ips.is_empty() {
+                return Ok(ips);
+            }
+        }
+    }
+}
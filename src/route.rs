@@ -0,0 +1,52 @@
+use std::net::Ipv4Addr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultRoute {
+    pub interface: String,
+    pub gateway: Ipv4Addr,
+    pub metric: u32,
+}
+
+// Parse the default routes out of Linux's /proc/net/route. Addresses there
+// are hex in host byte order.
+pub fn parse_proc_net_route(text: &str) -> Vec<DefaultRoute> {
+    let mut routes = Vec::new();
+    for line in text.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 8 {
+            continue;
+        }
+        let parse_hex = |s: &str| u32::from_str_radix(s, 16).ok();
+        let (Some(destination), Some(gateway), Some(flags), Some(mask)) =
+            (parse_hex(fields[1]), parse_hex(fields[2]), parse_hex(fields[3]), parse_hex(fields[7]))
+        else {
+            continue;
+        };
+        // RTF_UP must be set; a default route has an all-zero destination and mask
+        if flags & 0x1 == 0 || destination != 0 || mask != 0 {
+            continue;
+        }
+        routes.push(DefaultRoute {
+            interface: fields[0].to_string(),
+            gateway: Ipv4Addr::from(gateway.to_ne_bytes()),
+            metric: fields[6].parse().unwrap_or(u32::MAX),
+        });
+    }
+    routes
+}
+
+// The system's IPv4 default routes. Only Linux is supported so far; other
+// platforms return nothing and interface selection falls back to
+// enumeration order.
+pub fn default_routes() -> Vec<DefaultRoute> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/net/route")
+            .map(|text| parse_proc_net_route(&text))
+            .unwrap_or_default()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
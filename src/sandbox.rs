@@ -0,0 +1,130 @@
+// A seccomp-bpf allowlist for Linux, installed once the capture channel is
+// open (and privileges dropped, if asked). From then on the process can
+// move packets, keep time, use memory and threads, and read or write files
+// it opens, but not create sockets, run programs, change credentials or
+// trace other processes. Anything else fails with EPERM rather than
+// killing the process, so a missed syscall shows up as an error message.
+
+// Who the filter was built for; a syscall made through another ABI (x32,
+// or 32-bit compat on x86_64) is killed outright.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn allowed() -> Vec<libc::c_long> {
+    let mut allowed = vec![
+        // Packet and file I/O
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_pwrite64,
+        libc::SYS_recvfrom,
+        libc::SYS_sendto,
+        libc::SYS_recvmsg,
+        libc::SYS_sendmsg,
+        libc::SYS_pselect6,
+        libc::SYS_ppoll,
+        libc::SYS_openat,
+        libc::SYS_close,
+        libc::SYS_fstat,
+        libc::SYS_newfstatat,
+        libc::SYS_statx,
+        libc::SYS_lseek,
+        libc::SYS_fcntl,
+        libc::SYS_getdents64,
+        libc::SYS_fsync,
+        libc::SYS_renameat2,
+        libc::SYS_unlinkat,
+        // Clock
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_nanosleep,
+        libc::SYS_gettimeofday,
+        // Memory, threads and signals
+        libc::SYS_brk,
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_mprotect,
+        libc::SYS_madvise,
+        libc::SYS_futex,
+        libc::SYS_clone,
+        libc::SYS_clone3,
+        libc::SYS_set_robust_list,
+        libc::SYS_rseq,
+        libc::SYS_sched_yield,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sigaltstack,
+        libc::SYS_getrandom,
+        libc::SYS_getpid,
+        libc::SYS_gettid,
+        libc::SYS_tgkill,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+    ];
+    // Older entry points glibc still uses on x86_64
+    #[cfg(target_arch = "x86_64")]
+    allowed.extend([libc::SYS_poll, libc::SYS_select, libc::SYS_open, libc::SYS_stat, libc::SYS_rename, libc::SYS_renameat, libc::SYS_unlink]);
+    allowed
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn filter() -> Vec<libc::sock_filter> {
+    const LOAD_WORD: u16 = (libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16;
+    const JUMP_EQUAL: u16 = (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16;
+    const RETURN: u16 = (libc::BPF_RET | libc::BPF_K) as u16;
+    // Offsets in struct seccomp_data
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+    let op = |code: u16, jt: u8, jf: u8, k: u32| libc::sock_filter { code, jt, jf, k };
+
+    let mut program = vec![
+        op(LOAD_WORD, 0, 0, ARCH),
+        op(JUMP_EQUAL, 1, 0, AUDIT_ARCH),
+        op(RETURN, 0, 0, libc::SECCOMP_RET_KILL_PROCESS),
+        op(LOAD_WORD, 0, 0, NR),
+    ];
+    for nr in allowed() {
+        program.push(op(JUMP_EQUAL, 0, 1, nr as u32));
+        program.push(op(RETURN, 0, 0, libc::SECCOMP_RET_ALLOW));
+    }
+    program.push(op(RETURN, 0, 0, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    program
+}
+
+// Install the filter on every thread of the process. It can't be removed.
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn restrict() -> Result<(), String> {
+    let failed = |what: &str| format!("Failed to install the sandbox ({}): {}", what, std::io::Error::last_os_error());
+    let mut program = filter();
+    let prog = libc::sock_fprog { len: program.len() as u16, filter: program.as_mut_ptr() };
+    // Lets an unprivileged process install a filter, and stops setuid
+    // binaries from regaining privileges under it
+    if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        return Err(failed("no_new_privs"));
+    }
+    let installed = unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_TSYNC,
+            &prog as *const libc::sock_fprog,
+        )
+    };
+    if installed != 0 {
+        return Err(failed("seccomp"));
+    }
+    Ok(())
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+pub fn restrict() -> Result<(), String> {
+    Err("The sandbox is only supported on Linux on x86_64 and aarch64".to_string())
+}
@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use pnet_base::MacAddr;
+
+use crate::packet::{ArpHeader, Vlan, ARP_FRAME_LEN, MAX_FRAME_LEN};
+use crate::snooping::SwitchPort;
+
+// The sending and receiving side needs raw sockets and lives in its own
+// module so the result types stay usable without pnet.
+#[cfg(feature = "engine")]
+mod engine;
+#[cfg(feature = "engine")]
+mod lock;
+#[cfg(all(feature = "engine", target_os = "linux"))]
+mod fd;
+
+#[cfg(feature = "engine")]
+pub use engine::{get_default_interface, resolve_interface, scan_interfaces, scan_network, scan_network_with};
+#[cfg(all(feature = "engine", target_os = "linux"))]
+pub use fd::open_packet_socket;
+#[cfg(feature = "engine")]
+pub(crate) use engine::{is_arp_frame, open_channel, spawn_receiver};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyKind {
+    // An answer to one of our requests
+    Reply,
+    // A request the host sent on its own, which still proves it is there
+    Request,
+}
+
+#[derive(Debug, Clone)]
+pub struct HostReply {
+    pub ip: Ipv4Addr,
+    pub mac: MacAddr,
+    // Time from sending the request to the first reply
+    pub rtt: Option<Duration>,
+    pub interface: String,
+    // Source address of the Ethernet frame, which normally matches `mac`
+    // (the ARP sender hardware address)
+    pub eth_source: MacAddr,
+    pub arp: ArpHeader,
+    pub received: SystemTime,
+    pub kind: ReplyKind,
+    // Length of the captured frame, not counting VLAN tags, and any bytes
+    // after the ARP payload.
+    // Stacks are supposed to pad with zeros; anything else can fingerprint
+    // a buggy driver or leak memory (Etherleak).
+    pub frame_len: usize,
+    pub padding: Vec<u8>,
+    // Name this address was given by as a target, if any
+    pub hostname: Option<String>,
+    // Note attached to the MAC with `arp_scan note`, if any
+    pub note: Option<String>,
+    // Switch port from an imported DHCP snooping table, if any
+    pub switch_port: Option<SwitchPort>,
+    // Replies beyond the first within a round; some stacks answer every
+    // request two or three times
+    pub duplicates: u32,
+    // Other MACs later replies claimed, kept with DupPolicy::Flag
+    pub duplicate_macs: Vec<MacAddr>,
+    // Which request first got an answer: 1 for the first round's, 2 if
+    // that one went unanswered, and so on. None for unsolicited requests.
+    pub attempt: Option<u32>,
+    // Earlier sightings of the same MAC on other segments, oldest first,
+    // when merged results suggest the device moved (see Snapshot::merge_with)
+    pub roamed_from: Vec<Roam>,
+}
+
+// Where a device was seen before it turned up where it is now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roam {
+    pub interface: String,
+    pub ip: Ipv4Addr,
+    pub at: SystemTime,
+}
+
+impl HostReply {
+    // The Ethernet source differs from the ARP sender hardware address. This
+    // is a classic sign of a man-in-the-middle tool or a misbehaving bridge.
+    pub fn eth_source_mismatch(&self) -> bool {
+        self.eth_source != self.mac
+    }
+
+    pub fn padding_nonzero(&self) -> usize {
+        self.padding.iter().filter(|b| **b != 0).count()
+    }
+
+    // Minimum-size Ethernet frames are 60 bytes without the FCS (64 with it);
+    // virtual interfaces often deliver the bare 42. Any other size, or padding
+    // that isn't all zeros, is unusual.
+    pub fn padding_anomaly(&self) -> bool {
+        self.padding_nonzero() > 0 || !matches!(self.frame_len, 42 | 60 | 64)
+    }
+}
+
+// A probe that could not be sent, even after a retry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendFailure {
+    pub target: Ipv4Addr,
+    pub error: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+    pub interface: String,
+    pub source_ip: Ipv4Addr,
+    pub source_mac: MacAddr,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    pub replies: HashMap<Ipv4Addr, HostReply>,
+    // Number of sweeps performed, and how many of them each host answered
+    pub rounds: u32,
+    pub responses: HashMap<Ipv4Addr, u32>,
+    // Round-trip time of each host's first reply in every round it answered
+    pub rtts: HashMap<Ipv4Addr, Vec<Duration>>,
+    pub send_failures: Vec<SendFailure>,
+    // Every ARP request sent, in order, with when it went out relative to
+    // `started`
+    pub probes: Vec<(Ipv4Addr, Duration)>,
+    // Probes skipped because the scan ran out of --max-duration budget
+    pub unsent: usize,
+    // Probes per second the scan had slowed to, if --adaptive-rate kicked in
+    pub reduced_pps: Option<u32>,
+    // Reads from the capture channel that failed (not timeouts)
+    pub receive_errors: usize,
+    // Stopped early through ScanConfig::cancel; the replies are the ones
+    // that came in until then
+    pub cancelled: bool,
+}
+
+// Lets another thread stop a scan that is under way. The scan notices
+// within the channel's read timeout, closes its channel and returns what
+// it has so far. Clones share one flag, so a token given to several scans
+// stops them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// What to put in the sender fields of our requests (RFC 5227).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProbeStyle {
+    // Our own address as the sender, like any host resolving a neighbour
+    #[default]
+    Normal,
+    // An address conflict probe: sender IP 0.0.0.0, so targets answer
+    // without caching our address. Some embedded stacks answer these
+    // differently, or not at all.
+    Dad,
+}
+
+// What to keep when a host answers more than once, whether within a round
+// or across rounds. The first reply's round-trip time is kept either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DupPolicy {
+    // The first reply
+    First,
+    // Each reply replaces the one before
+    #[default]
+    Last,
+    // The first reply, noting any other MACs that answered for the address
+    Flag,
+}
+
+// Raw socket tuning, passed through to pnet. Its buffers default to 4096
+// bytes. On BSD and macOS the receive buffer is the kernel's BPF buffer, so
+// enlarging it lets a reply burst queue up instead of being dropped; on
+// Linux both are per-frame buffers. The read timeout bounds how long the
+// receive thread takes to notice the end of a scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelTuning {
+    pub rx_buffer: Option<usize>,
+    pub tx_buffer: Option<usize>,
+    pub read_timeout: Duration,
+    // Give up root as soon as the channel is open (see privilege.rs). Only
+    // for callers that open a single channel per process.
+    pub drop_privileges: bool,
+    // Then confine the process with a seccomp filter (see sandbox.rs), with
+    // the same restriction
+    pub sandbox: bool,
+    // Use this already open AF_PACKET socket instead of opening one, so
+    // the process needs no privileges of its own (Linux, see scan/fd.rs)
+    pub fd: Option<i32>,
+}
+
+impl Default for ChannelTuning {
+    fn default() -> Self {
+        ChannelTuning {
+            rx_buffer: None,
+            tx_buffer: None,
+            read_timeout: Duration::from_millis(100),
+            drop_privileges: false,
+            sandbox: false,
+            fd: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    // How long to listen for replies after the last request is sent
+    pub timeout: Duration,
+    // Pad requests to this many bytes (excluding FCS) instead of sending the
+    // bare 42-byte frame
+    pub pad_to: Option<usize>,
+    // Number of full sweeps to perform; replies are counted per host
+    pub rounds: u32,
+    // Stop as soon as this many hosts have answered
+    pub max_hosts: Option<usize>,
+    // Probe only likely gateways, send a broadcast ping, and otherwise rely
+    // on background ARP traffic from the targets
+    pub quick: bool,
+    // Scan from this interface (system name or Windows friendly name)
+    // instead of picking one automatically
+    pub interface: Option<String>,
+    // Send requests for these targets straight to the given MAC instead of
+    // broadcasting them
+    pub unicast: HashMap<Ipv4Addr, MacAddr>,
+    // Send at most this many probes per second
+    pub pps: Option<u32>,
+    // Halve the send rate whenever the kernel reports a full send buffer
+    pub adaptive_rate: bool,
+    pub probe_style: ProbeStyle,
+    pub channel: ChannelTuning,
+    // Finish within this long, however many targets and rounds there are.
+    // Each round gets an equal share, of which sending may use three
+    // quarters; the rest is kept for replies.
+    pub max_duration: Option<Duration>,
+    // Tag requests for this VLAN (or QinQ pair) and ignore replies tagged
+    // for another one
+    pub vlan: Vlan,
+    pub dup_policy: DupPolicy,
+    // Checked between probes and while waiting for replies
+    pub cancel: Option<CancelToken>,
+    // Also keep other processes from scanning the same interface at the
+    // same time, with a lock file per interface in this directory
+    pub lock_dir: Option<PathBuf>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig {
+            timeout: Duration::from_secs(5),
+            pad_to: None,
+            rounds: 1,
+            max_hosts: None,
+            quick: false,
+            interface: None,
+            unicast: HashMap::new(),
+            pps: None,
+            adaptive_rate: false,
+            probe_style: ProbeStyle::Normal,
+            channel: ChannelTuning::default(),
+            max_duration: None,
+            vlan: Vlan::Untagged,
+            dup_policy: DupPolicy::Last,
+            cancel: None,
+            lock_dir: None,
+        }
+    }
+}
+
+impl ScanConfig {
+    pub fn builder() -> ScanConfigBuilder {
+        ScanConfigBuilder::default()
+    }
+
+    // Settings that can't work, or only fail part way through a scan,
+    // checked before anything is sent.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timeout.is_zero() {
+            return Err("The reply timeout must be greater than zero".to_string());
+        }
+        if self.rounds == 0 {
+            return Err("A scan needs at least one round".to_string());
+        }
+        if self.max_hosts == Some(0) {
+            return Err("max_hosts must be at least 1".to_string());
+        }
+        if self.pps == Some(0) {
+            return Err("The probe rate must be at least 1 per second".to_string());
+        }
+        if let Some(pad_to) = self.pad_to {
+            if !(ARP_FRAME_LEN..=MAX_FRAME_LEN).contains(&pad_to) {
+                return Err(format!("Frame padding must be between {} and {} bytes", ARP_FRAME_LEN, MAX_FRAME_LEN));
+            }
+        }
+        if self.channel.read_timeout.is_zero() {
+            return Err("The channel read timeout must be greater than zero".to_string());
+        }
+        if self.channel.read_timeout > self.timeout {
+            return Err(format!(
+                "The channel read timeout ({} ms) is longer than the reply timeout ({} ms), so the scan would overrun it",
+                self.channel.read_timeout.as_millis(),
+                self.timeout.as_millis()
+            ));
+        }
+        if let Some(max) = self.max_duration {
+            // Sending may use three quarters of each round's share. The
+            // first probe goes out at once and the rest (count - 1) / pps
+            // later, so with any time at all one probe fits at every rate;
+            // whatever else doesn't is counted as unsent.
+            let sending = max * 3 / 4 / self.rounds;
+            if sending.is_zero() {
+                return Err(format!("max_duration of {} ms is too short for {} rounds", max.as_millis(), self.rounds));
+            }
+        }
+        let vlan_ids = match self.vlan {
+            Vlan::Untagged => vec![],
+            Vlan::Single(id) => vec![id],
+            Vlan::QinQ { outer, inner } => vec![outer, inner],
+        };
+        if let Some(id) = vlan_ids.iter().find(|id| !(1..=4094).contains(*id)) {
+            return Err(format!("Invalid VLAN ID {}: VLAN IDs are 1 to 4094", id));
+        }
+        // Tagging only makes sense on the trunk the VLAN is carried on,
+        // which the default route's interface rarely is
+        if !self.vlan.is_empty() && self.interface.is_none() {
+            return Err("Tagging requests for a VLAN needs an explicit interface".to_string());
+        }
+        if self.interface.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            return Err("The interface name is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Builds a ScanConfig for library users, checking the combination with
+// ScanConfig::validate so mistakes show up at build() rather than mid-scan:
+//   ScanConfig::builder().interface("eth0").timeout(Duration::from_secs(2)).build()?
+#[derive(Debug, Clone, Default)]
+pub struct ScanConfigBuilder {
+    config: ScanConfig,
+}
+
+impl ScanConfigBuilder {
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn pad_to(mut self, len: usize) -> Self {
+        self.config.pad_to = Some(len);
+        self
+    }
+
+    pub fn rounds(mut self, rounds: u32) -> Self {
+        self.config.rounds = rounds;
+        self
+    }
+
+    pub fn max_hosts(mut self, max: usize) -> Self {
+        self.config.max_hosts = Some(max);
+        self
+    }
+
+    pub fn quick(mut self, quick: bool) -> Self {
+        self.config.quick = quick;
+        self
+    }
+
+    pub fn interface(mut self, name: impl Into<String>) -> Self {
+        self.config.interface = Some(name.into());
+        self
+    }
+
+    // Send requests for `ip` straight to `mac` instead of broadcasting them
+    pub fn unicast(mut self, ip: Ipv4Addr, mac: MacAddr) -> Self {
+        self.config.unicast.insert(ip, mac);
+        self
+    }
+
+    pub fn pps(mut self, pps: u32) -> Self {
+        self.config.pps = Some(pps);
+        self
+    }
+
+    pub fn adaptive_rate(mut self, adaptive: bool) -> Self {
+        self.config.adaptive_rate = adaptive;
+        self
+    }
+
+    pub fn probe_style(mut self, style: ProbeStyle) -> Self {
+        self.config.probe_style = style;
+        self
+    }
+
+    pub fn channel(mut self, channel: ChannelTuning) -> Self {
+        self.config.channel = channel;
+        self
+    }
+
+    pub fn max_duration(mut self, max: Duration) -> Self {
+        self.config.max_duration = Some(max);
+        self
+    }
+
+    pub fn vlan(mut self, vlan: Vlan) -> Self {
+        self.config.vlan = vlan;
+        self
+    }
+
+    pub fn dup_policy(mut self, policy: DupPolicy) -> Self {
+        self.config.dup_policy = policy;
+        self
+    }
+
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.config.cancel = Some(token);
+        self
+    }
+
+    pub fn lock_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.lock_dir = Some(dir.into());
+        self
+    }
+
+    pub fn build(self) -> Result<ScanConfig, String> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
@@ -0,0 +1,609 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use pnet::datalink::{self, NetworkInterface};
+use pnet::util::MacAddr;
+
+use super::lock::InterfaceLock;
+use super::{CancelToken, ChannelTuning, DupPolicy, HostReply, ProbeStyle, ReplyKind, ScanConfig, ScanResult, SendFailure, Vlan};
+use crate::interfaces;
+use crate::packet::{
+    parse_arp_frame, ArpFrame, IcmpEchoFrame, ARP_FRAME_LEN, ARP_REPLY, ARP_REQUEST, ICMP_ECHO_FRAME_LEN,
+};
+use crate::privilege;
+use crate::route;
+use crate::sandbox;
+use crate::target;
+
+// Pick the interface to scan from. When several are usable, prefer the one
+// carrying the default route with the lowest metric, so a split-tunnel VPN
+// adapter that happens to enumerate first doesn't win.
+pub fn get_default_interface() -> Option<NetworkInterface> {
+    let candidates: Vec<NetworkInterface> = datalink::interfaces()
+        .into_iter()
+        .filter(interfaces::is_usable)
+        .collect();
+
+    let mut routes = route::default_routes();
+    routes.sort_by_key(|route| route.metric);
+    routes.iter()
+        .find_map(|route| candidates.iter().find(|iface| iface.name == route.interface))
+        .or_else(|| candidates.first())
+        .cloned()
+}
+
+// Scan the same targets from several interfaces at once, one result per
+// interface in the order given. With no names, scan from the default
+// interface only.
+pub fn scan_interfaces(targets: &[Ipv4Addr], config: &ScanConfig, names: &[String]) -> Result<Vec<ScanResult>, String> {
+    if names.is_empty() {
+        return scan_network(targets, config).map(|scan| vec![scan]);
+    }
+    thread::scope(|scope| {
+        let handles: Vec<_> = names.iter()
+            .map(|name| {
+                let config = ScanConfig { interface: Some(name.clone()), ..config.clone() };
+                scope.spawn(move || scan_network(targets, &config))
+            })
+            .collect();
+        handles.into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|_| Err("Scan thread panicked".to_string())))
+            .collect()
+    })
+}
+
+// The named interface, or the default one when no name is given.
+pub fn resolve_interface(name: Option<&str>) -> Result<NetworkInterface, String> {
+    match name {
+        Some(name) => interfaces::find_interface(name),
+        None => get_default_interface()
+            .ok_or_else(|| {
+                let os_msg = if cfg!(target_os = "windows") {
+                    "No suitable network interface found. Ensure you’re running with administrative privileges."
+                } else {
+                    "No suitable network interface found. Ensure you’re running with root privileges (e.g., sudo)."
+                };
+                os_msg.to_string()
+            }),
+    }
+}
+
+const SEND_RETRY_DELAY: Duration = Duration::from_millis(10);
+// Backoff while the send buffer is full: 1ms doubling to 256ms, about half
+// a second in all before the target is given up on
+const BACKOFF_START: Duration = Duration::from_millis(1);
+const BACKOFF_MAX: Duration = Duration::from_millis(256);
+// Where --adaptive-rate starts when no --pps was given, and its floor
+const ADAPTIVE_START: Duration = Duration::from_micros(100);
+const ADAPTIVE_SLOWEST: Duration = Duration::from_millis(100);
+
+// ENOBUFS: the kernel or driver has no room for the frame right now.
+fn buffer_full(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    let enobufs = Some(libc::ENOBUFS);
+    #[cfg(windows)]
+    let enobufs = Some(10055); // WSAENOBUFS
+    #[cfg(not(any(unix, windows)))]
+    let enobufs: Option<i32> = None;
+    error.kind() == io::ErrorKind::WouldBlock || (enobufs.is_some() && error.raw_os_error() == enobufs)
+}
+
+// Spaces probes out to a packets-per-second limit, which --adaptive-rate
+// lowers each time the send buffer overflows.
+struct Pacer {
+    interval: Option<Duration>,
+    next: Instant,
+    adaptive: bool,
+    reduced: bool,
+}
+
+impl Pacer {
+    fn new(pps: Option<u32>, adaptive: bool) -> Self {
+        Pacer {
+            interval: pps.filter(|pps| *pps > 0).map(|pps| Duration::from_secs(1) / pps),
+            next: Instant::now(),
+            adaptive,
+            reduced: false,
+        }
+    }
+
+    fn wait(&mut self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let now = Instant::now();
+        if self.next > now {
+            thread::sleep(self.next - now);
+        }
+        self.next = self.next.max(now) + interval;
+    }
+
+    fn slow_down(&mut self) {
+        if !self.adaptive {
+            return;
+        }
+        self.interval = Some(self.interval.map_or(ADAPTIVE_START, |interval| (interval * 2).min(ADAPTIVE_SLOWEST)));
+        self.reduced = true;
+    }
+
+    fn reduced_pps(&self) -> Option<u32> {
+        let interval = self.interval.filter(|_| self.reduced)?;
+        Some((1.0 / interval.as_secs_f64()).round() as u32)
+    }
+}
+
+// Send one frame. A full send buffer is waited out with increasing
+// backoff; any other error gets a single retry, since most (a link
+// renegotiating, say) are momentary.
+fn send_frame(
+    tx: &mut dyn datalink::DataLinkSender,
+    frame: &[u8],
+    pacer: &mut Pacer,
+    give_up: Option<Instant>,
+) -> Result<(), String> {
+    let mut backoff = BACKOFF_START;
+    let mut retried = false;
+    loop {
+        pacer.wait();
+        let error = match tx.send_to(frame, None) {
+            Some(Ok(())) => return Ok(()),
+            Some(Err(e)) => e,
+            None => return Err("the channel cannot send".to_string()),
+        };
+        if give_up.is_some_and(|at| Instant::now() >= at) {
+            return Err(format!("{} (out of time to retry)", error));
+        }
+        if buffer_full(&error) && backoff <= BACKOFF_MAX {
+            pacer.slow_down();
+            thread::sleep(backoff);
+            backoff *= 2;
+        } else if retried {
+            return Err(error.to_string());
+        } else {
+            retried = true;
+            thread::sleep(SEND_RETRY_DELAY);
+        }
+    }
+}
+
+pub(crate) type Channel = (Box<dyn datalink::DataLinkSender>, Box<dyn datalink::DataLinkReceiver>);
+
+pub(crate) fn open_channel(interface: &NetworkInterface, tuning: &ChannelTuning) -> Result<Channel, String> {
+    let channel = match tuning.fd {
+        Some(fd) => inherited_channel(fd, interface, tuning)?,
+        None => pnet_channel(interface, tuning)?,
+    };
+    if tuning.drop_privileges {
+        privilege::drop_privileges()?;
+    }
+    if tuning.sandbox {
+        sandbox::restrict()?;
+    }
+    Ok(channel)
+}
+
+#[cfg(target_os = "linux")]
+fn inherited_channel(fd: i32, interface: &NetworkInterface, tuning: &ChannelTuning) -> Result<Channel, String> {
+    super::fd::fd_channel(fd, interface, tuning)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn inherited_channel(fd: i32, _interface: &NetworkInterface, _tuning: &ChannelTuning) -> Result<Channel, String> {
+    Err(format!("Scanning on an inherited socket (fd {}) is only supported on Linux", fd))
+}
+
+fn pnet_channel(interface: &NetworkInterface, tuning: &ChannelTuning) -> Result<Channel, String> {
+    // A short read timeout lets the receive thread notice when the scan is
+    // over even if the segment is completely quiet.
+    let defaults = datalink::Config::default();
+    let channel_config = datalink::Config {
+        read_timeout: Some(tuning.read_timeout),
+        read_buffer_size: tuning.rx_buffer.unwrap_or(defaults.read_buffer_size),
+        write_buffer_size: tuning.tx_buffer.unwrap_or(defaults.write_buffer_size),
+        ..defaults
+    };
+    match datalink::channel(interface, channel_config) {
+        Ok(datalink::Channel::Ethernet(tx, rx)) => Ok((tx, rx)),
+        Ok(_) => Err("Unhandled channel type".to_string()),
+        Err(e) => {
+            let os_msg = if cfg!(target_os = "windows") {
+                format!("Failed to create channel: {}. Ensure you’re running as Administrator.", e)
+            } else {
+                format!("Failed to create channel: {}. Ensure you’re running with sudo.", e)
+            };
+            Err(os_msg)
+        }
+    }
+}
+
+pub fn scan_network(targets: &[Ipv4Addr], config: &ScanConfig) -> Result<ScanResult, String> {
+    scan_network_with(targets, config, &mut |_| {})
+}
+
+// Like scan_network, but calls `on_reply` for each host as soon as it is
+// first seen instead of only returning them all at the end.
+pub fn scan_network_with(
+    targets: &[Ipv4Addr],
+    config: &ScanConfig,
+    on_reply: &mut dyn FnMut(&HostReply),
+) -> Result<ScanResult, String> {
+    config.validate()?;
+
+    let interface = resolve_interface(config.interface.as_deref())?;
+
+    let source_ip = interface.ips.iter()
+        .find(|ip| ip.is_ipv4())
+        .map(|ip| match ip.ip() {
+            IpAddr::V4(ip) => ip,
+            _ => Ipv4Addr::new(0, 0, 0, 0),
+        })
+        .unwrap_or(Ipv4Addr::new(0, 0, 0, 0));
+
+    let source_mac = interface.mac.unwrap_or(MacAddr::zero());
+    let started = SystemTime::now();
+    let clock = Instant::now();
+
+    let _lock = InterfaceLock::acquire(&interface.name, config.lock_dir.as_deref())?;
+    let (mut tx, rx) = open_channel(&interface, &config.channel)?;
+
+    let (frames, receiver) = spawn_receiver(rx, is_arp_frame);
+    let mut collector = Collector::new(&interface.name, source_mac, config.vlan, config.dup_policy);
+
+    let sender_ip = match config.probe_style {
+        ProbeStyle::Normal => source_ip,
+        ProbeStyle::Dad => Ipv4Addr::UNSPECIFIED,
+    };
+    let frame_len = config.pad_to.unwrap_or(ARP_FRAME_LEN);
+    let mut frame = vec![0u8; frame_len.max(ICMP_ECHO_FRAME_LEN) + config.vlan.len()];
+
+    let mut send_failures = Vec::new();
+    let mut sent = Vec::new();
+    let mut pacer = Pacer::new(config.pps, config.adaptive_rate);
+    let quick_probes;
+    let probes = if config.quick {
+        collector.listen_for = targets.iter().copied().collect();
+        for (sequence, broadcast) in target::broadcast_addresses(targets).into_iter().enumerate() {
+            let ping = IcmpEchoFrame {
+                eth_source: source_mac.octets(),
+                source_ip,
+                destination_ip: broadcast,
+                identifier: std::process::id() as u16,
+                sequence: sequence as u16,
+            };
+            let len = ping.write(&mut frame)
+                .and_then(|len| config.vlan.tag(&mut frame, len))
+                .map_err(|e| e.to_string())?;
+            if let Err(error) = send_frame(tx.as_mut(), &frame[..len], &mut pacer, None) {
+                send_failures.push(SendFailure { target: broadcast, error });
+            }
+        }
+        quick_probes = target::gateway_candidates(targets);
+        &quick_probes[..]
+    } else {
+        targets
+    };
+
+    let cancelled = || config.cancel.as_ref().is_some_and(CancelToken::is_cancelled);
+    let mut stopped = false;
+    let rounds = config.rounds.max(1);
+    let budget = config.max_duration.map(|max| (Instant::now(), max / rounds));
+    let mut unsent = 0;
+    'rounds: for round in 0..rounds {
+        let start_time = Instant::now();
+        collector.answered.clear();
+        let (send_until, round_end) = match budget {
+            Some((began, share)) => {
+                let end = began + share * (round + 1);
+                (Some(end - share / 4), Some(end))
+            }
+            None => (None, None),
+        };
+
+        for (index, &target_ip) in probes.iter().enumerate() {
+            if cancelled() {
+                stopped = true;
+                break 'rounds;
+            }
+            if send_until.is_some_and(|at| Instant::now() >= at) {
+                unsent += probes.len() - index;
+                break;
+            }
+            let request = match config.unicast.get(&target_ip) {
+                Some(mac) => ArpFrame::unicast_request(source_mac.octets(), sender_ip, target_ip, mac.octets()),
+                None => ArpFrame::request(source_mac.octets(), sender_ip, target_ip),
+            };
+            let len = request
+                .write_padded(&mut frame, frame_len)
+                .and_then(|len| config.vlan.tag(&mut frame, len))
+                .map_err(|e| e.to_string())?;
+
+            collector.sent_at.entry(target_ip).or_default().push(Instant::now());
+            sent.push((target_ip, clock.elapsed()));
+            if let Err(error) = send_frame(tx.as_mut(), &frame[..len], &mut pacer, send_until) {
+                send_failures.push(SendFailure { target: target_ip, error });
+            }
+
+            // Handle replies as they arrive so --max-hosts can stop the
+            // sweep early on large ranges.
+            while let Ok(received) = frames.try_recv() {
+                if let Some(reply) = collector.handle(&received) {
+                    on_reply(reply);
+                }
+            }
+            if collector.reached(config.max_hosts) {
+                break 'rounds;
+            }
+        }
+
+        loop {
+            let mut remaining = config.timeout.saturating_sub(start_time.elapsed());
+            if let Some(end) = round_end {
+                remaining = remaining.min(end.saturating_duration_since(Instant::now()));
+            }
+            if remaining.is_zero() {
+                break;
+            }
+            if cancelled() {
+                stopped = true;
+                break 'rounds;
+            }
+            // Wake up regularly to look at the token
+            if config.cancel.is_some() {
+                remaining = remaining.min(config.channel.read_timeout);
+            }
+            if let Ok(received) = frames.recv_timeout(remaining) {
+                if let Some(reply) = collector.handle(&received) {
+                    on_reply(reply);
+                }
+            }
+            if collector.reached(config.max_hosts) {
+                break 'rounds;
+            }
+        }
+
+        collector.finish_round();
+    }
+    collector.finish_round();
+
+    let receive_errors = receiver.finish();
+
+    Ok(ScanResult {
+        interface: interface.name.clone(),
+        source_ip,
+        source_mac,
+        started,
+        finished: SystemTime::now(),
+        replies: collector.results,
+        rounds,
+        responses: collector.responses,
+        rtts: collector.rtts,
+        send_failures,
+        probes: sent,
+        unsent,
+        reduced_pps: pacer.reduced_pps(),
+        receive_errors,
+        cancelled: stopped,
+    })
+}
+
+pub(crate) struct ReceivedFrame {
+    pub(crate) data: Vec<u8>,
+    pub(crate) at: Instant,
+    pub(crate) wall_clock: SystemTime,
+}
+
+pub(crate) fn is_arp_frame(frame: &[u8]) -> bool {
+    parse_arp_frame(frame).is_some()
+}
+
+// The receive thread of an open channel. Dropping it, including on an
+// early return with an error, stops the thread and waits for it, so the
+// capture handle is closed (and any promiscuous mode it set undone) by the
+// time the caller returns.
+pub(crate) struct Receiver {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<usize>>,
+}
+
+impl Receiver {
+    // Stop the thread and return how many reads failed
+    pub(crate) fn finish(mut self) -> usize {
+        self.stop_and_join()
+    }
+
+    fn stop_and_join(&mut self) -> usize {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.take().and_then(|handle| handle.join().ok()).unwrap_or(0)
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+// Read frames on a separate thread so the sender never blocks on a quiet
+// segment. Frames `wanted` accepts are passed back over a channel until
+// the returned Receiver is finished or dropped.
+pub(crate) fn spawn_receiver(
+    mut rx: Box<dyn datalink::DataLinkReceiver>,
+    wanted: fn(&[u8]) -> bool,
+) -> (mpsc::Receiver<ReceivedFrame>, Receiver) {
+    let (sender, frames) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    let handle = thread::spawn(move || {
+        let mut errors = 0;
+        while !stopped.load(Ordering::Relaxed) {
+            match rx.next() {
+                Ok(packet) => {
+                    if !wanted(packet) {
+                        continue;
+                    }
+                    let frame = ReceivedFrame {
+                        data: packet.to_vec(),
+                        at: Instant::now(),
+                        wall_clock: SystemTime::now(),
+                    };
+                    if sender.send(frame).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(_) => {
+                    errors += 1;
+                    continue;
+                }
+            }
+        }
+        errors
+    });
+    (frames, Receiver { stop, handle: Some(handle) })
+}
+
+// Reply bookkeeping shared by every round of a scan.
+struct Collector {
+    interface: String,
+    source_mac: MacAddr,
+    // When each request to a target went out, one per round
+    sent_at: HashMap<Ipv4Addr, Vec<Instant>>,
+    results: HashMap<Ipv4Addr, HostReply>,
+    answered: HashSet<Ipv4Addr>,
+    responses: HashMap<Ipv4Addr, u32>,
+    rtts: HashMap<Ipv4Addr, Vec<Duration>>,
+    // Hosts whose unsolicited ARP traffic counts as a sighting even though
+    // we never probed them (quick mode)
+    listen_for: HashSet<Ipv4Addr>,
+    vlan: Vlan,
+    dup_policy: DupPolicy,
+}
+
+impl Collector {
+    fn new(interface: &str, source_mac: MacAddr, vlan: Vlan, dup_policy: DupPolicy) -> Self {
+        Collector {
+            interface: interface.to_string(),
+            source_mac,
+            vlan,
+            dup_policy,
+            sent_at: HashMap::new(),
+            results: HashMap::new(),
+            answered: HashSet::new(),
+            responses: HashMap::new(),
+            rtts: HashMap::new(),
+            listen_for: HashSet::new(),
+        }
+    }
+
+    // The request a reply received at `at` answers, numbered from 1, and
+    // when it was sent. ARP has no request IDs, so this is the last request
+    // to that address that went out before the reply arrived.
+    fn attempt(&self, ip: Ipv4Addr, at: Instant) -> Option<(u32, Instant)> {
+        let sends = self.sent_at.get(&ip)?;
+        let index = sends.iter().rposition(|sent| *sent <= at)?;
+        Some((index as u32 + 1, sends[index]))
+    }
+
+    // Record a received frame. Returns the host if this is the first time
+    // it has been seen.
+    fn handle(&mut self, received: &ReceivedFrame) -> Option<&HostReply> {
+        let packet = &received.data;
+        let frame = parse_arp_frame(packet)?;
+        let kind = match frame.arp.operation {
+            ARP_REPLY => ReplyKind::Reply,
+            ARP_REQUEST => ReplyKind::Request,
+            _ => return None,
+        };
+        if !self.vlan.accepts(frame.vlan) {
+            return None;
+        }
+        let ip = frame.arp.sender_ip;
+        let mac = MacAddr::from(frame.arp.sender_mac);
+        // Skip our own requests, DAD probes from 0.0.0.0, and
+        // chatter from hosts we weren't asked about
+        if mac == self.source_mac || ip.is_unspecified() {
+            return None;
+        }
+        if kind == ReplyKind::Request && !self.sent_at.contains_key(&ip) && !self.listen_for.contains(&ip) {
+            return None;
+        }
+        let previous = self.results.get(&ip);
+        if kind == ReplyKind::Request && previous.is_some() {
+            return None;
+        }
+        let first = previous.is_none();
+
+        let attempt = match kind {
+            ReplyKind::Reply => self.attempt(ip, received.at),
+            ReplyKind::Request => None,
+        };
+        let rtt = attempt.map(|(_, sent)| received.at.saturating_duration_since(sent));
+        if let (Some(sample), false) = (rtt, self.answered.contains(&ip)) {
+            self.rtts.entry(ip).or_default().push(sample);
+        }
+        if let Some(previous) = self.results.get_mut(&ip) {
+            if self.answered.contains(&ip) {
+                previous.duplicates += 1;
+            }
+            match self.dup_policy {
+                DupPolicy::Last => {}
+                DupPolicy::First => {
+                    self.answered.insert(ip);
+                    return None;
+                }
+                DupPolicy::Flag => {
+                    if mac != previous.mac && !previous.duplicate_macs.contains(&mac) {
+                        previous.duplicate_macs.push(mac);
+                    }
+                    self.answered.insert(ip);
+                    return None;
+                }
+            }
+        }
+        let previous = self.results.get(&ip);
+        let rtt = previous.and_then(|prev| prev.rtt).or(rtt);
+        let attempt = previous.and_then(|prev| prev.attempt).or(attempt.map(|(attempt, _)| attempt));
+        let duplicates = previous.map_or(0, |prev| prev.duplicates);
+        self.results.insert(ip, HostReply {
+            ip,
+            mac,
+            rtt,
+            interface: self.interface.clone(),
+            eth_source: MacAddr::from(frame.eth_source),
+            arp: frame.arp,
+            received: received.wall_clock,
+            kind,
+            frame_len: packet.len() - frame.vlan.len(),
+            padding: packet[frame.payload_end()..].to_vec(),
+            hostname: None,
+            note: None,
+            switch_port: None,
+            duplicates,
+            duplicate_macs: Vec::new(),
+            attempt,
+            roamed_from: Vec::new(),
+        });
+        self.answered.insert(ip);
+        if first {
+            self.results.get(&ip)
+        } else {
+            None
+        }
+    }
+
+    fn reached(&self, max_hosts: Option<usize>) -> bool {
+        max_hosts.is_some_and(|max| self.results.len() >= max)
+    }
+
+    // Credit every host that answered during the round just finished.
+    fn finish_round(&mut self) {
+        for ip in self.answered.drain() {
+            *self.responses.entry(ip).or_insert(0) += 1;
+        }
+    }
+}
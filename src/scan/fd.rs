@@ -0,0 +1,166 @@
+// A capture channel over an AF_PACKET socket someone else opened: a
+// privileged parent, a container runtime or a wrapper that passed it down
+// by inheritance or SCM_RIGHTS. Creating the socket is the only step that
+// needs CAP_NET_RAW; binding it, setting promiscuous mode and using it do
+// not, so the process itself can run with no capabilities at all.
+//
+// The channel works on duplicates of the descriptor, so a monitor can
+// reopen it for every sweep and the original stays open until exit.
+
+use std::io;
+use std::mem;
+use std::os::raw::c_int;
+use std::time::Duration;
+
+use pnet::datalink::{DataLinkReceiver, DataLinkSender, NetworkInterface};
+
+use super::engine::Channel;
+use crate::scan::ChannelTuning;
+
+struct Socket(c_int);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+fn check(result: c_int, what: &str) -> Result<(), String> {
+    if result < 0 {
+        return Err(format!("Failed to {} on the inherited socket: {}", what, io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+fn duplicate(fd: c_int) -> Result<Socket, String> {
+    let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    check(copy, "duplicate it")?;
+    Ok(Socket(copy))
+}
+
+// Make sure `fd` is a raw packet socket before anything is sent on it.
+fn check_packet_socket(fd: c_int) -> Result<(), String> {
+    let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+    if unsafe { libc::getsockname(fd, &mut address as *mut _ as *mut libc::sockaddr, &mut len) } < 0 {
+        return Err(format!("File descriptor {} is not an open socket: {}", fd, io::Error::last_os_error()));
+    }
+    let mut kind: c_int = 0;
+    let mut len = mem::size_of::<c_int>() as libc::socklen_t;
+    check(unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_TYPE, &mut kind as *mut _ as *mut libc::c_void, &mut len) }, "read its type")?;
+    if address.sll_family as c_int != libc::AF_PACKET || kind != libc::SOCK_RAW {
+        return Err(format!("File descriptor {} is not a raw AF_PACKET socket", fd));
+    }
+    Ok(())
+}
+
+// A packet socket for fd_channel, opened up front while the process may
+// still have root. It is bound to nothing, so it queues no frames until
+// a channel binds it to an interface. The caller owns the descriptor.
+pub fn open_packet_socket() -> Result<c_int, String> {
+    let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(format!("Failed to open a packet socket: {}. Ensure you’re running with sudo.", io::Error::last_os_error()));
+    }
+    Ok(fd)
+}
+
+pub(crate) fn fd_channel(fd: c_int, interface: &NetworkInterface, tuning: &ChannelTuning) -> Result<Channel, String> {
+    // Frames are sent straight from their own buffer; there is no send
+    // buffer to size
+    if tuning.tx_buffer.is_some() {
+        return Err("A send buffer size does not apply to an inherited socket".to_string());
+    }
+    check_packet_socket(fd)?;
+    let rx = duplicate(fd)?;
+
+    // Every frame on the scanning interface, whatever it was bound to before
+    let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+    address.sll_family = libc::AF_PACKET as u16;
+    address.sll_protocol = (libc::ETH_P_ALL as u16).to_be();
+    address.sll_ifindex = interface.index as c_int;
+    let len = mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+    check(unsafe { libc::bind(rx.0, &address as *const _ as *const libc::sockaddr, len) }, "bind it to the interface")?;
+
+    // Like pnet's own channels, so passive mode hears LLDP and the rest.
+    // The membership belongs to the socket, which outlives this channel,
+    // so the receiver drops it again.
+    check(promiscuous(rx.0, interface.index as c_int, libc::PACKET_ADD_MEMBERSHIP), "enable promiscuous mode")?;
+
+    let tx = duplicate(rx.0)?;
+    let receiver = FdReceiver {
+        socket: rx,
+        ifindex: interface.index as c_int,
+        buffer: vec![0; tuning.rx_buffer.unwrap_or(4096)],
+        timeout: tuning.read_timeout,
+    };
+    Ok((Box::new(FdSender(tx)), Box::new(receiver)))
+}
+
+fn promiscuous(fd: c_int, ifindex: c_int, option: c_int) -> c_int {
+    let mut membership: libc::packet_mreq = unsafe { mem::zeroed() };
+    membership.mr_ifindex = ifindex;
+    membership.mr_type = libc::PACKET_MR_PROMISC as u16;
+    unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_PACKET,
+            option,
+            &membership as *const _ as *const libc::c_void,
+            mem::size_of::<libc::packet_mreq>() as libc::socklen_t,
+        )
+    }
+}
+
+struct FdSender(Socket);
+
+impl DataLinkSender for FdSender {
+    fn build_and_send(&mut self, num_packets: usize, packet_size: usize, func: &mut dyn FnMut(&mut [u8])) -> Option<io::Result<()>> {
+        let mut packet = vec![0; packet_size];
+        for _ in 0..num_packets {
+            func(&mut packet);
+            if let Some(Err(e)) = self.send_to(&packet, None) {
+                return Some(Err(e));
+            }
+        }
+        Some(Ok(()))
+    }
+
+    // The socket is bound to the scanning interface, which is where
+    // everything goes
+    fn send_to(&mut self, packet: &[u8], _dst: Option<NetworkInterface>) -> Option<io::Result<()>> {
+        let sent = unsafe { libc::send(self.0 .0, packet.as_ptr() as *const libc::c_void, packet.len(), 0) };
+        Some(if sent < 0 { Err(io::Error::last_os_error()) } else { Ok(()) })
+    }
+}
+
+struct FdReceiver {
+    socket: Socket,
+    ifindex: c_int,
+    buffer: Vec<u8>,
+    timeout: Duration,
+}
+
+impl Drop for FdReceiver {
+    fn drop(&mut self) {
+        promiscuous(self.socket.0, self.ifindex, libc::PACKET_DROP_MEMBERSHIP);
+    }
+}
+
+impl DataLinkReceiver for FdReceiver {
+    fn next(&mut self) -> io::Result<&[u8]> {
+        let mut poll = libc::pollfd { fd: self.socket.0, events: libc::POLLIN, revents: 0 };
+        let ready = unsafe { libc::poll(&mut poll, 1, self.timeout.as_millis().min(c_int::MAX as u128) as c_int) };
+        if ready < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if ready == 0 {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out"));
+        }
+        let len = unsafe { libc::recv(self.socket.0, self.buffer.as_mut_ptr() as *mut libc::c_void, self.buffer.len(), 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(&self.buffer[..len as usize])
+    }
+}
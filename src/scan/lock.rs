@@ -0,0 +1,88 @@
+// One scan per interface at a time. Two sweeps interleaved on one segment
+// double the load and answer each other's requests, which muddles reply
+// counts and round-trip times. Within the process a list of interfaces in
+// use takes care of it; with ScanConfig::lock_dir a lock file per interface
+// extends that to other processes. The kernel releases the file lock when
+// the process exits, so a crashed scan never leaves the interface locked.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+static IN_USE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Held for the duration of a scan; dropping it releases the interface.
+pub(crate) struct InterfaceLock {
+    interface: String,
+    _file: Option<File>,
+}
+
+impl InterfaceLock {
+    pub(crate) fn acquire(interface: &str, dir: Option<&Path>) -> Result<Self, String> {
+        {
+            let mut in_use = IN_USE.lock().unwrap_or_else(PoisonError::into_inner);
+            if in_use.iter().any(|name| name == interface) {
+                return Err(format!("A scan is already running on {}", interface));
+            }
+            in_use.push(interface.to_string());
+        }
+        let mut lock = InterfaceLock { interface: interface.to_string(), _file: None };
+        if let Some(dir) = dir {
+            lock._file = Some(lock_file(&lock_path(dir, interface), interface)?);
+        }
+        Ok(lock)
+    }
+}
+
+impl Drop for InterfaceLock {
+    fn drop(&mut self) {
+        let mut in_use = IN_USE.lock().unwrap_or_else(PoisonError::into_inner);
+        in_use.retain(|name| *name != self.interface);
+    }
+}
+
+// Windows device names carry backslashes and braces
+fn lock_path(dir: &Path, interface: &str) -> PathBuf {
+    let name: String = interface
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    dir.join(format!("arp_scan-{}.lock", name))
+}
+
+#[cfg(unix)]
+fn lock_file(path: &Path, interface: &str) -> Result<File, String> {
+    use std::io::{ErrorKind, Read, Seek, Write};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let failed = |e: std::io::Error| format!("Failed to open lock file {}: {}", path.display(), e);
+    // Left behind by another user, it can still be locked read-only
+    let mut file = match File::options().read(true).write(true).create(true).truncate(false).mode(0o644).open(path) {
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => File::open(path).map_err(failed)?,
+        opened => opened.map_err(failed)?,
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = std::io::Error::last_os_error();
+        if error.kind() != ErrorKind::WouldBlock {
+            return Err(format!("Failed to lock {}: {}", path.display(), error));
+        }
+        let mut pid = String::new();
+        let _ = file.read_to_string(&mut pid);
+        let holder = match pid.trim() {
+            "" => String::new(),
+            pid => format!(" (pid {})", pid),
+        };
+        return Err(format!("Another process{} is scanning on {} (lock file {})", holder, interface, path.display()));
+    }
+    // The pid is only for the message above; a read-only file goes without
+    if file.set_len(0).is_ok() && file.rewind().is_ok() {
+        let _ = writeln!(file, "{}", std::process::id());
+    }
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn lock_file(_path: &Path, _interface: &str) -> Result<File, String> {
+    Err("Lock files are only supported on Unix".to_string())
+}
@@ -0,0 +1,6 @@
+// Version of the JSON output layout. Fields may be added without a bump,
+// but renaming, removing or retyping a field requires a new version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+// JSON Schema document describing the output for SCHEMA_VERSION.
+pub const JSON_SCHEMA: &str = include_str!("files/scan.schema.json");
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use pnet::util::MacAddr;
+
+use arp_scan::vendor::{PrefixDb, VendorDb};
+
+// Scanner side of the veth pair and its address
+const SCANNER: (&str, &str) = ("st0", "198.51.100.1/24");
+// Responder side; it has no address of its own
+const RESPONDER: &str = "st1";
+// Addresses the responder answers for. The first MAC carries a real OUI so
+// the vendor lookup has something to find.
+const HOSTS: [(Ipv4Addr, [u8; 6]); 3] = [
+    (Ipv4Addr::new(198, 51, 100, 10), [0x00, 0x00, 0x0c, 0x51, 0x7e, 0x57]),
+    (Ipv4Addr::new(198, 51, 100, 20), [0x02, 0x00, 0x00, 0x51, 0x7e, 0x14]),
+    (Ipv4Addr::new(198, 51, 100, 30), [0x02, 0x00, 0x00, 0x51, 0x7e, 0x1e]),
+];
+// Probed too, but nobody answers for them
+const SILENT: [Ipv4Addr; 2] = [Ipv4Addr::new(198, 51, 100, 40), Ipv4Addr::new(198, 51, 100, 50)];
+
+enum Outcome {
+    Ok(String),
+    Skip(String),
+    Fail(String),
+}
+
+struct Report {
+    failed: usize,
+    passed: usize,
+}
+
+impl Report {
+    fn record(&mut self, check: &str, outcome: Outcome) {
+        match outcome {
+            Outcome::Ok(detail) => {
+                self.passed += 1;
+                println!("[ ok ] {}: {}", check, detail);
+            }
+            Outcome::Skip(detail) => println!("[skip] {}: {}", check, detail),
+            Outcome::Fail(detail) => {
+                self.failed += 1;
+                println!("[FAIL] {}: {}", check, detail);
+            }
+        }
+    }
+}
+
+fn expected() -> BTreeMap<Ipv4Addr, MacAddr> {
+    HOSTS.iter().map(|(ip, mac)| (*ip, MacAddr::from(*mac))).collect()
+}
+
+// Set up a veth pair inside a throwaway network namespace, answer ARP on
+// one end and scan from the other, then check every stage from the frames
+// on the wire to the saved results. Nothing outside the namespace is
+// touched, and it is removed again afterwards.
+#[cfg(target_os = "linux")]
+pub fn run(vendors: &PrefixDb) -> Result<(), String> {
+    let mut report = Report { failed: 0, passed: 0 };
+    let namespace = match linux::Namespace::create() {
+        Ok(namespace) => namespace,
+        Err(e) => {
+            report.record("create namespace", Outcome::Fail(e));
+            return Err("Self-test could not set up its test network (it needs root or CAP_NET_ADMIN)".to_string());
+        }
+    };
+    report.record("create namespace", Outcome::Ok(format!("{} with veth pair {}/{}", namespace.name, SCANNER.0, RESPONDER)));
+
+    let scan = match linux::scan_against_responder(&namespace) {
+        Ok(scan) => scan,
+        Err(e) => {
+            report.record("scan", Outcome::Fail(e));
+            return Err("Self-test failed".to_string());
+        }
+    };
+    drop(namespace);
+
+    let found: BTreeMap<Ipv4Addr, MacAddr> = scan.replies.values().map(|reply| (reply.ip, reply.mac)).collect();
+    report.record("send", match scan.send_failures.first() {
+        None => Outcome::Ok(format!("{} probes sent", scan.probes.len())),
+        Some(failure) => Outcome::Fail(format!("{} of {} probes failed: {}", scan.send_failures.len(), scan.probes.len(), failure.error)),
+    });
+    report.record("receive", if found == expected() {
+        Outcome::Ok(format!("all {} simulated hosts answered with the right MAC", HOSTS.len()))
+    } else {
+        let found: Vec<String> = found.iter().map(|(ip, mac)| format!("{} {}", ip, mac)).collect();
+        Outcome::Fail(format!("expected {} hosts, got [{}]", HOSTS.len(), found.join(", ")))
+    });
+    let phantoms: Vec<String> = SILENT.iter().filter(|ip| scan.replies.contains_key(ip)).map(Ipv4Addr::to_string).collect();
+    report.record("silent addresses", if phantoms.is_empty() {
+        Outcome::Ok(format!("{} unused addresses stayed unanswered", SILENT.len()))
+    } else {
+        Outcome::Fail(format!("replies for addresses nobody owns: {}", phantoms.join(", ")))
+    });
+    report.record("parse", match scan.replies.values().find(|reply| reply.eth_source_mismatch() || reply.padding_nonzero() > 0) {
+        None => Outcome::Ok("reply frames decoded cleanly".to_string()),
+        Some(reply) => Outcome::Fail(format!("reply from {} decoded with anomalies", reply.ip)),
+    });
+
+    let mac = MacAddr::from(HOSTS[0].1);
+    report.record("vendor lookup", match vendors.lookup(&mac) {
+        Some(vendor) if vendor.contains("Cisco") => Outcome::Ok(format!("{} is {}", mac, vendor)),
+        Some(vendor) => Outcome::Fail(format!("{} looked up as '{}', expected Cisco", mac, vendor)),
+        None if vendors.is_empty() => Outcome::Skip("no vendor database found".to_string()),
+        None => Outcome::Fail(format!("{} not found in the vendor database", mac)),
+    });
+
+    report.record("snapshot", check_snapshot(scan, &found));
+
+    println!();
+    if report.failed > 0 {
+        return Err(format!("Self-test failed: {} of {} checks failed", report.failed, report.failed + report.passed));
+    }
+    println!("Self-test passed ({} checks)", report.passed);
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn run(_vendors: &PrefixDb) -> Result<(), String> {
+    Err("self-test needs Linux network namespaces".to_string())
+}
+
+// Results survive being written out and read back.
+#[cfg(target_os = "linux")]
+fn check_snapshot(scan: arp_scan::scan::ScanResult, found: &BTreeMap<Ipv4Addr, MacAddr>) -> Outcome {
+    use arp_scan::meta::ScanMeta;
+    use arp_scan::snapshot::Snapshot;
+
+    let snapshot = Snapshot { meta: ScanMeta::from_scan(&scan), scans: vec![scan] };
+    match Snapshot::parse(&snapshot.to_text()) {
+        Ok(loaded) => {
+            let reloaded: BTreeMap<Ipv4Addr, MacAddr> = loaded.scans.iter()
+                .flat_map(|scan| scan.replies.values())
+                .map(|reply| (reply.ip, reply.mac))
+                .collect();
+            if &reloaded == found {
+                Outcome::Ok("results survived a save and load".to_string())
+            } else {
+                Outcome::Fail(format!("{} hosts saved, {} loaded back", found.len(), reloaded.len()))
+            }
+        }
+        Err(e) => Outcome::Fail(e),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::net::Ipv4Addr;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use pnet::datalink::{self, Channel, Config};
+
+    use arp_scan::interfaces;
+    use arp_scan::packet::{self, ArpFrame, ARP_FRAME_LEN, ARP_REPLY, ARP_REQUEST};
+    use arp_scan::scan::{scan_interfaces, ScanConfig, ScanResult};
+
+    use super::{HOSTS, RESPONDER, SCANNER, SILENT};
+
+    pub struct Namespace {
+        pub name: String,
+    }
+
+    fn ip(args: &[&str]) -> Result<(), String> {
+        let output = Command::new("ip")
+            .args(args)
+            .output()
+            .map_err(|e| format!("cannot run ip (iproute2): {}", e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!("ip {}: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()))
+        }
+    }
+
+    impl Namespace {
+        pub fn create() -> Result<Self, String> {
+            let name = format!("arp_scan-selftest-{}", std::process::id());
+            ip(&["netns", "add", &name])?;
+            // From here on Drop cleans up, whatever fails next
+            let namespace = Namespace { name };
+            let name = namespace.name.as_str();
+            ip(&["-n", name, "link", "add", SCANNER.0, "type", "veth", "peer", "name", RESPONDER])?;
+            ip(&["-n", name, "addr", "add", SCANNER.1, "dev", SCANNER.0])?;
+            ip(&["-n", name, "link", "set", SCANNER.0, "up"])?;
+            ip(&["-n", name, "link", "set", RESPONDER, "up"])?;
+            Ok(namespace)
+        }
+
+        // Move the calling thread into the namespace. Only that thread
+        // moves; the rest of the process stays where it was.
+        fn enter(&self) -> Result<(), String> {
+            interfaces::enter_netns(&self.name)
+        }
+    }
+
+    impl Drop for Namespace {
+        fn drop(&mut self) {
+            if let Err(e) = ip(&["netns", "del", &self.name]) {
+                eprintln!("Warning: could not remove test namespace: {}", e);
+            }
+        }
+    }
+
+    // Answer requests for HOSTS on the responder end until `stop` is set.
+    fn respond(namespace: &Namespace, ready: mpsc::Sender<Result<(), String>>, stop: &AtomicBool) {
+        let channel = namespace.enter().and_then(|()| {
+            let interface = datalink::interfaces()
+                .into_iter()
+                .find(|interface| interface.name == RESPONDER)
+                .ok_or_else(|| format!("{} not found", RESPONDER))?;
+            let config = Config { read_timeout: Some(Duration::from_millis(50)), ..Config::default() };
+            match datalink::channel(&interface, config) {
+                Ok(Channel::Ethernet(tx, rx)) => Ok((tx, rx)),
+                Ok(_) => Err("unsupported channel type".to_string()),
+                Err(e) => Err(format!("responder channel: {}", e)),
+            }
+        });
+        let (mut tx, mut rx) = match channel {
+            Ok(channel) => channel,
+            Err(e) => {
+                let _ = ready.send(Err(e));
+                return;
+            }
+        };
+        let _ = ready.send(Ok(()));
+
+        let mut buf = [0u8; ARP_FRAME_LEN];
+        while !stop.load(Ordering::Relaxed) {
+            let Ok(frame) = rx.next() else {
+                continue;
+            };
+            let Some(request) = packet::parse_arp_frame(frame) else {
+                continue;
+            };
+            if request.arp.operation != ARP_REQUEST {
+                continue;
+            }
+            let Some((ip, mac)) = HOSTS.iter().find(|(ip, _)| *ip == request.arp.target_ip) else {
+                continue;
+            };
+            let reply = ArpFrame {
+                eth_destination: request.eth_source,
+                eth_source: *mac,
+                operation: ARP_REPLY,
+                sender_mac: *mac,
+                sender_ip: *ip,
+                target_mac: request.arp.sender_mac,
+                target_ip: request.arp.sender_ip,
+            };
+            if let Ok(len) = reply.write(&mut buf) {
+                tx.send_to(&buf[..len], None);
+            }
+        }
+    }
+
+    // Start the responder, scan HOSTS and SILENT from the other end, then
+    // stop the responder again.
+    pub fn scan_against_responder(namespace: &Namespace) -> Result<ScanResult, String> {
+        let stop = AtomicBool::new(false);
+        thread::scope(|scope| {
+            let (ready_tx, ready_rx) = mpsc::channel();
+            scope.spawn(|| respond(namespace, ready_tx, &stop));
+            let started = ready_rx.recv().map_err(|_| "responder exited early".to_string()).and_then(|ready| ready);
+            let result = started.and_then(|()| {
+                scope.spawn(|| {
+                    namespace.enter()?;
+                    let targets: Vec<Ipv4Addr> = HOSTS.iter().map(|(ip, _)| *ip).chain(SILENT).collect();
+                    let config = ScanConfig { timeout: Duration::from_millis(500), ..ScanConfig::default() };
+                    let mut scans = scan_interfaces(&targets, &config, &[SCANNER.0.to_string()])?;
+                    scans.pop().ok_or_else(|| "scan returned no results".to_string())
+                })
+                .join()
+                .map_err(|_| "scanner thread panicked".to_string())?
+            });
+            stop.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+}
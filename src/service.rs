@@ -0,0 +1,123 @@
+// Running the monitor as a systemd service, so it survives reboots. The
+// unit runs it as a dynamic user holding nothing but CAP_NET_RAW, as
+// Type=notify with a watchdog (see heartbeat.rs), and restarts it when it
+// fails.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+pub const UNIT_NAME: &str = "arp_scan.service";
+const UNIT_DIR: &str = "/etc/systemd/system";
+// RuntimeDirectory=, so the dynamic user can create the socket in it
+pub const CONTROL_SOCKET: &str = "/run/arp_scan/arp_scan.sock";
+
+// ExecStart= splits on whitespace and expands % specifiers and $ variables,
+// so anything unusual is quoted and those are doubled.
+fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';')) {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// The monitor options as they go on the command line. --control alone
+// would pick a socket from the service's environment, which ctl run from
+// a shell wouldn't find, so the socket is named.
+fn monitor_args(args: &[String]) -> Vec<String> {
+    let mut args = args.to_vec();
+    if args.iter().any(|arg| arg == "--control") && !args.iter().any(|arg| arg == "--control-socket") {
+        args.extend(["--control-socket".to_string(), CONTROL_SOCKET.to_string()]);
+    }
+    args
+}
+
+pub fn unit(exe: &Path, args: &[String]) -> String {
+    let mut command = vec![quote(&exe.to_string_lossy()), "monitor".to_string()];
+    command.extend(monitor_args(args).iter().map(|arg| quote(arg)));
+    format!(
+        "[Unit]
+Description=arp_scan network monitor
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=notify
+ExecStart={}
+Restart=on-failure
+RestartSec=10
+WatchdogSec=60
+DynamicUser=yes
+RuntimeDirectory=arp_scan
+AmbientCapabilities=CAP_NET_RAW
+CapabilityBoundingSet=CAP_NET_RAW
+NoNewPrivileges=yes
+
+[Install]
+WantedBy=multi-user.target
+",
+        command.join(" ")
+    )
+}
+
+fn systemctl(args: &[&str]) -> Result<(), String> {
+    let status = process::Command::new("systemctl")
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to run systemctl: {}", e))?;
+    if !status.success() {
+        return Err(format!("systemctl {} failed ({})", args.join(" "), status));
+    }
+    Ok(())
+}
+
+fn unit_path() -> PathBuf {
+    Path::new(UNIT_DIR).join(UNIT_NAME)
+}
+
+// Write the unit and start it now and at every boot, or with `print` only
+// show it.
+pub fn install(args: &[String], print: bool) -> Result<(), String> {
+    let exe = env::current_exe()
+        .and_then(fs::canonicalize)
+        .map_err(|e| format!("Cannot tell where arp_scan is installed: {}", e))?;
+    let unit = unit(&exe, args);
+    if print {
+        print!("{}", unit);
+        return Ok(());
+    }
+    if !cfg!(target_os = "linux") {
+        return Err("install-service sets up a systemd unit and needs Linux; use --print to see it".to_string());
+    }
+    let path = unit_path();
+    fs::write(&path, unit).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => format!("{}: {} (run as root)", path.display(), e),
+        _ => format!("{}: {}", path.display(), e),
+    })?;
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", UNIT_NAME])?;
+    println!("Installed and started {} ({})", UNIT_NAME, path.display());
+    println!("Follow it with: journalctl -u {} -f", UNIT_NAME);
+    if monitor_args(args).iter().any(|arg| arg == CONTROL_SOCKET) {
+        println!("Control it with: arp_scan ctl status --control-socket {}", CONTROL_SOCKET);
+    }
+    Ok(())
+}
+
+pub fn uninstall() -> Result<(), String> {
+    if !cfg!(target_os = "linux") {
+        return Err("uninstall-service removes a systemd unit and needs Linux".to_string());
+    }
+    let path = unit_path();
+    if !path.exists() {
+        return Err(format!("{} is not installed ({} not found)", UNIT_NAME, path.display()));
+    }
+    systemctl(&["disable", "--now", UNIT_NAME])?;
+    fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    systemctl(&["daemon-reload"])?;
+    println!("Stopped and removed {}", UNIT_NAME);
+    Ok(())
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use pnet_base::MacAddr;
+
+use crate::meta::{new_scan_id, ScanMeta};
+use crate::packet::ArpHeader;
+use crate::scan::{HostReply, ReplyKind, Roam, ScanResult, SendFailure};
+
+const HEADER: &str = "arp_scan-snapshot";
+const VERSION: u32 = 1;
+
+// Complete results of one run, saved with --save so they can be printed
+// again later in any format without rescanning.
+//
+// The file is plain text, one tab-separated record per line. The first
+// line is "arp_scan-snapshot<TAB>1". Then come the meta records (scan_id,
+// started, finished, interface, source_ip, source_mac, target, setting),
+// and a "scan" record for each interface, followed by that interface's
+// "host", "roam" and "failure" records. Times are nanoseconds since the Unix epoch.
+// Readers skip record types they don't know, so later versions can add
+// records without breaking older readers. Probe timings are not kept.
+pub struct Snapshot {
+    pub meta: ScanMeta,
+    pub scans: Vec<ScanResult>,
+}
+
+fn nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+}
+
+fn from_nanos(text: &str) -> Result<SystemTime, String> {
+    let ns: u64 = text.parse().map_err(|_| format!("invalid time '{}'", text))?;
+    Ok(UNIX_EPOCH + Duration::from_nanos(ns))
+}
+
+// Fields can't contain the separators.
+fn clean(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(format!("invalid hex '{}'", text));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(&text[at..at + 2], 16).map_err(|_| format!("invalid hex '{}'", text)))
+        .collect()
+}
+
+fn field<'a>(fields: &[&'a str], index: usize) -> Result<&'a str, String> {
+    fields.get(index).copied().ok_or_else(|| format!("'{}' record is missing fields", fields[0]))
+}
+
+fn parse_field<T: std::str::FromStr>(fields: &[&str], index: usize) -> Result<T, String> {
+    let text = field(fields, index)?;
+    text.parse().map_err(|_| format!("invalid {} field '{}'", fields[0], text))
+}
+
+fn host_line(scan: &ScanResult, reply: &HostReply) -> String {
+    let responses = scan.responses.get(&reply.ip).copied().unwrap_or(0);
+    format!(
+        "host\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        reply.ip,
+        reply.mac,
+        reply.rtt.map_or("-".to_string(), |rtt| rtt.as_nanos().to_string()),
+        clean(&reply.interface),
+        reply.eth_source,
+        hex(&reply.arp.to_bytes()),
+        nanos(reply.received),
+        match reply.kind {
+            ReplyKind::Reply => "reply",
+            ReplyKind::Request => "request",
+        },
+        reply.frame_len,
+        hex(&reply.padding),
+        responses,
+        reply.hostname.as_deref().map_or("-".to_string(), clean),
+        reply.duplicates,
+        if reply.duplicate_macs.is_empty() {
+            "-".to_string()
+        } else {
+            reply.duplicate_macs.iter().map(MacAddr::to_string).collect::<Vec<_>>().join(",")
+        },
+        reply.attempt.map_or("-".to_string(), |attempt| attempt.to_string())
+    )
+}
+
+fn parse_host(fields: &[&str]) -> Result<(HostReply, u32), String> {
+    let rtt = match field(fields, 3)? {
+        "-" => None,
+        ns => Some(Duration::from_nanos(ns.parse().map_err(|_| format!("invalid rtt '{}'", ns))?)),
+    };
+    let arp = ArpHeader::parse(&unhex(field(fields, 6)?)?).ok_or("truncated ARP header")?;
+    let kind = match field(fields, 8)? {
+        "reply" => ReplyKind::Reply,
+        "request" => ReplyKind::Request,
+        other => return Err(format!("invalid reply kind '{}'", other)),
+    };
+    let hostname = match field(fields, 12)? {
+        "-" => None,
+        name => Some(name.to_string()),
+    };
+    // Older snapshots end at the hostname
+    let duplicates = match fields.get(13) {
+        Some(_) => parse_field(fields, 13)?,
+        None => 0,
+    };
+    let duplicate_macs = match fields.get(14).copied() {
+        None | Some("-") => Vec::new(),
+        Some(macs) => macs.split(',')
+            .map(|mac| mac.parse().map_err(|_| format!("invalid MAC '{}'", mac)))
+            .collect::<Result<_, String>>()?,
+    };
+    let attempt = match fields.get(15).copied() {
+        None | Some("-") => None,
+        Some(_) => Some(parse_field(fields, 15)?),
+    };
+    let reply = HostReply {
+        ip: parse_field(fields, 1)?,
+        mac: parse_field(fields, 2)?,
+        rtt,
+        interface: field(fields, 4)?.to_string(),
+        eth_source: parse_field(fields, 5)?,
+        arp,
+        received: from_nanos(field(fields, 7)?)?,
+        kind,
+        frame_len: parse_field(fields, 9)?,
+        padding: unhex(field(fields, 10)?)?,
+        hostname,
+        note: None,
+        switch_port: None,
+        duplicates,
+        duplicate_macs,
+        attempt,
+        roamed_from: Vec::new(),
+    };
+    Ok((reply, parse_field(fields, 11)?))
+}
+
+fn empty_scan(interface: String, source_ip: Ipv4Addr, source_mac: MacAddr, started: SystemTime) -> ScanResult {
+    ScanResult {
+        interface,
+        source_ip,
+        source_mac,
+        started,
+        finished: started,
+        replies: HashMap::new(),
+        rounds: 1,
+        responses: HashMap::new(),
+        rtts: HashMap::new(),
+        send_failures: Vec::new(),
+        probes: Vec::new(),
+        unsent: 0,
+        reduced_pps: None,
+        receive_errors: 0,
+        cancelled: false,
+    }
+}
+
+// Where a merged reply was heard: which snapshot, on which interface, and
+// when that scan ended.
+struct Segment<'a> {
+    snapshot: usize,
+    interface: &'a str,
+    finished: SystemTime,
+}
+
+impl Segment<'_> {
+    fn same(&self, other: &Segment) -> bool {
+        self.snapshot == other.snapshot && self.interface == other.interface
+    }
+}
+
+// Fold sightings of one MAC on different segments into the latest, as
+// long as each came within `window` of the next. Times are compared as
+// instants, so snapshots from agents in different time zones line up. A
+// MAC with several addresses on one segment, or still answering on one
+// segment while it turned up on another (a router with one MAC on every
+// VLAN), is a multihomed host and is left alone.
+fn correlate_roaming(replies: &mut HashMap<Ipv4Addr, HostReply>, segments: &HashMap<Ipv4Addr, Segment>, window: Duration) {
+    let mut by_mac: HashMap<MacAddr, Vec<Ipv4Addr>> = HashMap::new();
+    for reply in replies.values() {
+        by_mac.entry(reply.mac).or_default().push(reply.ip);
+    }
+    for mut ips in by_mac.into_values() {
+        if ips.len() < 2 {
+            continue;
+        }
+        ips.sort_by_key(|ip| (replies[ip].received, *ip));
+        let latest = ips[ips.len() - 1];
+        let mut moved = Vec::new();
+        let mut next = latest;
+        for &ip in ips.iter().rev().skip(1) {
+            let gap = replies[&next].received.duration_since(replies[&ip].received).unwrap_or_default();
+            if segments[&ip].same(&segments[&next]) || gap > window {
+                break;
+            }
+            if segments[&ip].finished >= replies[&next].received {
+                break;
+            }
+            moved.push(ip);
+            next = ip;
+        }
+        let mut roamed_from = Vec::new();
+        for ip in moved.into_iter().rev() {
+            if let Some(earlier) = replies.remove(&ip) {
+                roamed_from.extend(earlier.roamed_from);
+                roamed_from.push(Roam { interface: earlier.interface, ip, at: earlier.received });
+            }
+        }
+        if let Some(reply) = replies.get_mut(&latest) {
+            roamed_from.append(&mut reply.roamed_from);
+            roamed_from.sort_by_key(|roam| roam.at);
+            reply.roamed_from = roamed_from;
+        }
+    }
+}
+
+// Apply one record to the snapshot being read.
+fn read_record(fields: &[&str], meta: &mut ScanMeta, scans: &mut Vec<ScanResult>) -> Result<(), String> {
+    match fields[0] {
+        "scan_id" => meta.scan_id = field(fields, 1)?.to_string(),
+        "started" => meta.started = from_nanos(field(fields, 1)?)?,
+        "finished" => meta.finished = from_nanos(field(fields, 1)?)?,
+        "interface" => meta.interface = field(fields, 1)?.to_string(),
+        "source_ip" => meta.source_ip = parse_field(fields, 1)?,
+        "source_mac" => meta.source_mac = parse_field(fields, 1)?,
+        "target" => meta.targets.push(field(fields, 1)?.to_string()),
+        "setting" => meta.settings.push((field(fields, 1)?.to_string(), field(fields, 2)?.to_string())),
+        "scan" => {
+            let mut scan = empty_scan(
+                field(fields, 1)?.to_string(),
+                parse_field(fields, 2)?,
+                parse_field(fields, 3)?,
+                from_nanos(field(fields, 4)?)?,
+            );
+            scan.finished = from_nanos(field(fields, 5)?)?;
+            scan.rounds = parse_field(fields, 6)?;
+            scans.push(scan);
+        }
+        "host" => {
+            let scan = scans.last_mut().ok_or("host record before any scan record")?;
+            let (reply, responses) = parse_host(fields)?;
+            scan.responses.insert(reply.ip, responses);
+            scan.replies.insert(reply.ip, reply);
+        }
+        "roam" => {
+            let ip: Ipv4Addr = parse_field(fields, 1)?;
+            let reply = scans.last_mut()
+                .and_then(|scan| scan.replies.get_mut(&ip))
+                .ok_or("roam record before its host record")?;
+            reply.roamed_from.push(Roam {
+                interface: field(fields, 2)?.to_string(),
+                ip: parse_field(fields, 3)?,
+                at: from_nanos(field(fields, 4)?)?,
+            });
+        }
+        "failure" => {
+            let scan = scans.last_mut().ok_or("failure record before any scan record")?;
+            scan.send_failures.push(SendFailure {
+                target: parse_field(fields, 1)?,
+                error: field(fields, 2)?.to_string(),
+            });
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+impl Snapshot {
+    pub fn to_text(&self) -> String {
+        let meta = &self.meta;
+        let mut text = format!("{}\t{}\n", HEADER, VERSION);
+        text.push_str(&format!("scan_id\t{}\n", clean(&meta.scan_id)));
+        text.push_str(&format!("started\t{}\n", nanos(meta.started)));
+        text.push_str(&format!("finished\t{}\n", nanos(meta.finished)));
+        text.push_str(&format!("interface\t{}\n", clean(&meta.interface)));
+        text.push_str(&format!("source_ip\t{}\n", meta.source_ip));
+        text.push_str(&format!("source_mac\t{}\n", meta.source_mac));
+        for target in &meta.targets {
+            text.push_str(&format!("target\t{}\n", clean(target)));
+        }
+        for (key, value) in &meta.settings {
+            text.push_str(&format!("setting\t{}\t{}\n", clean(key), clean(value)));
+        }
+        for scan in &self.scans {
+            text.push_str(&format!(
+                "scan\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                clean(&scan.interface),
+                scan.source_ip,
+                scan.source_mac,
+                nanos(scan.started),
+                nanos(scan.finished),
+                scan.rounds
+            ));
+            let mut replies: Vec<&HostReply> = scan.replies.values().collect();
+            replies.sort_by_key(|reply| reply.ip);
+            for reply in replies {
+                text.push_str(&host_line(scan, reply));
+                for roam in &reply.roamed_from {
+                    text.push_str(&format!("roam\t{}\t{}\t{}\t{}\n", reply.ip, clean(&roam.interface), roam.ip, nanos(roam.at)));
+                }
+            }
+            for failure in &scan.send_failures {
+                text.push_str(&format!("failure\t{}\t{}\n", failure.target, clean(&failure.error)));
+            }
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        match lines.next().map(|(_, line)| line.trim_end_matches('\r').split_once('\t')) {
+            Some(Some((HEADER, version))) => {
+                let version: u32 = version.parse().map_err(|_| "line 1: invalid snapshot version".to_string())?;
+                if version > VERSION {
+                    return Err(format!(
+                        "snapshot version {} is newer than this tool supports ({})",
+                        version, VERSION
+                    ));
+                }
+            }
+            _ => return Err("not an arp_scan snapshot".to_string()),
+        }
+
+        let mut meta = ScanMeta {
+            scan_id: String::new(),
+            started: UNIX_EPOCH,
+            finished: UNIX_EPOCH,
+            interface: String::new(),
+            source_ip: Ipv4Addr::UNSPECIFIED,
+            source_mac: MacAddr::zero(),
+            targets: Vec::new(),
+            settings: Vec::new(),
+        };
+        let mut scans: Vec<ScanResult> = Vec::new();
+        for (number, line) in lines {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            read_record(&fields, &mut meta, &mut scans).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        if scans.is_empty() {
+            return Err("snapshot contains no scans".to_string());
+        }
+        Ok(Snapshot { meta, scans })
+    }
+
+    // Combine several snapshots into one, for example one per segment or
+    // site. Hosts are keyed by address; when several snapshots have the
+    // same address, the most recent reply wins. Every host keeps the
+    // interface it was seen on, and the merge counts as a single round.
+    // A single snapshot is returned as it is, and an empty list is an error.
+    pub fn merge(snapshots: Vec<Snapshot>) -> Result<Snapshot, String> {
+        Snapshot::merge_with(snapshots, None)
+    }
+
+    // Like merge, but a MAC seen on different segments with each sighting
+    // within `roam_window` of the next is taken for one device that moved,
+    // such as a laptop going from Wi-Fi to a dock. Only the latest sighting
+    // stays, with the others in its roamed_from.
+    pub fn merge_with(mut snapshots: Vec<Snapshot>, roam_window: Option<Duration>) -> Result<Snapshot, String> {
+        if snapshots.len() == 1 {
+            return Ok(snapshots.remove(0));
+        }
+        let Some(first) = snapshots.first().map(|snapshot| &snapshot.meta) else {
+            return Err("No snapshots to merge".to_string());
+        };
+        let mut meta = ScanMeta {
+            scan_id: new_scan_id(),
+            started: first.started,
+            finished: first.finished,
+            interface: String::new(),
+            source_ip: first.source_ip,
+            source_mac: first.source_mac,
+            targets: Vec::new(),
+            settings: Vec::new(),
+        };
+        let mut interfaces: Vec<&str> = Vec::new();
+        let mut merged_from = Vec::new();
+        let mut replies: HashMap<Ipv4Addr, HostReply> = HashMap::new();
+        // Which snapshot and interface each reply came from
+        let mut segments: HashMap<Ipv4Addr, Segment> = HashMap::new();
+        let mut send_failures = Vec::new();
+        for (index, snapshot) in snapshots.iter().enumerate() {
+            meta.started = meta.started.min(snapshot.meta.started);
+            meta.finished = meta.finished.max(snapshot.meta.finished);
+            for target in &snapshot.meta.targets {
+                if !meta.targets.contains(target) {
+                    meta.targets.push(target.clone());
+                }
+            }
+            merged_from.push(snapshot.meta.scan_id.as_str());
+            for scan in &snapshot.scans {
+                if !interfaces.contains(&scan.interface.as_str()) {
+                    interfaces.push(&scan.interface);
+                }
+                for reply in scan.replies.values() {
+                    match replies.get(&reply.ip) {
+                        Some(existing) if existing.received >= reply.received => {}
+                        _ => {
+                            replies.insert(reply.ip, reply.clone());
+                            segments.insert(reply.ip, Segment { snapshot: index, interface: &scan.interface, finished: scan.finished });
+                        }
+                    }
+                }
+                send_failures.extend(scan.send_failures.iter().cloned());
+            }
+        }
+        meta.interface = interfaces.join(",");
+        meta.settings.push(("merged_from".to_string(), merged_from.join(",")));
+        if let Some(window) = roam_window {
+            correlate_roaming(&mut replies, &segments, window);
+            meta.settings.push(("roam_window_ms".to_string(), window.as_millis().to_string()));
+        }
+
+        let mut scan = empty_scan(meta.interface.clone(), meta.source_ip, meta.source_mac, meta.started);
+        scan.finished = meta.finished;
+        scan.responses = replies.keys().map(|ip| (*ip, 1)).collect();
+        scan.replies = replies;
+        scan.send_failures = send_failures;
+        Ok(Snapshot { meta, scans: vec![scan] })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Snapshot::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
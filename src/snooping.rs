@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+use pnet_base::MacAddr;
+
+// Where a host is plugged in, as reported by DHCP snooping on the switch:
+// either an exported binding table or the relay agent information (option
+// 82) the switch adds to a client's DHCP messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchPort {
+    // Switch name or MAC, when the source says
+    pub switch: Option<String>,
+    pub port: String,
+    pub vlan: Option<u16>,
+}
+
+impl fmt::Display for SwitchPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(switch) = &self.switch {
+            write!(f, "{} ", switch)?;
+        }
+        f.write_str(&self.port)?;
+        if let Some(vlan) = self.vlan {
+            write!(f, " VLAN {}", vlan)?;
+        }
+        Ok(())
+    }
+}
+
+// Option 82 as a switch inserts it. Circuit ID and remote ID are opaque in
+// RFC 3046, but most switches use the "default" encodings: a 4-byte VLAN,
+// module and port for the circuit, and the switch's base MAC for the remote
+// ID. Anything else is kept as text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayAgent {
+    pub circuit_id: Vec<u8>,
+    pub remote_id: Vec<u8>,
+}
+
+const SUBOPTION_CIRCUIT_ID: u8 = 1;
+const SUBOPTION_REMOTE_ID: u8 = 2;
+
+fn printable(bytes: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(bytes).ok()?.trim_matches('\0').trim();
+    (!text.is_empty() && text.chars().all(|c| !c.is_control())).then(|| text.to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl RelayAgent {
+    // The sub-options of an option 82 value.
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        let mut agent = RelayAgent { circuit_id: Vec::new(), remote_id: Vec::new() };
+        let mut at = 0;
+        while at < value.len() {
+            let code = value[at];
+            let len = *value.get(at + 1)? as usize;
+            let data = value.get(at + 2..at + 2 + len)?;
+            match code {
+                SUBOPTION_CIRCUIT_ID => agent.circuit_id = data.to_vec(),
+                SUBOPTION_REMOTE_ID => agent.remote_id = data.to_vec(),
+                _ => {}
+            }
+            at += 2 + len;
+        }
+        (!agent.circuit_id.is_empty()).then_some(agent)
+    }
+
+    pub fn switch_port(&self) -> SwitchPort {
+        let (port, vlan) = match self.circuit_id.as_slice() {
+            [0, 4, vlan_hi, vlan_lo, module, port] => {
+                (format!("{}/{}", module, port), Some(u16::from_be_bytes([*vlan_hi, *vlan_lo])))
+            }
+            id => (printable(id).unwrap_or_else(|| hex(id)), None),
+        };
+        let switch = match self.remote_id.as_slice() {
+            [] => None,
+            [0, 6, mac @ ..] if mac.len() == 6 => {
+                Some(MacAddr::new(mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]).to_string())
+            }
+            id => Some(printable(id).unwrap_or_else(|| hex(id))),
+        };
+        SwitchPort { switch, port, vlan }
+    }
+}
+
+// Host locations imported from a switch. Two layouts are understood:
+//
+//   * Cisco "show ip dhcp snooping binding" output, pasted as is:
+//     MacAddress  IpAddress  Lease(sec)  Type  VLAN  Interface
+//   * CSV: mac,switch,port[,vlan], one host per line
+//
+// Header, separator and summary lines are skipped, as is '#' to the end of
+// a line. The same file may cover several switches only in the CSV layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnoopingTable {
+    ports: HashMap<MacAddr, SwitchPort>,
+}
+
+impl SnoopingTable {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut ports = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let first = line.split([',', ' ', '\t']).next().unwrap_or_default();
+            // Headers, rules and "Total number of bindings" all fail here
+            let Ok(mac) = first.parse::<MacAddr>() else {
+                continue;
+            };
+            let port = if line.contains(',') {
+                parse_csv(line)
+            } else {
+                parse_binding(line)
+            };
+            let port = port.map_err(|e| format!("line {}: {}", number + 1, e))?;
+            ports.insert(mac, port);
+        }
+        if ports.is_empty() {
+            return Err("no bindings found; expected 'show ip dhcp snooping binding' output or mac,switch,port[,vlan] lines".to_string());
+        }
+        Ok(SnoopingTable { ports })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        SnoopingTable::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn get(&self, mac: &MacAddr) -> Option<&SwitchPort> {
+        self.ports.get(mac)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ports.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ports.is_empty()
+    }
+}
+
+fn parse_vlan(text: &str) -> Result<Option<u16>, String> {
+    match text.trim() {
+        "" => Ok(None),
+        vlan => vlan.parse().map(Some).map_err(|_| format!("invalid VLAN '{}'", vlan)),
+    }
+}
+
+fn parse_csv(line: &str) -> Result<SwitchPort, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    let (switch, port, vlan) = match fields.as_slice() {
+        [_, switch, port] => (switch, port, ""),
+        [_, switch, port, vlan] => (switch, port, *vlan),
+        _ => return Err("expected mac,switch,port[,vlan]".to_string()),
+    };
+    if port.is_empty() {
+        return Err("missing port".to_string());
+    }
+    Ok(SwitchPort {
+        switch: (!switch.is_empty()).then(|| switch.to_string()),
+        port: port.to_string(),
+        vlan: parse_vlan(vlan)?,
+    })
+}
+
+fn parse_binding(line: &str) -> Result<SwitchPort, String> {
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [_, ip, _lease, _kind, vlan, port, ..] if ip.parse::<Ipv4Addr>().is_ok() => Ok(SwitchPort {
+            switch: None,
+            port: port.to_string(),
+            vlan: parse_vlan(vlan)?,
+        }),
+        _ => Err("expected MacAddress IpAddress Lease Type VLAN Interface".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mac(text: &str) -> MacAddr {
+        text.parse().unwrap()
+    }
+
+    fn port(switch: Option<&str>, port: &str, vlan: Option<u16>) -> SwitchPort {
+        SwitchPort { switch: switch.map(str::to_string), port: port.to_string(), vlan }
+    }
+
+    const CISCO: &str = "\
+sw-access-02#show ip dhcp snooping binding
+MacAddress          IpAddress        Lease(sec)  Type           VLAN  Interface
+------------------  ---------------  ----------  -------------  ----  --------------------
+00:1B:21:AA:00:01   192.0.2.1        84973       dhcp-snooping   20    GigabitEthernet1/0/12
+3C:D9:2B:10:20:30   192.0.2.10       86012       dhcp-snooping   20    GigabitEthernet1/0/3
+08:00:27:12:34:56   192.0.2.23       infinite    static          30    Port-channel1
+Total number of bindings: 3
+
+sw-access-02#
+";
+
+    #[test]
+    fn parses_a_cisco_binding_table() {
+        let table = SnoopingTable::parse(CISCO).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get(&mac("00:1b:21:aa:00:01")), Some(&port(None, "GigabitEthernet1/0/12", Some(20))));
+        assert_eq!(table.get(&mac("3c:d9:2b:10:20:30")), Some(&port(None, "GigabitEthernet1/0/3", Some(20))));
+        assert_eq!(table.get(&mac("08:00:27:12:34:56")), Some(&port(None, "Port-channel1", Some(30))));
+        assert_eq!(table.get(&mac("00:1b:21:aa:00:02")), None);
+    }
+
+    #[test]
+    fn parses_csv() {
+        let csv = "# exported from the inventory\n\
+            mac,switch,port,vlan\n\
+            00:1b:21:aa:00:01,sw-core-01,Gi1/0/1,10\n\
+            3c:d9:2b:10:20:30, sw-core-01 , Gi1/0/2 ,\n\
+            08:00:27:12:34:56,,ge-0/0/7  # no switch name\n";
+        let table = SnoopingTable::parse(csv).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get(&mac("00:1b:21:aa:00:01")), Some(&port(Some("sw-core-01"), "Gi1/0/1", Some(10))));
+        assert_eq!(table.get(&mac("3c:d9:2b:10:20:30")), Some(&port(Some("sw-core-01"), "Gi1/0/2", None)));
+        assert_eq!(table.get(&mac("08:00:27:12:34:56")), Some(&port(None, "ge-0/0/7", None)));
+    }
+
+    #[test]
+    fn reports_the_broken_line() {
+        let errors = [
+            ("mac,switch,port\n00:1b:21:aa:00:01,sw-core-01,\n", "line 2: missing port"),
+            ("00:1b:21:aa:00:01,sw-core-01,Gi1/0/1,ten\n", "line 1: invalid VLAN 'ten'"),
+            ("00:1b:21:aa:00:01,sw,Gi1/0/1,10,extra\n", "line 1: expected mac,switch,port[,vlan]"),
+            ("00:1B:21:AA:00:01   192.0.2.1   84973\n", "line 1: expected MacAddress IpAddress Lease Type VLAN Interface"),
+        ];
+        for (text, error) in errors {
+            assert_eq!(SnoopingTable::parse(text), Err(error.to_string()));
+        }
+        assert!(SnoopingTable::parse("Total number of bindings: 0\n").is_err());
+    }
+
+    #[test]
+    fn decodes_relay_agent_suboptions() {
+        // Default encodings: VLAN 20, module 1, port 12; remote ID is a MAC
+        let default = [1, 6, 0, 4, 0, 20, 1, 12, 2, 8, 0, 6, 0x00, 0x1b, 0x54, 0xc2, 0x80, 0x00];
+        assert_eq!(
+            RelayAgent::parse(&default).unwrap().switch_port(),
+            port(Some("00:1b:54:c2:80:00"), "1/12", Some(20))
+        );
+        // Text IDs, as many vendors configure them
+        let mut text = vec![1, 8];
+        text.extend_from_slice(b"Gi1/0/12");
+        text.extend_from_slice(&[2, 6]);
+        text.extend_from_slice(b"sw-02\0");
+        assert_eq!(RelayAgent::parse(&text).unwrap().switch_port(), port(Some("sw-02"), "Gi1/0/12", None));
+        // Binary circuit ID in some other layout, and no remote ID
+        assert_eq!(RelayAgent::parse(&[1, 3, 0x01, 0xff, 0x10]).unwrap().switch_port(), port(None, "01ff10", None));
+        // No circuit ID, or a sub-option running past the end
+        assert_eq!(RelayAgent::parse(&[2, 2, b'a', b'b']), None);
+        assert_eq!(RelayAgent::parse(&[1, 6, 0, 4, 0]), None);
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+// Upper bounds (in milliseconds) of the latency histogram buckets. The last
+// bucket catches everything slower.
+pub const HISTOGRAM_BOUNDS_MS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    // One count per HISTOGRAM_BOUNDS_MS entry, plus the overflow bucket
+    pub histogram: Vec<usize>,
+}
+
+// Nearest-rank percentile of an already sorted, non-empty slice.
+fn percentile(sorted: &[Duration], pct: f64) -> Duration {
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl LatencyStats {
+    // Summarize a set of round-trip times. Returns None when there is
+    // nothing to summarize.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        let mut histogram = vec![0; HISTOGRAM_BOUNDS_MS.len() + 1];
+        for sample in &sorted {
+            let ms = sample.as_secs_f64() * 1000.0;
+            let bucket = HISTOGRAM_BOUNDS_MS.iter()
+                .position(|bound| ms <= *bound)
+                .unwrap_or(HISTOGRAM_BOUNDS_MS.len());
+            histogram[bucket] += 1;
+        }
+
+        let total: Duration = sorted.iter().sum();
+        Some(LatencyStats {
+            count: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: total / sorted.len() as u32,
+            p50: percentile(&sorted, 50.0),
+            p90: percentile(&sorted, 90.0),
+            p99: percentile(&sorted, 99.0),
+            histogram,
+        })
+    }
+
+    // Human-readable label for histogram bucket `i`.
+    pub fn bucket_label(i: usize) -> String {
+        match HISTOGRAM_BOUNDS_MS.get(i) {
+            Some(bound) => format!("<= {} ms", bound),
+            None => format!("> {} ms", HISTOGRAM_BOUNDS_MS[HISTOGRAM_BOUNDS_MS.len() - 1]),
+        }
+    }
+}
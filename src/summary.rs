@@ -0,0 +1,132 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::Ipv4Addr;
+
+use pnet_base::MacAddr;
+
+use crate::mac::MacAddress;
+use crate::scan::ScanResult;
+use crate::target::{CidrV4, TargetBlock};
+
+// Aggregate counts over one or more scans, for the overview rather than
+// the row-by-row table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub hosts: usize,
+    // Distinct addresses probed, per interface; zero for results loaded
+    // from a snapshot, which doesn't keep the probes
+    pub probed: usize,
+    // Most common first, ties by name
+    pub by_vendor: Vec<(String, usize)>,
+    pub by_subnet: Vec<(CidrV4, usize)>,
+    // Locally administered MACs: privacy addresses on phones and laptops,
+    // but also VMs and containers
+    pub randomized: usize,
+    // Hosts with a note attached, i.e. ones somebody has already looked at.
+    // Without notes that is none of them, so it says nothing about which
+    // hosts are new to the network.
+    pub noted: usize,
+    pub rounds: u32,
+    // With several rounds: hosts that answered every one
+    pub steady: usize,
+    // Hosts by the attempt that first got an answer, in attempt order.
+    // Many beyond the first point at a lossy segment.
+    pub by_attempt: Vec<(u32, usize)>,
+}
+
+// Counts for one target entry. Addresses covered by several entries count
+// toward the first, the one they were probed for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSummary {
+    pub label: String,
+    pub probed: usize,
+    pub hosts: usize,
+}
+
+impl BlockSummary {
+    pub fn response_rate(&self) -> Option<f64> {
+        (self.probed > 0).then(|| self.hosts as f64 * 100.0 / self.probed as f64)
+    }
+}
+
+// Index of the block an address belongs to.
+pub fn block_of(blocks: &[TargetBlock], ip: Ipv4Addr) -> Option<usize> {
+    blocks.iter().position(|block| block.contains(ip))
+}
+
+pub fn by_block(scans: &[ScanResult], blocks: &[TargetBlock]) -> Vec<BlockSummary> {
+    let mut counts: Vec<BlockSummary> = blocks.iter()
+        .map(|block| BlockSummary { label: block.label.clone(), probed: 0, hosts: 0 })
+        .collect();
+    for scan in scans {
+        let probed: HashSet<Ipv4Addr> = scan.probes.iter().map(|(ip, _)| *ip).collect();
+        for ip in probed {
+            if let Some(index) = block_of(blocks, ip) {
+                counts[index].probed += 1;
+            }
+        }
+        for ip in scan.replies.keys() {
+            if let Some(index) = block_of(blocks, *ip) {
+                counts[index].hosts += 1;
+            }
+        }
+    }
+    counts
+}
+
+// The locally administered bit, set on randomized and most virtual MACs.
+pub fn is_randomized(mac: &MacAddr) -> bool {
+    MacAddress(*mac).is_local()
+}
+
+impl Summary {
+    pub fn from_scans<F: Fn(&MacAddr) -> String>(scans: &[ScanResult], vendor: F) -> Self {
+        let mut by_vendor: HashMap<String, usize> = HashMap::new();
+        let mut by_subnet: BTreeMap<Ipv4Addr, usize> = BTreeMap::new();
+        let mut summary = Summary {
+            hosts: 0,
+            probed: 0,
+            by_vendor: Vec::new(),
+            by_subnet: Vec::new(),
+            randomized: 0,
+            noted: 0,
+            rounds: scans.iter().map(|scan| scan.rounds).max().unwrap_or(1),
+            steady: 0,
+            by_attempt: Vec::new(),
+        };
+        let mut by_attempt: BTreeMap<u32, usize> = BTreeMap::new();
+        for scan in scans {
+            let probed: HashSet<Ipv4Addr> = scan.probes.iter().map(|(ip, _)| *ip).collect();
+            summary.probed += probed.len();
+            for reply in scan.replies.values() {
+                summary.hosts += 1;
+                *by_vendor.entry(vendor(&reply.mac)).or_insert(0) += 1;
+                let network = Ipv4Addr::from(u32::from(reply.ip) & 0xffff_ff00);
+                *by_subnet.entry(network).or_insert(0) += 1;
+                if is_randomized(&reply.mac) {
+                    summary.randomized += 1;
+                }
+                if reply.note.is_some() {
+                    summary.noted += 1;
+                }
+                if scan.responses.get(&reply.ip).copied().unwrap_or(1) >= scan.rounds {
+                    summary.steady += 1;
+                }
+                if let Some(attempt) = reply.attempt {
+                    *by_attempt.entry(attempt).or_insert(0) += 1;
+                }
+            }
+        }
+        summary.by_attempt = by_attempt.into_iter().collect();
+        summary.by_vendor = by_vendor.into_iter().collect();
+        summary.by_vendor.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        summary.by_subnet = by_subnet.into_iter()
+            .filter_map(|(network, count)| Some((CidrV4::new(network, 24).ok()?, count)))
+            .collect();
+        summary
+    }
+
+    // Share of probed addresses that answered, as a percentage.
+    pub fn response_rate(&self) -> Option<f64> {
+        (self.probed > 0).then(|| self.hosts as f64 * 100.0 / self.probed as f64)
+    }
+}
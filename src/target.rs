@@ -0,0 +1,760 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::BufRead;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+// An IPv4 network in CIDR notation. The address is always the network
+// address: host bits given when parsing (192.168.1.7/24) are cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CidrV4 {
+    network: Ipv4Addr,
+    prefix: u8,
+}
+
+impl CidrV4 {
+    pub fn new(ip: Ipv4Addr, prefix: u8) -> Result<Self, String> {
+        if prefix > 32 {
+            return Err("Subnet mask must be between 0 and 32".to_string());
+        }
+        Ok(CidrV4 {
+            network: u32_to_ip(ip_to_u32(ip) & prefix_mask(prefix)),
+            prefix,
+        })
+    }
+
+    pub fn parse(cidr: &str) -> Result<Self, String> {
+        let (ip, prefix) = cidr.split_once('/')
+            .ok_or_else(|| "Invalid CIDR format. Use: x.x.x.x/n".to_string())?;
+        let ip = Ipv4Addr::from_str(ip.trim())
+            .map_err(|e| format!("Invalid IP address: {}", e))?;
+        let prefix = prefix.trim();
+        let prefix = if prefix.contains('.') {
+            netmask_prefix(prefix)?
+        } else {
+            prefix.parse::<u8>()
+                .map_err(|e| format!("Invalid subnet mask: {}", e))?
+        };
+        CidrV4::new(ip, prefix)
+    }
+
+    pub fn network(&self) -> Ipv4Addr {
+        self.network
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        u32_to_ip(prefix_mask(self.prefix))
+    }
+
+    pub fn broadcast(&self) -> Ipv4Addr {
+        u32_to_ip(ip_to_u32(self.network) | !prefix_mask(self.prefix))
+    }
+
+    // Number of addresses in the block, including network and broadcast.
+    pub fn size(&self) -> u64 {
+        1 << (32 - self.prefix)
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        ip_to_u32(ip) & prefix_mask(self.prefix) == ip_to_u32(self.network)
+    }
+
+    // First and last usable host. The network and broadcast addresses are
+    // left out, except in a /31 (a point-to-point link, RFC 3021) and a
+    // /32, where every address is a host.
+    pub fn host_range(&self) -> (Ipv4Addr, Ipv4Addr) {
+        if self.prefix >= 31 {
+            (self.network, self.broadcast())
+        } else {
+            (u32_to_ip(ip_to_u32(self.network) + 1), u32_to_ip(ip_to_u32(self.broadcast()) - 1))
+        }
+    }
+
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let (first, last) = self.host_range();
+        (ip_to_u32(first)..=ip_to_u32(last)).map(u32_to_ip)
+    }
+}
+
+impl FromStr for CidrV4 {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        CidrV4::parse(s)
+    }
+}
+
+impl fmt::Display for CidrV4 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+// Mask with the top `prefix` bits set. A plain shift would overflow for /0.
+fn prefix_mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+// Prefix length of a dotted netmask such as 255.255.255.0. The set bits
+// must be contiguous.
+fn netmask_prefix(mask: &str) -> Result<u8, String> {
+    let bits = Ipv4Addr::from_str(mask)
+        .map(ip_to_u32)
+        .map_err(|e| format!("Invalid subnet mask '{}': {}", mask, e))?;
+    if bits.leading_ones() + bits.trailing_zeros() != 32 {
+        return Err(format!("Invalid subnet mask '{}': set bits are not contiguous", mask));
+    }
+    Ok(bits.leading_ones() as u8)
+}
+
+// The network address and prefix length of a CIDR block.
+pub fn parse_cidr(cidr: &str) -> Result<(Ipv4Addr, u32), String> {
+    let cidr = CidrV4::parse(cidr)?;
+    Ok((cidr.network(), cidr.prefix() as u32))
+}
+
+pub fn ip_to_u32(ip: Ipv4Addr) -> u32 {
+    u32::from_be_bytes(ip.octets())
+}
+
+pub fn u32_to_ip(n: u32) -> Ipv4Addr {
+    Ipv4Addr::from(n.to_be_bytes())
+}
+
+// An inclusive block of addresses, as integers.
+type Block = (u32, u32);
+
+fn cidr_block(cidr: &str) -> Result<Block, String> {
+    let (first, last) = CidrV4::parse(cidr)?.host_range();
+    Ok((ip_to_u32(first), ip_to_u32(last)))
+}
+
+// Ranges may be written in full (10.0.0.5-10.0.0.20) or with just the last
+// octet of the end address (10.0.0.5-20).
+fn range_block(range: &str) -> Result<Block, String> {
+    let (start, end) = range.split_once('-')
+        .ok_or_else(|| format!("Invalid range '{}'", range))?;
+    let start = Ipv4Addr::from_str(start.trim())
+        .map_err(|e| format!("Invalid IP address '{}': {}", start, e))?;
+    let end = end.trim();
+    let end = if end.contains('.') {
+        Ipv4Addr::from_str(end)
+            .map_err(|e| format!("Invalid IP address '{}': {}", end, e))?
+    } else {
+        let last = end.parse::<u8>()
+            .map_err(|e| format!("Invalid range end '{}': {}", end, e))?;
+        let o = start.octets();
+        Ipv4Addr::new(o[0], o[1], o[2], last)
+    };
+
+    let (start, end) = (ip_to_u32(start), ip_to_u32(end));
+    if end < start {
+        return Err(format!("Range '{}' ends before it starts", range));
+    }
+    Ok((start, end))
+}
+
+// A single target: a lone IP, a CIDR block, or a range.
+fn parse_block(target: &str) -> Result<Block, String> {
+    let target = target.trim();
+    if target.contains('/') {
+        cidr_block(target)
+    } else if target.contains('-') {
+        range_block(target)
+    } else {
+        Ipv4Addr::from_str(target)
+            .map(|ip| (ip_to_u32(ip), ip_to_u32(ip)))
+            .map_err(|e| format!("Invalid target '{}': {}", target, e))
+    }
+}
+
+// Looks a host name up, e.g. resolve::resolve_host.
+pub type Resolver<'a> = dyn FnMut(&str) -> Result<Vec<Ipv4Addr>, String> + 'a;
+
+fn no_resolver(name: &str) -> Result<Vec<Ipv4Addr>, String> {
+    Err(format!("Invalid target '{}': host names are not accepted here", name))
+}
+
+// Addresses never contain letters, so anything that does is a host name.
+fn is_hostname(target: &str) -> bool {
+    target.bytes().any(|b| b.is_ascii_alphabetic())
+}
+
+// A set of targets to scan, enumerated lazily so large blocks never have to
+// be held in memory. Addresses are yielded once each, in the order given,
+// minus any exclusions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetSpec {
+    include: Vec<Block>,
+    // Each include block's entry as written, for reporting
+    labels: Vec<String>,
+    exclude: Vec<Block>,
+    names: Vec<(Ipv4Addr, String)>,
+}
+
+impl TargetSpec {
+    // Comma-separated targets, e.g. "10.0.0.0/22,192.168.1.5". Entries
+    // starting with '!' are excluded instead: "10.0.0.0/24,!10.0.0.1".
+    pub fn parse(text: &str) -> Result<Self, String> {
+        TargetSpec::parse_with(text, &mut no_resolver)
+    }
+
+    // Like parse, but host names are accepted and looked up with `resolve`.
+    pub fn parse_with(text: &str, resolve: &mut Resolver) -> Result<Self, String> {
+        let mut spec = TargetSpec::default();
+        for entry in text.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            spec.add(entry, resolve)?;
+        }
+        Ok(spec)
+    }
+
+    fn add(&mut self, entry: &str, resolve: &mut Resolver) -> Result<(), String> {
+        let (target, excluded) = match entry.strip_prefix('!') {
+            Some(target) => (target.trim(), true),
+            None => (entry, false),
+        };
+        if !is_hostname(target) {
+            let block = parse_block(target)?;
+            if excluded {
+                self.exclude.push(block);
+            } else {
+                self.include.push(block);
+                self.labels.push(target.to_string());
+            }
+            return Ok(());
+        }
+        for ip in resolve(target)? {
+            let n = ip_to_u32(ip);
+            if excluded {
+                self.exclude.push((n, n));
+            } else {
+                self.include.push((n, n));
+                self.labels.push(target.to_string());
+                self.names.push((ip, target.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn include(&mut self, target: &str) -> Result<(), String> {
+        self.include.push(parse_block(target)?);
+        self.labels.push(target.trim().to_string());
+        Ok(())
+    }
+
+    pub fn exclude(&mut self, target: &str) -> Result<(), String> {
+        self.exclude.push(parse_block(target)?);
+        Ok(())
+    }
+
+    // Host names given as targets and the addresses they resolved to.
+    pub fn names(&self) -> &[(Ipv4Addr, String)] {
+        &self.names
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        let n = ip_to_u32(ip);
+        self.include.iter().any(|&(start, end)| (start..=end).contains(&n)) && !self.excluded(n)
+    }
+
+    fn excluded(&self, n: u32) -> bool {
+        self.exclude.iter().any(|&(start, end)| (start..=end).contains(&n))
+    }
+
+    // Whether `n`, found in include block `block`, should be yielded there:
+    // not excluded and not already covered by an earlier block.
+    fn yields(&self, block: usize, n: u32) -> bool {
+        !self.excluded(n) && !self.include[..block].iter().any(|&(start, end)| (start..=end).contains(&n))
+    }
+
+    // Number of addresses across the include blocks, before exclusions and
+    // duplicates are removed.
+    fn span(&self) -> u64 {
+        self.include.iter().map(|&(start, end)| (end - start) as u64 + 1).sum()
+    }
+
+    // The `index`th address across the include blocks, with its block.
+    fn nth_address(&self, mut index: u64) -> (usize, u32) {
+        for (block, &(start, end)) in self.include.iter().enumerate() {
+            let size = (end - start) as u64 + 1;
+            if index < size {
+                return (block, start + index as u32);
+            }
+            index -= size;
+        }
+        unreachable!("index beyond the target span")
+    }
+
+    // The include entries, with a host name that resolved to several
+    // addresses kept as one.
+    pub fn blocks(&self) -> Vec<TargetBlock> {
+        let mut blocks: Vec<TargetBlock> = Vec::new();
+        for (&(start, end), label) in self.include.iter().zip(&self.labels) {
+            let range = (u32_to_ip(start), u32_to_ip(end));
+            match blocks.iter_mut().find(|block| block.label == *label) {
+                Some(block) => block.ranges.push(range),
+                None => blocks.push(TargetBlock { label: label.clone(), ranges: vec![range] }),
+            }
+        }
+        blocks
+    }
+
+    // Addresses in the order given.
+    pub fn iter(&self) -> Targets<'_> {
+        Targets {
+            spec: self,
+            block: 0,
+            next: self.include.first().map_or(0, |&(start, _)| start as u64),
+        }
+    }
+
+    // The same addresses taking one from each include block in turn, so
+    // several blocks are swept side by side instead of one after another.
+    pub fn interleaved(&self) -> Interleaved<'_> {
+        Interleaved {
+            spec: self,
+            next: self.include.iter().map(|&(start, _)| start as u64).collect(),
+            block: 0,
+            live: self.include.len(),
+        }
+    }
+
+    // The same addresses in a pseudo-random order determined by `seed`,
+    // so sweeps don't walk the segment sequentially. Not cryptographic.
+    pub fn shuffled(&self, seed: u64) -> Shuffled<'_> {
+        let span = self.span();
+        let size = span.next_power_of_two();
+        Shuffled {
+            spec: self,
+            span,
+            mask: size - 1,
+            bits: size.trailing_zeros(),
+            increment: (seed << 1) | 1,
+            state: (seed.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 29) & (size - 1),
+            remaining: size,
+        }
+    }
+}
+
+pub struct Targets<'a> {
+    spec: &'a TargetSpec,
+    block: usize,
+    next: u64,
+}
+
+impl Iterator for Targets<'_> {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Ipv4Addr> {
+        loop {
+            let &(_, end) = self.spec.include.get(self.block)?;
+            if self.next > end as u64 {
+                self.block += 1;
+                self.next = self.spec.include.get(self.block).map_or(0, |&(start, _)| start as u64);
+                continue;
+            }
+            let n = self.next as u32;
+            self.next += 1;
+            if self.spec.yields(self.block, n) {
+                return Some(u32_to_ip(n));
+            }
+        }
+    }
+}
+
+pub struct Interleaved<'a> {
+    spec: &'a TargetSpec,
+    // The next address to try in each block
+    next: Vec<u64>,
+    block: usize,
+    // Blocks not yet exhausted
+    live: usize,
+}
+
+impl Iterator for Interleaved<'_> {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Ipv4Addr> {
+        while self.live > 0 {
+            let block = self.block;
+            self.block = (block + 1) % self.next.len();
+            let (_, end) = self.spec.include[block];
+            while self.next[block] <= end as u64 {
+                let n = self.next[block] as u32;
+                self.next[block] += 1;
+                if self.next[block] > end as u64 {
+                    self.live -= 1;
+                }
+                if self.spec.yields(block, n) {
+                    return Some(u32_to_ip(n));
+                }
+            }
+        }
+        None
+    }
+}
+
+// One target entry as given, e.g. "10.0.1.0/24" or a host name, with the
+// address ranges it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetBlock {
+    pub label: String,
+    pub ranges: Vec<(Ipv4Addr, Ipv4Addr)>,
+}
+
+impl TargetBlock {
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        self.ranges.iter().any(|(first, last)| (*first..=*last).contains(&ip))
+    }
+}
+
+// Walks a full-period LCG over the next power of two above the target
+// span, scrambling each state with a bijection and skipping values past
+// the span. Every index is visited exactly once.
+pub struct Shuffled<'a> {
+    spec: &'a TargetSpec,
+    span: u64,
+    mask: u64,
+    bits: u32,
+    increment: u64,
+    state: u64,
+    remaining: u64,
+}
+
+impl Iterator for Shuffled<'_> {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Ipv4Addr> {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            self.state = self.state
+                .wrapping_mul(0x5851_F42D_4C95_7F2D)
+                .wrapping_add(self.increment) & self.mask;
+            let mut index = self.state;
+            index ^= index >> (self.bits / 2 + 1);
+            index = index.wrapping_mul(0x9E37_79B9_7F4A_7C15) & self.mask;
+            if index >= self.span {
+                continue;
+            }
+            let (block, n) = self.spec.nth_address(index);
+            if self.spec.yields(block, n) {
+                return Some(u32_to_ip(n));
+            }
+        }
+        None
+    }
+}
+
+// Expand a target spec (see TargetSpec::parse) into its addresses.
+pub fn expand_target(target: &str) -> Result<Vec<Ipv4Addr>, String> {
+    Ok(TargetSpec::parse(target)?.iter().collect())
+}
+
+// Read targets one per line, ignoring blank lines and '#' comments.
+pub fn read_targets<R: BufRead>(reader: R) -> Result<Vec<String>, String> {
+    let mut targets = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(|e| format!("Failed to read targets: {}", e))?;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if !line.is_empty() {
+            targets.push(line.to_string());
+        }
+    }
+    Ok(targets)
+}
+
+// Combine several target specs, e.g. lines read with read_targets.
+pub fn parse_targets(targets: &[String], resolve: &mut Resolver) -> Result<TargetSpec, String> {
+    let mut spec = TargetSpec::default();
+    for target in targets {
+        let parsed = TargetSpec::parse_with(target, resolve)?;
+        spec.include.extend(parsed.include);
+        spec.labels.extend(parsed.labels);
+        spec.exclude.extend(parsed.exclude);
+        spec.names.extend(parsed.names);
+    }
+    Ok(spec)
+}
+
+// Expand every target and drop duplicates, keeping the first occurrence.
+pub fn expand_targets(targets: &[String]) -> Result<Vec<Ipv4Addr>, String> {
+    Ok(parse_targets(targets, &mut no_resolver)?.iter().collect())
+}
+
+// Addresses most likely to be a router or otherwise always-on: the first and
+// last two hosts of every /24 the targets touch. Used by the quick mode,
+// which only probes these and listens for everything else.
+pub fn gateway_candidates(targets: &[Ipv4Addr]) -> Vec<Ipv4Addr> {
+    let wanted: HashSet<Ipv4Addr> = targets.iter().copied().collect();
+    let mut blocks: Vec<u32> = targets.iter().map(|ip| ip_to_u32(*ip) & 0xFFFF_FF00).collect();
+    blocks.sort_unstable();
+    blocks.dedup();
+
+    blocks.iter()
+        .flat_map(|block| [1, 254, 2, 253].map(|host| u32_to_ip(block + host)))
+        .filter(|ip| wanted.contains(ip))
+        .collect()
+}
+
+// Directed broadcast addresses of the /24 blocks the targets touch.
+pub fn broadcast_addresses(targets: &[Ipv4Addr]) -> Vec<Ipv4Addr> {
+    let mut blocks: Vec<u32> = targets.iter().map(|ip| ip_to_u32(*ip) | 0xFF).collect();
+    blocks.sort_unstable();
+    blocks.dedup();
+    blocks.into_iter().map(u32_to_ip).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> CidrV4 {
+        CidrV4::parse(s).unwrap()
+    }
+
+    fn ip(s: &str) -> Ipv4Addr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn normalizes_host_bits() {
+        assert_eq!(cidr("192.168.1.77/24").network(), ip("192.168.1.0"));
+        assert_eq!(cidr("10.1.2.3/8").network(), ip("10.0.0.0"));
+        assert_eq!(cidr("10.1.2.3/32").network(), ip("10.1.2.3"));
+        assert_eq!(cidr("10.1.2.3/0").network(), ip("0.0.0.0"));
+        assert_eq!(cidr("192.168.1.77/24"), cidr("192.168.1.0/24"));
+        assert_eq!(cidr("192.168.1.77/24").to_string(), "192.168.1.0/24");
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        for bad in ["10.0.0.0", "10.0.0.0/33", "10.0.0.0/-1", "10.0.0.0/", "10.0.0/24", "10.0.0.0/24/1", "/24"] {
+            assert!(CidrV4::parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn netmask_notation() {
+        assert_eq!(cidr("192.168.1.0/255.255.255.0"), cidr("192.168.1.0/24"));
+        assert_eq!(cidr("10.9.8.7/255.255.240.0"), cidr("10.9.0.0/20"));
+        assert_eq!(cidr("10.0.0.1/0.0.0.0"), cidr("0.0.0.0/0"));
+        assert_eq!(cidr("10.0.0.1/255.255.255.255"), cidr("10.0.0.1/32"));
+        assert!(CidrV4::parse("10.0.0.0/255.0.255.0").is_err());
+        assert!(CidrV4::parse("10.0.0.0/0.255.255.255").is_err());
+        assert!(CidrV4::parse("10.0.0.0/255.255.256.0").is_err());
+    }
+
+    #[test]
+    fn prefix_zero() {
+        let all = cidr("0.0.0.0/0");
+        assert_eq!(all.netmask(), ip("0.0.0.0"));
+        assert_eq!(all.broadcast(), ip("255.255.255.255"));
+        assert_eq!(all.size(), 1 << 32);
+        assert!(all.contains(ip("203.0.113.9")));
+        assert_eq!(all.host_range(), (ip("0.0.0.1"), ip("255.255.255.254")));
+        assert_eq!(all.hosts().take(2).collect::<Vec<_>>(), vec![ip("0.0.0.1"), ip("0.0.0.2")]);
+    }
+
+    #[test]
+    fn prefix_one() {
+        let upper = cidr("200.0.0.0/1");
+        assert_eq!(upper.network(), ip("128.0.0.0"));
+        assert_eq!(upper.netmask(), ip("128.0.0.0"));
+        assert_eq!(upper.broadcast(), ip("255.255.255.255"));
+        assert!(!upper.contains(ip("127.255.255.255")));
+    }
+
+    #[test]
+    fn prefix_30() {
+        let link = cidr("10.0.0.6/30");
+        assert_eq!(link.network(), ip("10.0.0.4"));
+        assert_eq!(link.broadcast(), ip("10.0.0.7"));
+        assert_eq!(link.hosts().collect::<Vec<_>>(), vec![ip("10.0.0.5"), ip("10.0.0.6")]);
+    }
+
+    #[test]
+    fn prefix_31() {
+        let link = cidr("10.0.0.7/31");
+        assert_eq!(link.network(), ip("10.0.0.6"));
+        assert_eq!(link.netmask(), ip("255.255.255.254"));
+        assert_eq!(link.size(), 2);
+        assert_eq!(link.hosts().collect::<Vec<_>>(), vec![ip("10.0.0.6"), ip("10.0.0.7")]);
+    }
+
+    #[test]
+    fn prefix_32() {
+        let host = cidr("10.0.0.7/32");
+        assert_eq!(host.netmask(), ip("255.255.255.255"));
+        assert_eq!(host.broadcast(), ip("10.0.0.7"));
+        assert_eq!(host.size(), 1);
+        assert!(host.contains(ip("10.0.0.7")));
+        assert!(!host.contains(ip("10.0.0.6")));
+        assert_eq!(host.hosts().collect::<Vec<_>>(), vec![ip("10.0.0.7")]);
+    }
+
+    #[test]
+    fn top_of_address_space() {
+        let last = cidr("255.255.255.255/32");
+        assert_eq!(last.hosts().collect::<Vec<_>>(), vec![ip("255.255.255.255")]);
+        let last = cidr("255.255.255.254/31");
+        assert_eq!(last.hosts().count(), 2);
+        let last = cidr("255.255.255.0/24");
+        assert_eq!(last.host_range(), (ip("255.255.255.1"), ip("255.255.255.254")));
+    }
+
+    #[test]
+    fn every_prefix_is_consistent() {
+        for prefix in 0..=32u8 {
+            let block = CidrV4::new(ip("172.16.200.77"), prefix).unwrap();
+            assert!(block.contains(ip("172.16.200.77")));
+            assert_eq!(ip_to_u32(block.broadcast()) as u64 - ip_to_u32(block.network()) as u64 + 1, block.size());
+            let (first, last) = block.host_range();
+            let hosts = ip_to_u32(last) as u64 - ip_to_u32(first) as u64 + 1;
+            let expected = if prefix >= 31 { block.size() } else { block.size() - 2 };
+            assert_eq!(hosts, expected, "/{}", prefix);
+        }
+    }
+
+    #[test]
+    fn expands_targets() {
+        assert_eq!(expand_target("192.168.1.9/30").unwrap(), vec![ip("192.168.1.9"), ip("192.168.1.10")]);
+        assert_eq!(expand_target("192.168.1.9/32").unwrap(), vec![ip("192.168.1.9")]);
+        assert_eq!(expand_target(" 192.168.1.9 ").unwrap(), vec![ip("192.168.1.9")]);
+        assert_eq!(expand_target("10.0.0.0/24,!10.0.0.2-254").unwrap(), vec![ip("10.0.0.1")]);
+        assert!(expand_target("nas.local").is_err());
+    }
+
+    #[test]
+    fn resolves_host_names() {
+        let mut resolve = |name: &str| match name {
+            "nas.lan" => Ok(vec![ip("10.0.0.20")]),
+            "printer-2.lan" => Ok(vec![ip("10.0.0.30"), ip("10.0.0.31")]),
+            other => Err(format!("unknown {}", other)),
+        };
+        let spec = TargetSpec::parse_with("nas.lan, printer-2.lan, !10.0.0.31", &mut resolve).unwrap();
+        assert_eq!(spec.iter().collect::<Vec<_>>(), vec![ip("10.0.0.20"), ip("10.0.0.30")]);
+        assert_eq!(spec.names()[0], (ip("10.0.0.20"), "nas.lan".to_string()));
+        assert!(TargetSpec::parse_with("nope.lan", &mut resolve).is_err());
+    }
+
+    #[test]
+    fn interleaves_blocks() {
+        let spec = TargetSpec::parse("10.0.1.1-3, 10.0.2.1, 10.0.3.1-2, 10.0.1.2, !10.0.3.2").unwrap();
+        assert_eq!(
+            spec.interleaved().collect::<Vec<_>>(),
+            ["10.0.1.1", "10.0.2.1", "10.0.3.1", "10.0.1.2", "10.0.1.3"].map(ip).to_vec()
+        );
+        let blocks = spec.blocks();
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0].label, "10.0.1.1-3");
+        assert!(blocks[0].contains(ip("10.0.1.3")) && !blocks[1].contains(ip("10.0.1.3")));
+    }
+
+    // Properties over generated blocks and specs. Blocks stay small so
+    // every case can be enumerated in full.
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+        use std::collections::HashSet;
+
+        fn addr() -> impl Strategy<Value = Ipv4Addr> {
+            any::<u32>().prop_map(u32_to_ip)
+        }
+
+        // A block near `base` written in one of the accepted forms.
+        fn entry(base: u32) -> impl Strategy<Value = String> {
+            let base = base & 0xFFFF_FC00;
+            prop_oneof![
+                (0..1024u32).prop_map(move |n| u32_to_ip(base + n).to_string()),
+                (0..1024u32, 24..=32u8).prop_map(move |(n, prefix)| format!("{}/{}", u32_to_ip(base + n), prefix)),
+                (0..1024u32, 0..64u32).prop_map(move |(n, len)| {
+                    let start = base + n.min(1023 - len);
+                    format!("{}-{}", u32_to_ip(start), u32_to_ip(start + len))
+                }),
+            ]
+        }
+
+        fn spec() -> impl Strategy<Value = (String, TargetSpec)> {
+            any::<u32>()
+                .prop_flat_map(|base| {
+                    (prop::collection::vec(entry(base), 1..5), prop::collection::vec(entry(base), 0..3))
+                })
+                .prop_map(|(include, exclude)| {
+                    let excluded = exclude.iter().map(|e| format!("!{}", e));
+                    let text = include.iter().cloned().chain(excluded).collect::<Vec<_>>().join(",");
+                    let spec = TargetSpec::parse(&text).unwrap();
+                    (text, spec)
+                })
+        }
+
+        proptest! {
+            #[test]
+            fn cidr_round_trips(ip in addr(), prefix in 0..=32u8) {
+                let block = CidrV4::new(ip, prefix).unwrap();
+                prop_assert_eq!(CidrV4::parse(&block.to_string()).unwrap(), block);
+                prop_assert_eq!(CidrV4::parse(&format!("{}/{}", ip, block.netmask())).unwrap(), block);
+                prop_assert!(block.contains(ip));
+                prop_assert!(block.contains(block.network()) && block.contains(block.broadcast()));
+                prop_assert_eq!(ip_to_u32(block.network()) & !ip_to_u32(block.netmask()), 0);
+            }
+
+            #[test]
+            fn cidr_contains_matches_the_mask(block_ip in addr(), prefix in 0..=32u8, other in addr()) {
+                let block = CidrV4::new(block_ip, prefix).unwrap();
+                let mask = ip_to_u32(block.netmask());
+                prop_assert_eq!(block.contains(other), ip_to_u32(other) & mask == ip_to_u32(block_ip) & mask);
+            }
+
+            #[test]
+            fn ranges_expand_in_order(start in addr(), len in 0..512u32) {
+                let start_n = ip_to_u32(start).min(u32::MAX - len);
+                let (first, last) = (u32_to_ip(start_n), u32_to_ip(start_n + len));
+                let expanded = expand_target(&format!("{}-{}", first, last)).unwrap();
+                prop_assert_eq!(expanded.len() as u32, len + 1);
+                prop_assert_eq!(expanded.first(), Some(&first));
+                prop_assert!(expanded.windows(2).all(|pair| ip_to_u32(pair[1]) == ip_to_u32(pair[0]) + 1));
+            }
+
+            #[test]
+            fn iter_yields_each_contained_address_once((text, spec) in spec()) {
+                let yielded: Vec<Ipv4Addr> = spec.iter().collect();
+                let unique: HashSet<Ipv4Addr> = yielded.iter().copied().collect();
+                prop_assert_eq!(unique.len(), yielded.len(), "duplicates in {}", text);
+                prop_assert!(yielded.iter().all(|&ip| spec.contains(ip)), "excluded address yielded by {}", text);
+                let (low, high) = spec.include.iter().fold((u32::MAX, 0), |(low, high), &(start, end)| (low.min(start), high.max(end)));
+                let contained = (low..=high).filter(|&n| spec.contains(u32_to_ip(n))).count();
+                prop_assert_eq!(contained, yielded.len(), "missed addresses in {}", text);
+            }
+
+            #[test]
+            fn orderings_are_permutations((text, spec) in spec(), seed in any::<u64>()) {
+                let mut expected: Vec<Ipv4Addr> = spec.iter().collect();
+                expected.sort();
+                let mut shuffled: Vec<Ipv4Addr> = spec.shuffled(seed).collect();
+                shuffled.sort();
+                let mut interleaved: Vec<Ipv4Addr> = spec.interleaved().collect();
+                interleaved.sort();
+                prop_assert_eq!(&shuffled, &expected, "shuffled {}", text);
+                prop_assert_eq!(&interleaved, &expected, "interleaved {}", text);
+            }
+
+            #[test]
+            fn blocks_cover_every_target((text, spec) in spec()) {
+                let blocks = spec.blocks();
+                prop_assert!(spec.iter().all(|ip| blocks.iter().any(|block| block.contains(ip))), "{}", text);
+            }
+        }
+    }
+}
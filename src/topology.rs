@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::scan::ScanResult;
+
+// Guesses at the physical layout of a segment from reply latency. Every
+// switch a frame crosses adds a little store-and-forward delay, and Wi-Fi
+// adds a lot, with plenty of jitter. None of this is reliable: a busy host
+// answers slowly wherever it is. Hence hints, and off unless asked for.
+
+// Median above which a host is more likely wireless (or very busy) than
+// wired
+const WIRELESS_MEDIAN: Duration = Duration::from_micros(1500);
+// Spread between fastest and slowest reply that suggests a wireless hop,
+// when there are enough samples to tell
+const WIRELESS_JITTER: Duration = Duration::from_millis(1);
+const JITTER_SAMPLES: usize = 3;
+// A new group starts when the median jumps by this factor and by at least
+// this much in absolute terms
+const GAP_FACTOR: f64 = 1.5;
+const MIN_GAP: Duration = Duration::from_micros(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    // The fastest wired group: probably the same switch as the scanner
+    Nearest,
+    // A slower wired group; `hops` counts the groups in between
+    Farther { hops: usize },
+    // Slow or jittery enough to be Wi-Fi, a mesh hop, or a loaded host
+    Wireless,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostLatency {
+    pub ip: Ipv4Addr,
+    pub median: Duration,
+    // Slowest minus fastest reply
+    pub jitter: Duration,
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopologyGroup {
+    pub placement: Placement,
+    // Ordered by median latency
+    pub hosts: Vec<HostLatency>,
+}
+
+fn latency(ip: Ipv4Addr, samples: &[Duration]) -> Option<HostLatency> {
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let median = *sorted.get(sorted.len() / 2)?;
+    Some(HostLatency {
+        ip,
+        median,
+        jitter: sorted[sorted.len() - 1] - sorted[0],
+        samples: sorted.len(),
+    })
+}
+
+// Every answering host's latency samples. Scans loaded from a snapshot
+// only have the first reply's time.
+fn samples(scans: &[ScanResult]) -> HashMap<Ipv4Addr, Vec<Duration>> {
+    let mut samples: HashMap<Ipv4Addr, Vec<Duration>> = HashMap::new();
+    for scan in scans {
+        for (ip, reply) in &scan.replies {
+            match scan.rtts.get(ip) {
+                Some(rtts) => samples.entry(*ip).or_default().extend(rtts),
+                None => samples.entry(*ip).or_default().extend(reply.rtt),
+            }
+        }
+    }
+    samples
+}
+
+// Sort hosts into groups that probably sit at the same distance from us.
+// Wired hosts are split wherever the median latency jumps; hosts that look
+// wireless form one group of their own at the end.
+pub fn group_hosts(scans: &[ScanResult]) -> Vec<TopologyGroup> {
+    let mut hosts: Vec<HostLatency> = samples(scans).iter().filter_map(|(ip, rtts)| latency(*ip, rtts)).collect();
+    hosts.sort_by_key(|host| (host.median, host.ip));
+    let (wireless, wired): (Vec<HostLatency>, Vec<HostLatency>) = hosts.into_iter().partition(|host| {
+        host.median > WIRELESS_MEDIAN || (host.samples >= JITTER_SAMPLES && host.jitter > WIRELESS_JITTER.max(host.median))
+    });
+
+    let mut groups: Vec<TopologyGroup> = Vec::new();
+    for host in wired {
+        let jump = groups.last()
+            .and_then(|group| group.hosts.last())
+            .is_none_or(|last| {
+                host.median.as_secs_f64() > last.median.as_secs_f64() * GAP_FACTOR
+                    && host.median - last.median >= MIN_GAP
+            });
+        if jump {
+            let placement = match groups.len() {
+                0 => Placement::Nearest,
+                hops => Placement::Farther { hops },
+            };
+            groups.push(TopologyGroup { placement, hosts: Vec::new() });
+        }
+        if let Some(group) = groups.last_mut() {
+            group.hosts.push(host);
+        }
+    }
+    if !wireless.is_empty() {
+        groups.push(TopologyGroup { placement: Placement::Wireless, hosts: wireless });
+    }
+    groups
+}
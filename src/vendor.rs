@@ -0,0 +1,482 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use pnet_base::MacAddr;
+
+use crate::paths;
+
+// Anything that can turn a MAC address into a manufacturer name. The CLI
+// uses PrefixDb, but callers can plug in their own resolver.
+pub trait VendorDb {
+    fn lookup(&self, mac: &MacAddr) -> Option<&str>;
+
+    // ISO country code of the manufacturer's registered address, for
+    // databases that carry one.
+    fn country(&self, _mac: &MacAddr) -> Option<&str> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbFormat {
+    // IEEE registry export (oui.txt)
+    Ieee,
+    // Wireshark's manuf file
+    Wireshark,
+    // nmap's nmap-mac-prefixes file
+    Nmap,
+}
+
+// Vendor names keyed by MAC prefix. Prefixes may be longer than the usual
+// 24 bits (Wireshark and nmap both carry MA-M/MA-S assignments), so lookups
+// try the longest known prefix length first.
+#[derive(Debug, Default)]
+pub struct PrefixDb {
+    entries: HashMap<(u8, u64), String>,
+    // Only the IEEE registry lists addresses
+    countries: HashMap<(u8, u64), [u8; 2]>,
+    lengths: Vec<u8>,
+}
+
+fn mac_to_u64(mac: &MacAddr) -> u64 {
+    [mac.0, mac.1, mac.2, mac.3, mac.4, mac.5]
+        .iter()
+        .fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+pub fn u64_to_mac(value: u64) -> MacAddr {
+    let b = value.to_be_bytes();
+    MacAddr::new(b[2], b[3], b[4], b[5], b[6], b[7])
+}
+
+// A prefix as colon-separated bytes: "00:1b:c5" for a whole number of
+// bytes, otherwise all six with the length, "70:b3:d5:1f:30:00/36".
+pub fn format_prefix(prefix: u64, bits: u8) -> String {
+    let octets = u64_to_mac(prefix).octets();
+    let whole = bits.is_multiple_of(8);
+    let shown = if whole { (bits / 8).max(1) as usize } else { 6 };
+    let text: Vec<String> = octets[..shown].iter().map(|b| format!("{:02x}", b)).collect();
+    if whole {
+        text.join(":")
+    } else {
+        format!("{}/{}", text.join(":"), bits)
+    }
+}
+
+fn prefix_key(value: u64, bits: u8) -> u64 {
+    if bits == 0 {
+        0
+    } else {
+        value >> (48 - bits as u32)
+    }
+}
+
+impl PrefixDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, prefix: u64, bits: u8, vendor: String) {
+        let bits = bits.min(48);
+        if !self.lengths.contains(&bits) {
+            self.lengths.push(bits);
+            self.lengths.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        self.entries.insert((bits, prefix_key(prefix, bits)), vendor);
+    }
+
+    // Record the country for a prefix already inserted with the same length.
+    // Anything but a two-letter code is ignored.
+    pub fn insert_country(&mut self, prefix: u64, bits: u8, country: &str) {
+        if let [a, b] = *country.as_bytes() {
+            if a.is_ascii_uppercase() && b.is_ascii_uppercase() {
+                let bits = bits.min(48);
+                self.countries.insert((bits, prefix_key(prefix, bits)), [a, b]);
+            }
+        }
+    }
+
+    // The key of the longest prefix with a vendor entry for `mac`.
+    fn find(&self, mac: &MacAddr) -> Option<(u8, u64)> {
+        let value = mac_to_u64(mac);
+        self.lengths
+            .iter()
+            .map(|bits| (*bits, prefix_key(value, *bits)))
+            .find(|key| self.entries.contains_key(key))
+    }
+
+    // The vendor of a prefix `bits` long, from entries no longer than it:
+    // a 24-bit OUI shouldn't pick up a 36-bit assignment inside it.
+    pub fn lookup_prefix(&self, prefix: u64, bits: u8) -> Option<&str> {
+        self.lengths
+            .iter()
+            .filter(|length| **length <= bits)
+            .find_map(|length| self.entries.get(&(*length, prefix_key(prefix, *length))))
+            .map(String::as_str)
+    }
+
+    // Every entry whose vendor name contains `text`, ignoring case, as
+    // (prefix left-aligned in 48 bits, length, vendor) in prefix order.
+    pub fn search(&self, text: &str) -> Vec<(u64, u8, &str)> {
+        let text = text.to_lowercase();
+        let mut found: Vec<(u64, u8, &str)> = self
+            .entries
+            .iter()
+            .filter(|(_, vendor)| vendor.to_lowercase().contains(&text))
+            .map(|((bits, key), vendor)| (if *bits == 0 { 0 } else { key << (48 - *bits as u32) }, *bits, vendor.as_str()))
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader<R: BufRead>(mut reader: R) -> io::Result<Self> {
+        // The registry is not reliably UTF-8, so decode line by line and
+        // replace anything invalid rather than failing the whole load.
+        let mut lines = Vec::new();
+        let mut buf = Vec::new();
+        while reader.read_until(b'\n', &mut buf)? > 0 {
+            lines.push(String::from_utf8_lossy(&buf).into_owned());
+            buf.clear();
+        }
+        let format = detect_format(&lines);
+        Ok(Self::parse(&lines, format))
+    }
+
+    pub fn parse(lines: &[String], format: DbFormat) -> Self {
+        let mut db = Self::new();
+        match format {
+            DbFormat::Ieee => parse_ieee(lines, &mut db),
+            DbFormat::Wireshark => parse_wireshark(lines, &mut db),
+            DbFormat::Nmap => parse_nmap(lines, &mut db),
+        }
+        db
+    }
+}
+
+// The IEEE registry compiled into the binary, for installs that ship a
+// single executable.
+#[cfg(feature = "embed-oui")]
+pub const EMBEDDED_OUI: &[u8] = include_bytes!("files/oui.txt");
+
+pub const OUI_FILE_NAME: &str = "oui.txt";
+
+// Where to look for the vendor database, most specific first: $ARPSCAN_OUI,
+// the per-user data directory (XDG on Unix, %APPDATA% on Windows), next to
+// the executable, then the working directory.
+pub fn search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(path) = env::var_os("ARPSCAN_OUI").filter(|p| !p.is_empty()) {
+        paths.push(PathBuf::from(path));
+    }
+    if let Some(dir) = paths::data_dir() {
+        paths.push(dir.join(OUI_FILE_NAME));
+    }
+    if let Some(dir) = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        paths.push(dir.join(OUI_FILE_NAME));
+    }
+    paths.push(PathBuf::from(OUI_FILE_NAME));
+    paths
+}
+
+impl VendorDb for PrefixDb {
+    fn lookup(&self, mac: &MacAddr) -> Option<&str> {
+        self.find(mac).and_then(|key| self.entries.get(&key)).map(|s| s.as_str())
+    }
+
+    fn country(&self, mac: &MacAddr) -> Option<&str> {
+        let code = self.countries.get(&self.find(mac)?)?;
+        std::str::from_utf8(code).ok()
+    }
+}
+
+// Guess the file format from the first meaningful lines.
+pub fn detect_format(lines: &[String]) -> DbFormat {
+    for line in lines.iter().take(200) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.contains("(hex)") || line.contains("(base 16)") {
+            return DbFormat::Ieee;
+        }
+        let first = line.split_whitespace().next().unwrap_or("");
+        if first.contains(':') || (first.contains('-') && line.contains('\t')) {
+            return DbFormat::Wireshark;
+        }
+        if first.len() >= 6 && first.chars().all(|c| c.is_ascii_hexdigit()) {
+            return DbFormat::Nmap;
+        }
+    }
+    DbFormat::Ieee
+}
+
+// Parse a hex prefix like "00:1B:C5", "00-1B-C5" or "001BC5", optionally
+// followed by "/36". Returns the prefix left-aligned in 48 bits.
+pub fn parse_prefix(text: &str) -> Option<(u64, u8)> {
+    let (hex_part, mask) = match text.split_once('/') {
+        Some((h, m)) => (h, Some(m.parse::<u8>().ok()?)),
+        None => (text, None),
+    };
+    let digits: String = hex_part.chars().filter(|c| *c != ':' && *c != '-' && *c != '.').collect();
+    if digits.is_empty() || digits.len() > 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u64::from_str_radix(&digits, 16).ok()?;
+    let digit_bits = (digits.len() * 4) as u8;
+    let bits = mask.unwrap_or(digit_bits).min(48);
+    Some((value << (48 - digit_bits as u32), bits))
+}
+
+// Each entry is a (hex) line, a (base 16) line with the same prefix, then
+// the company's address, whose last line is the country code.
+fn parse_ieee(lines: &[String], db: &mut PrefixDb) {
+    let mut current_oui = String::new();
+    let mut address: Option<((u64, u8), &str)> = None;
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.contains("(hex)") {
+            if let Some(((prefix, bits), country)) = address.take() {
+                db.insert_country(prefix, bits, country);
+            }
+        }
+        if line.contains("(hex)") {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 3 {
+                current_oui = parts[0].replace('-', "").to_uppercase();
+            }
+        } else if !current_oui.is_empty() && line.contains("base 16") {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 4 {
+                if let Some((prefix, bits)) = parse_prefix(&current_oui) {
+                    db.insert(prefix, bits, parts[3..].join(" "));
+                    address = Some(((prefix, bits), ""));
+                }
+            }
+        } else if let Some((_, last)) = address.as_mut() {
+            if !line.is_empty() {
+                *last = line;
+            }
+        }
+    }
+    if let Some(((prefix, bits), country)) = address {
+        db.insert_country(prefix, bits, country);
+    }
+}
+
+fn parse_wireshark(lines: &[String], db: &mut PrefixDb) {
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').map(str::trim).filter(|f| !f.is_empty()).collect();
+        if fields.len() < 2 {
+            continue;
+        }
+        // Prefer the long name when present; strip any trailing comment.
+        let name = fields.get(2).unwrap_or(&fields[1]);
+        let name = name.split(" # ").next().unwrap_or(name).trim();
+        if let Some((prefix, bits)) = parse_prefix(fields[0]) {
+            db.insert(prefix, bits, name.to_string());
+        }
+    }
+}
+
+fn parse_nmap(lines: &[String], db: &mut PrefixDb) {
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((prefix, name)) = line.split_once(char::is_whitespace) {
+            if let Some((value, bits)) = parse_prefix(prefix) {
+                db.insert(value, bits, name.trim().to_string());
+            }
+        }
+    }
+}
+
+const LEGAL_SUFFIXES: &[&str] = &[
+    "co.,ltd", "co., ltd", "co ltd", "co.ltd", "co", "ltd", "limited", "inc", "incorporated",
+    "llc", "corp", "corporation", "gmbh", "s.a", "b.v", "ag", "plc", "s.r.l", "s.p.a", "oy",
+    "ab", "pte", "sdn bhd", "kg", "a/s", "pty",
+];
+
+fn strip_legal_suffixes(name: &str) -> &str {
+    let mut name = name;
+    loop {
+        let trimmed = name.trim_end_matches([' ', ',', '.', '&']);
+        let lower = trimmed.to_ascii_lowercase();
+        let stripped = LEGAL_SUFFIXES.iter().find_map(|suffix| {
+            let cut = lower.strip_suffix(suffix)?;
+            // Only strip whole words, and never the entire name.
+            if cut.trim_end_matches([' ', ',', '.', '&']).is_empty() {
+                return None;
+            }
+            if !cut.ends_with([' ', ',', '.']) {
+                return None;
+            }
+            Some(&trimmed[..cut.len()])
+        });
+        match stripped {
+            Some(rest) => name = rest,
+            None => return trimmed,
+        }
+    }
+}
+
+// Clean up a vendor name for display: drop control characters, trim and
+// collapse internal whitespace, and optionally remove legal suffixes such as
+// "Co., Ltd." or "Inc.".
+pub fn normalize_vendor(name: &str, strip_suffixes: bool) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_control() || c == '\u{FFFD}' { ' ' } else { c })
+        .collect();
+    let collapsed = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if strip_suffixes {
+        strip_legal_suffixes(&collapsed).to_string()
+    } else {
+        collapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split_inclusive('\n').map(str::to_string).collect()
+    }
+
+    fn db(text: &str) -> PrefixDb {
+        PrefixDb::from_reader(text.as_bytes()).unwrap()
+    }
+
+    fn mac(text: &str) -> MacAddr {
+        text.parse().unwrap()
+    }
+
+    // Excerpt of the IEEE registry as published: CRLF line endings, the
+    // column header, no-break spaces in one name and a Private entry
+    // without an address.
+    const IEEE: &str = "OUI/MA-L                                                    Organization                                 \r\n\
+        company_id                                                  Organization                                 \r\n\
+        \x20                                                           Address                                      \r\n\
+        \r\n\
+        90-56-07   (hex)\t\tSichuan\u{a0}AI-Link\u{a0}Technology\u{a0}Co.,\u{a0}Ltd.\r\n\
+        905607     (base 16)\t\tSichuan\u{a0}AI-Link\u{a0}Technology\u{a0}Co.,\u{a0}Ltd.\r\n\
+        \t\t\t\tAnzhou,Industrial Park\r\n\
+        \t\t\t\tAnzhou,Industrial Park  Sichuan  621000\r\n\
+        \t\t\t\tCN\r\n\
+        \r\n\
+        E4-F1-4C   (hex)\t\tPrivate\r\n\
+        E4F14C     (base 16)\t\tPrivate\r\n\
+        \r\n\
+        00-1B-21   (hex)\t\tIntel Corporate\r\n\
+        001B21     (base 16)\t\tIntel Corporate\r\n\
+        \t\t\t\tLot 8, Jalan Hi-Tech 2/3 \r\n\
+        \t\t\t\tKulim  Kedah  09000\r\n\
+        \t\t\t\tMY\r\n";
+
+    const WIRESHARK: &str = "# This file was generated by running ./tools/make-manuf.py.\n\
+        #\n\
+        00:00:0C\tCisco\tCisco Systems, Inc\n\
+        00:1B:21\tIntel\tIntel Corporate\n\
+        08:00:27\tPcsSystemtec\tPCS Systemtechnik GmbH\n\
+        70:B3:D5:1F:30:00/36\tSanminaIsrae\tSanmina Israel\n";
+
+    const NMAP: &str = "# $Id$ generated with make-mac-prefixes.pl\n\
+        000000 Xerox\n\
+        001B21 Intel Corporate\n\
+        0050C2C7C Scientific Instruments\n";
+
+    #[test]
+    fn detects_each_layout() {
+        assert_eq!(detect_format(&lines(IEEE)), DbFormat::Ieee);
+        assert_eq!(detect_format(&lines(WIRESHARK)), DbFormat::Wireshark);
+        assert_eq!(detect_format(&lines(NMAP)), DbFormat::Nmap);
+        assert_eq!(detect_format(&lines("# only comments\n\n")), DbFormat::Ieee);
+    }
+
+    #[test]
+    fn parses_ieee_registry() {
+        let db = db(IEEE);
+        assert_eq!(db.len(), 3);
+        assert_eq!(db.lookup(&mac("00:1b:21:12:34:56")), Some("Intel Corporate"));
+        assert_eq!(db.country(&mac("00:1b:21:12:34:56")), Some("MY"));
+        let sichuan = mac("90:56:07:00:00:01");
+        assert_eq!(
+            db.lookup(&sichuan).map(|name| normalize_vendor(name, false)).as_deref(),
+            Some("Sichuan AI-Link Technology Co., Ltd.")
+        );
+        assert_eq!(db.country(&sichuan), Some("CN"));
+        assert_eq!(db.lookup(&mac("e4:f1:4c:00:00:01")), Some("Private"));
+        assert_eq!(db.country(&mac("e4:f1:4c:00:00:01")), None);
+        assert_eq!(db.lookup(&mac("00:1b:22:00:00:01")), None);
+    }
+
+    #[test]
+    fn parses_wireshark_manuf() {
+        let db = db(WIRESHARK);
+        assert_eq!(db.len(), 4);
+        assert_eq!(db.lookup(&mac("00:00:0c:01:02:03")), Some("Cisco Systems, Inc"));
+        assert_eq!(db.lookup(&mac("08:00:27:12:34:56")), Some("PCS Systemtechnik GmbH"));
+        // The 36-bit block wins inside its range only
+        assert_eq!(db.lookup(&mac("70:b3:d5:1f:3a:bc")), Some("Sanmina Israel"));
+        assert_eq!(db.lookup(&mac("70:b3:d5:1f:40:00")), None);
+        assert_eq!(db.country(&mac("00:1b:21:00:00:01")), None);
+    }
+
+    #[test]
+    fn parses_nmap_prefixes() {
+        let db = db(NMAP);
+        assert_eq!(db.len(), 3);
+        assert_eq!(db.lookup(&mac("00:00:00:00:00:01")), Some("Xerox"));
+        assert_eq!(db.lookup(&mac("00:1b:21:ff:ff:ff")), Some("Intel Corporate"));
+        assert_eq!(db.lookup(&mac("00:50:c2:c7:c1:23")), Some("Scientific Instruments"));
+        assert_eq!(db.lookup(&mac("00:50:c2:c7:d1:23")), None);
+    }
+
+    #[test]
+    fn parses_prefix_spellings() {
+        assert_eq!(parse_prefix("00:1B:C5"), Some((0x001bc5 << 24, 24)));
+        assert_eq!(parse_prefix("00-1b-c5"), Some((0x001bc5 << 24, 24)));
+        assert_eq!(parse_prefix("0050C2C7C"), Some((0x0050c2c7c << 12, 36)));
+        assert_eq!(parse_prefix("70:B3:D5:1F:30:00/36"), Some((0x70b3d51f3000, 36)));
+        assert_eq!(parse_prefix(""), None);
+        assert_eq!(parse_prefix("00:1B:C5/x"), None);
+        assert_eq!(parse_prefix("Xerox"), None);
+        assert_eq!(format_prefix(0x70b3d51f3000, 36), "70:b3:d5:1f:30:00/36");
+    }
+
+    #[test]
+    fn normalizes_vendor_names() {
+        assert_eq!(normalize_vendor("  Intel \t Corporate\r\n", false), "Intel Corporate");
+        assert_eq!(normalize_vendor("Nokia Shanghai Bell Co., Ltd.", true), "Nokia Shanghai Bell");
+        assert_eq!(normalize_vendor("Sichuan\u{a0}AI-Link Technology Co.,\u{a0}Ltd.", true), "Sichuan AI-Link Technology");
+        assert_eq!(normalize_vendor("Espressif Inc.", true), "Espressif");
+        assert_eq!(normalize_vendor("Hewlett Packard Enterprise LLC", true), "Hewlett Packard Enterprise");
+        // Whole words only, and never the whole name
+        assert_eq!(normalize_vendor("Vivo Mobile Communication Co", true), "Vivo Mobile Communication");
+        assert_eq!(normalize_vendor("Ltd.", true), "Ltd");
+        assert_eq!(normalize_vendor("Cisco", true), "Cisco");
+        assert_eq!(normalize_vendor("Bad\u{fffd}Byte", false), "Bad Byte");
+    }
+}
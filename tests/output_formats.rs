@@ -0,0 +1,188 @@
+// Every output format rendered from a fixed snapshot and compared with the
+// files in tests/golden. The snapshot goes in with --load, so nothing here
+// touches the network or needs privileges. Downstream parsers depend on
+// these formats; after an intended change, regenerate the files with
+//   UPDATE_GOLDEN=1 cargo test --test output_formats
+// and review the diff.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("arp_scan-golden-{}-{}", std::process::id(), name))
+}
+
+// Run the binary over `snapshot` with a fixed vendor database and notes
+// file, returning stdout.
+fn render(snapshot: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_arp_scan"))
+        .arg("--load")
+        .arg(snapshot)
+        .arg("--oui-file")
+        .arg(fixture("oui.txt"))
+        .args(args)
+        .env("ARPSCAN_NOTES", fixture("notes.txt"))
+        .env_remove("ARPSCAN_OUI")
+        .output()
+        .expect("failed to run arp_scan");
+    assert!(
+        output.status.success(),
+        "arp_scan {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("output is not UTF-8")
+}
+
+// The version changes with every release, the formats shouldn't.
+fn normalize(text: &str) -> String {
+    text.replace(env!("CARGO_PKG_VERSION"), "{version}")
+}
+
+fn check(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+    let actual = normalize(actual);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", path.display(), e));
+    assert!(
+        expected == actual,
+        "{} differs from the golden file; if the change is intended, rerun with UPDATE_GOLDEN=1\n--- expected\n{}\n--- actual\n{}",
+        name,
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn table() {
+    check("table.txt", &render(&fixture("office.arps"), &[]));
+    check("table-summary.txt", &render(&fixture("office.arps"), &["--summary"]));
+    check("table-verbose.txt", &render(&fixture("office.arps"), &["--verbose"]));
+}
+
+#[test]
+fn json() {
+    check("scan.json", &render(&fixture("office.arps"), &["--output", "json", "--summary"]));
+}
+
+#[test]
+fn csv() {
+    check("scan.csv", &render(&fixture("office.arps"), &["--output", "csv"]));
+}
+
+#[test]
+fn markdown() {
+    check("scan.md", &render(&fixture("office.arps"), &["--output", "markdown"]));
+}
+
+#[test]
+fn html() {
+    let report = scratch("report.html");
+    render(&fixture("office.arps"), &["--output", "html", report.to_str().unwrap()]);
+    let html = fs::read_to_string(&report).unwrap();
+    let _ = fs::remove_file(&report);
+    check("report.html", &html);
+}
+
+#[test]
+fn template() {
+    check("template.txt", &render(&fixture("office.arps"), &["--format", "{ip}\\t{mac}\\t{vendor}\\t{rtt}"]));
+}
+
+#[test]
+fn exports() {
+    for (format, name) in [
+        ("ethers", "ethers.txt"),
+        ("dnsmasq", "dnsmasq.conf"),
+        ("ansible-inventory", "inventory.yml"),
+        ("graph", "map.dot"),
+        ("mermaid", "map.mmd"),
+    ] {
+        check(name, &render(&fixture("office.arps"), &["--export", format]));
+    }
+    // ethers and dnsmasq take either case but only colons
+    check("ethers-upper.txt", &render(&fixture("office.arps"), &["--export", "ethers", "--mac-format", "upper"]));
+}
+
+// The printer turns up on the dock two minutes after it was seen in the
+// office, so within a five minute window the two sightings are one device.
+// Without --roam-window every sighting is kept.
+#[test]
+fn merge_roaming() {
+    let office = fixture("office.arps");
+    let dock = fixture("dock.arps");
+    let render_merge = |args: &[&str]| {
+        let mut all = vec!["--load", dock.to_str().unwrap()];
+        all.extend_from_slice(args);
+        render(&office, &all)
+    };
+    check("merge-roamed.txt", &render_merge(&["--roam-window", "5m"]));
+    check("merge-apart.txt", &render_merge(&["--roam-window", "60"]));
+    assert_eq!(render_merge(&[]), render_merge(&["--roam-window", "60"]));
+    // A merge gets a fresh scan ID every time
+    let json = render_merge(&["--output", "json"]);
+    let id = json.find("\"scan_id\": \"").unwrap() + 12;
+    check("merge.json", &format!("{}{{scan_id}}{}", &json[..id], &json[id + 36..]));
+}
+
+// Run a subcommand that needs no snapshot, returning its exit status and
+// stdout.
+fn run(args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_arp_scan")).args(args).output().expect("failed to run arp_scan");
+    (output.status.code(), String::from_utf8(output.stdout).expect("output is not UTF-8"))
+}
+
+#[test]
+fn oui_lookup() {
+    let oui_file = fixture("oui.txt");
+    let oui = |args: &[&str]| {
+        let mut all = vec!["oui", "--oui-file", oui_file.to_str().unwrap()];
+        all.extend_from_slice(args);
+        run(&all)
+    };
+    let (status, lookup) = oui(&["00:1B:21:aa:bb:cc", "3cd92b", "0800.2700.0001", "11:22:33"]);
+    assert_eq!(status, Some(1), "an unknown address should fail the lookup");
+    let (status, search) = oui(&["--search", "GMBH"]);
+    assert_eq!(status, Some(0));
+    check("oui-lookup.txt", &format!("{}{}", lookup, search));
+}
+
+#[test]
+fn mac_utilities() {
+    let (status, valid) = run(&["mac", "validate", "00:1B:21:aa:bb:cc", "001b.21aa.bbcc", "02-00-5e-10-00-01", "ff:ff:ff:ff:ff:ff", "1:0:5e:0:0:fb"]);
+    assert_eq!(status, Some(0));
+    check("mac-validate.txt", &valid);
+    assert_eq!(run(&["mac", "validate", "00:1b:21", "00:1b:21:aa:bb:cc"]).0, Some(1));
+
+    let (status, random) = run(&["mac", "random", "--count", "20"]);
+    assert_eq!(status, Some(0));
+    for line in random.lines() {
+        let first = u8::from_str_radix(&line[..2], 16).unwrap();
+        assert_eq!(first & 0x03, 0x02, "{} is not a locally administered unicast address", line);
+    }
+    let (_, random) = run(&["mac", "random", "--vendor", "00-1B-21", "--count", "20", "--mac-format", "dash,upper"]);
+    assert_eq!(random.lines().count(), 20);
+    assert!(random.lines().all(|line| line.starts_with("00-1B-21-") && line.len() == 17), "{}", random);
+}
+
+// Saving what was loaded gives back the same snapshot, and the copy
+// renders exactly like the original.
+#[test]
+fn snapshot_round_trip() {
+    let copy = scratch("copy.arps");
+    render(&fixture("office.arps"), &["--output", "json", "--save", copy.to_str().unwrap()]);
+    let saved = fs::read_to_string(&copy).unwrap();
+    let rendered = render(&copy, &["--output", "json", "--summary"]);
+    let _ = fs::remove_file(&copy);
+    assert_eq!(saved, fs::read_to_string(fixture("office.arps")).unwrap());
+    check("scan.json", &rendered);
+}